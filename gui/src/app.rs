@@ -586,7 +586,9 @@ fn get_color_for_rule(rule: &EWarningRule, _is_dark_mode: bool) -> Color32 {
         }
         EWarningRule::Conflict(_) => Color32::YELLOW,
         EWarningRule::Requires(_) => Color32::RED,
+        EWarningRule::RequiresVersion(_) => Color32::RED,
         EWarningRule::Patch(_) => Color32::YELLOW,
+        EWarningRule::Blacklist(_) => Color32::RED,
     }
 }
 
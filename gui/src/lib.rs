@@ -7,8 +7,9 @@ use std::{env, path::Path, sync::mpsc::Sender};
 pub use app::TemplateApp;
 use log::{error, warn};
 use plox::{
-    conflict2, detect_game, download_latest_rules, gather_mods, get_default_rules_dir,
-    get_game_version,
+    conflict2, detect_game, download_latest_rules,
+    error::TopoError,
+    gather_mods, get_default_rules_dir, get_game_version,
     parser::{self, Warning},
     sorter::new_stable_sorter,
 };
@@ -78,7 +79,7 @@ fn init_parser(settings: AppSettings, tx: Sender<String>) -> Option<AppData> {
         let _ = tx.send(format!("Using game: {:?}", game));
         game
     } else {
-        match detect_game() {
+        match detect_game(Path::new(".")) {
             Some(g) => {
                 let _ = tx.send(format!("Detected game: {:?}", g));
                 g
@@ -159,11 +160,14 @@ fn init_parser(settings: AppSettings, tx: Sender<String>) -> Option<AppData> {
                     status = ELoadStatus::Success;
                 }
                 Err(e) => {
-                    let error_msg = format!("{e:?}");
-                    error!("error sorting: {error_msg}");
-
-                    // TODO better
-                    if error_msg.contains("Out of iterations") {
+                    error!("error sorting: {e}");
+
+                    if matches!(
+                        e,
+                        TopoError::Cycle(_)
+                            | TopoError::Contradiction(_, _)
+                            | TopoError::OutOfIterations
+                    ) {
                         let _ = tx.send("Cycle detected, skipping sort.".to_string());
                         status = ELoadStatus::Cycle;
                     } else {
@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use log::{error, info, warn};
+
+use crate::*;
+
+/// Evaluates the warning rules against the mods in root and prints the warnings.
+/// Fails if any warning of at least the fail_on severity applies, so a curated load order can be checked in CI
+pub fn check(
+    game: ESupportedGame,
+    game_folder: &Option<PathBuf>,
+    rules_path: &Option<String>,
+    format: EOutputFormat,
+    fail_on: ESeverity,
+) -> ExitCode {
+    // get game root
+    let root = match game_folder {
+        Some(path) => path.clone(),
+        None => env::current_dir().expect("No current working dir"),
+    };
+
+    // get default rules dir
+    let rules_dir = if let Some(path) = rules_path {
+        PathBuf::from(path)
+    } else {
+        get_default_rules_dir(game)
+    };
+
+    let warnings = match check_mods(game, &root, &rules_dir) {
+        Ok(warnings) => warnings,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match format {
+        EOutputFormat::Json => match serde_json::to_string_pretty(&warnings) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                error!("Could not serialize warnings: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        EOutputFormat::Text => {
+            for warning in &warnings {
+                let message = format!("[{}]\n{}", warning.rule.to_uppercase(), warning.comment);
+                match warning.severity {
+                    ESeverity::Info => info!("{}", message),
+                    ESeverity::Warning => warn!("{}", message),
+                    ESeverity::Error => error!("{}", message),
+                }
+                if !warning.missing.is_empty() {
+                    info!("Missing: [{}]", warning.missing.join(";"));
+                }
+                info!("Reference: [{}]", warning.plugins.join(";"));
+            }
+        }
+    }
+
+    check_exit_code(&warnings, fail_on)
+}
+
+/// Fails if any of the warnings is at least as serious as fail_on
+pub fn check_exit_code(warnings: &[parser::EvaluatedWarning], fail_on: ESeverity) -> ExitCode {
+    if warnings.iter().any(|w| w.severity >= fail_on) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Gathers the mods in root and evaluates the warning rules in rules_dir against them
+///
+/// # Errors
+///
+/// This function will return an error if parsing the rules fails
+pub fn check_mods(
+    game: ESupportedGame,
+    root: &Path,
+    rules_dir: &Path,
+) -> std::io::Result<Vec<parser::EvaluatedWarning>> {
+    let game_version = get_game_version(game);
+    let mods = gather_mods(&root, game, &game_version);
+
+    let mut parser = parser::get_parser(game, game_version);
+    parser.parse(rules_dir)?;
+
+    parser.evaluate_plugins(&mods);
+    Ok(parser
+        .warnings
+        .iter()
+        .map(parser::EvaluatedWarning::from)
+        .collect())
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use log::{error, info};
+
+use crate::*;
+
+/// Prints the changes sorting would make to the current load order without applying them
+pub fn diff_order(
+    game: ESupportedGame,
+    game_folder: &Option<PathBuf>,
+    rules_path: &Option<String>,
+    full: bool,
+) -> ExitCode {
+    // get game root
+    let root = match game_folder {
+        Some(path) => path.clone(),
+        None => env::current_dir().expect("No current working dir"),
+    };
+
+    // get default rules dir
+    let rules_dir = if let Some(path) = rules_path {
+        PathBuf::from(path)
+    } else {
+        get_default_rules_dir(game)
+    };
+
+    match gather_and_sort(game, &root, &rules_dir) {
+        Ok((current, proposed)) => {
+            if current == proposed {
+                info!("Mods are in correct order, no sorting needed.");
+                return ExitCode::SUCCESS;
+            }
+
+            for line in diff_lines(&current, &proposed, !full) {
+                println!("{}", line);
+            }
+
+            let moved = moved_plugins(&current, &proposed);
+            info!("{} plugins moved: {}", moved.len(), moved.join(", "));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Gathers the mods in root and sorts them with the rules in rules_dir.
+/// Returns the current and the sorted load order
+///
+/// # Errors
+///
+/// This function will return an error if no mods are found, parsing fails or the rules contain a cycle
+pub fn gather_and_sort(
+    game: ESupportedGame,
+    root: &Path,
+    rules_dir: &Path,
+) -> std::io::Result<(Vec<String>, Vec<String>)> {
+    let game_version = get_game_version(game);
+
+    let mods = gather_mods(&root, game, &game_version);
+    if mods.is_empty() {
+        return Err(std::io::Error::other("No mods found"));
+    }
+
+    let mut parser = parser::get_parser(game, game_version);
+    parser.parse(rules_dir)?;
+
+    let proposed = sorter::new_stable_sorter()
+        .topo_sort(game, &mods, &parser.order_rules, &parser.warning_rules)
+        .map_err(std::io::Error::other)?;
+    let current = mods.into_iter().map(|m| m.name).collect();
+
+    Ok((current, proposed))
+}
+
+/// Returns a unified-style diff of two load orders.
+/// Removed lines start with '-', added lines with '+' and unchanged lines with ' '.
+/// If elide_unchanged is set, runs of unchanged lines are replaced with a single "..." line
+pub fn diff_lines(current: &[String], proposed: &[String], elide_unchanged: bool) -> Vec<String> {
+    // longest common subsequence table
+    let n = current.len();
+    let m = proposed.len();
+    let mut lcs = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if current[i] == proposed[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let mut unchanged = vec![];
+    let flush = |lines: &mut Vec<String>, unchanged: &mut Vec<String>| {
+        if elide_unchanged {
+            if !unchanged.is_empty() {
+                lines.push("...".to_owned());
+            }
+        } else {
+            lines.append(unchanged);
+        }
+        unchanged.clear();
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && current[i] == proposed[j] {
+            unchanged.push(format!(" {}", current[i]));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            flush(&mut lines, &mut unchanged);
+            lines.push(format!("+{}", proposed[j]));
+            j += 1;
+        } else {
+            flush(&mut lines, &mut unchanged);
+            lines.push(format!("-{}", current[i]));
+            i += 1;
+        }
+    }
+    flush(&mut lines, &mut unchanged);
+
+    lines
+}
+
+/// Returns the plugins that changed their position relative to the other plugins
+pub fn moved_plugins(current: &[String], proposed: &[String]) -> Vec<String> {
+    diff_lines(current, proposed, true)
+        .into_iter()
+        .filter_map(|l| l.strip_prefix('+').map(|s| s.to_owned()))
+        .filter(|p| current.contains(p))
+        .collect()
+}
@@ -32,11 +32,8 @@ pub fn graph(
     if let Some(modlist_path) = mod_list {
         mods = read_file_as_list(modlist_path, &game_version);
     } else {
-        mods = match game {
-            ESupportedGame::Morrowind => gather_tes3_mods(&root),
-            ESupportedGame::Cyberpunk => gather_cp77_mods(&root, &game_version),
-            ESupportedGame::Openmw => gather_openmw_mods(),
-        };
+        // without the plugins disabled in disabled.txt
+        mods = gather_mods(&root, game, &game_version);
         if mods.is_empty() {
             info!("No mods found");
             return ExitCode::FAILURE;
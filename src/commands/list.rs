@@ -1,12 +1,17 @@
 use std::path::PathBuf;
 use std::process::ExitCode;
 
-use log::info;
+use log::{error, info};
 
 use crate::*;
 
 /// Lists the current mod load order
-pub fn list_mods(root: &Option<PathBuf>, game: ESupportedGame) -> ExitCode {
+pub fn list_mods(
+    root: &Option<PathBuf>,
+    game: ESupportedGame,
+    format: EOutputFormat,
+    list_format: EListFormat,
+) -> ExitCode {
     info!("Printing active mods...");
 
     let root = match root {
@@ -14,9 +19,19 @@ pub fn list_mods(root: &Option<PathBuf>, game: ESupportedGame) -> ExitCode {
         None => env::current_dir().expect("No current working dir"),
     };
 
-    for m in gather_mods(&root, game, &None) {
-        println!("{}", m.name);
-        //info!("{}", m);
+    let mods = gather_mods_detailed(&root, game);
+    match format {
+        EOutputFormat::Text => {
+            let names = mods.into_iter().map(|m| m.name).collect::<Vec<_>>();
+            print!("{}", format_mod_list(&names, list_format));
+        }
+        EOutputFormat::Json => match serde_json::to_string_pretty(&mods) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                error!("Could not serialize mods: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
     }
 
     ExitCode::SUCCESS
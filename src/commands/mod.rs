@@ -1,3 +1,6 @@
+pub mod diff;
+pub use diff::*;
+
 pub mod graph;
 pub use graph::*;
 
@@ -9,3 +12,12 @@ pub use verify::*;
 
 pub mod list;
 pub use list::*;
+
+pub mod parse;
+pub use parse::*;
+
+pub mod check;
+pub use check::*;
+
+pub mod watch;
+pub use watch::*;
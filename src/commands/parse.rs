@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::ExitCode;
+
+use log::{error, info};
+
+use crate::*;
+
+/// Syntax-checks a rules file without a game install.
+/// Prints the number of parsed rules per kind and all rules that failed to parse
+pub fn validate_rules(path: &Path, game: ESupportedGame) -> ExitCode {
+    info!("Validating rules from {} ...", path.display());
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Could not open {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // the game version is not needed to parse rules
+    let parser = parser::get_parser(game, None);
+    let (rules, errors) = match parser.parse_rules_with_errors(BufReader::new(file)) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Could not read {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for (name, count) in count_rules(&rules) {
+        info!("{}: {}", name, count);
+    }
+
+    for e in &errors {
+        error!("{}", e);
+    }
+
+    if errors.is_empty() {
+        info!("Validate SUCCESS: {} rules", rules.len());
+        ExitCode::SUCCESS
+    } else {
+        error!(
+            "Validate FAILURE: {} rules, {} errors",
+            rules.len(),
+            errors.len()
+        );
+        ExitCode::FAILURE
+    }
+}
+
+/// Counts the rules by kind
+pub fn count_rules(rules: &[ERule]) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for rule in rules {
+        let name = match rule {
+            ERule::EOrderRule(EOrderRule::Order(_)) => "Order",
+            ERule::EOrderRule(EOrderRule::NearStart(_)) => "NearStart",
+            ERule::EOrderRule(EOrderRule::NearEnd(_)) => "NearEnd",
+            ERule::EOrderRule(EOrderRule::Pin(_)) => "Pin",
+            ERule::EOrderRule(EOrderRule::Remove(_)) => "Remove",
+            ERule::EOrderRule(EOrderRule::Group(_)) => "Group",
+            ERule::EWarningRule(EWarningRule::Note(_)) => "Note",
+            ERule::EWarningRule(EWarningRule::Conflict(_)) => "Conflict",
+            ERule::EWarningRule(EWarningRule::Requires(_)) => "Requires",
+            ERule::EWarningRule(EWarningRule::Patch(_)) => "Patch",
+            ERule::EWarningRule(EWarningRule::RequiresVersion(_)) => "RequiresVersion",
+            ERule::EWarningRule(EWarningRule::Blacklist(_)) => "Blacklist",
+        };
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    counts
+}
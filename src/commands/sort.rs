@@ -1,5 +1,8 @@
 use std::process::ExitCode;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 use log::{debug, error, info, warn};
 
@@ -51,17 +54,20 @@ pub fn sort(options: CliSortOptions) -> ExitCode {
     if let Some(modlist_path) = mod_list {
         mods = read_file_as_list(modlist_path, &game_version);
     } else {
-        mods = match game {
-            ESupportedGame::Morrowind => gather_tes3_mods(&root),
-            ESupportedGame::Cyberpunk => gather_cp77_mods(&root, &game_version),
-            ESupportedGame::Openmw => gather_openmw_mods(),
-        };
+        // without the plugins disabled in disabled.txt
+        mods = gather_mods(&root, game, &game_version);
         if mods.is_empty() {
             info!("No mods found");
             return ExitCode::FAILURE;
         }
     }
 
+    // check for mods that only differ by case
+    let names = mods.iter().map(|m| m.name.to_owned()).collect::<Vec<_>>();
+    for (a, b) in detect_case_conflicts(&names) {
+        warn!("Mods only differ by case: {} and {}", a, b);
+    }
+
     let mut parser = parser::get_parser(game, game_version);
     if let Err(e) = parser.parse(rules_dir) {
         error!("Parser init failed: {}", e);
@@ -89,12 +95,23 @@ pub fn sort(options: CliSortOptions) -> ExitCode {
                 }
                 EWarningRule::Requires(r) => {
                     error!("[REQUIRES]\n{}", r.get_comment());
+                    if let Some(message) = r.missing_message() {
+                        error!("{}", message);
+                    }
                     info!("Reference: [{}]", r.plugins.join(";"));
                 }
                 EWarningRule::Patch(p) => {
                     warn!("[Patch]\n{}", p.get_comment());
                     info!("Reference: [{}]", p.plugins.join(";"));
                 }
+                EWarningRule::RequiresVersion(r) => {
+                    error!("[REQUIRESVERSION]\n{}", r.get_comment());
+                    info!("Reference: [{}]", r.plugins.join(";"));
+                }
+                EWarningRule::Blacklist(b) => {
+                    error!("[BLACKLIST]\n{}", b.get_comment());
+                    info!("Reference: [{}]", b.plugins.join(";"));
+                }
             }
             println!();
         }
@@ -156,7 +173,7 @@ pub fn sort(options: CliSortOptions) -> ExitCode {
                     } else {
                         info!("New:\n{:?}", result);
 
-                        match update_new_load_order(game, &result) {
+                        match write_load_order(&root, game, &result) {
                             Ok(_) => {
                                 info!("Update successful");
                                 ExitCode::SUCCESS
@@ -176,3 +193,27 @@ pub fn sort(options: CliSortOptions) -> ExitCode {
         }
     }
 }
+
+/// Prints the sorted load order, one plugin per line, without changing the current load order.
+/// Fails if no mods are found, the rules fail to parse or contain a cycle
+pub fn sort_mods(root: &Option<PathBuf>, game: ESupportedGame, rules_dir: &Path) -> ExitCode {
+    // get game root
+    let root = match root {
+        Some(path) => path.clone(),
+        None => env::current_dir().expect("No current working dir"),
+    };
+
+    match gather_and_sort(game, &root, rules_dir) {
+        Ok((_, proposed)) => {
+            for plugin in proposed {
+                println!("{}", plugin);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            // a cycle error lists the plugins in cycle order
+            error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+
+use crate::*;
+
+/// How often the rules file is checked for changes
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Validates a rules file and validates it again whenever it changes, until the process is stopped.
+/// The file may be deleted and recreated, e.g. by editors that save atomically
+pub fn watch_rules(path: &Path, game: ESupportedGame) -> ExitCode {
+    watch_rules_for(path, game, WATCH_INTERVAL, None)
+}
+
+/// Validates a rules file and validates it again whenever it changes.
+/// Returns the result of the last validation after max_runs re-validations, or never if max_runs is None
+pub fn watch_rules_for(
+    path: &Path,
+    game: ESupportedGame,
+    interval: Duration,
+    max_runs: Option<usize>,
+) -> ExitCode {
+    info!("Watching {} for changes ...", path.display());
+
+    let mut state = file_state(path);
+    let mut result = validate_rules(path, game);
+    let mut runs = 0;
+    while max_runs.is_none_or(|max| runs < max) {
+        thread::sleep(interval);
+
+        let new_state = file_state(path);
+        if new_state == state {
+            continue;
+        }
+        state = new_state;
+
+        // a missing file is waited for, it is validated again once it is recreated
+        if state.is_none() {
+            warn!(
+                "{} was removed, waiting for it to be recreated",
+                path.display()
+            );
+            continue;
+        }
+
+        info!("{} changed", path.display());
+        result = validate_rules(path, game);
+        runs += 1;
+    }
+
+    result
+}
+
+/// The modification time and size of a file, or None if it does not exist
+fn file_state(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
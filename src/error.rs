@@ -0,0 +1,113 @@
+////////////////////////////////////////////////////////////////////////
+// ERRORS
+////////////////////////////////////////////////////////////////////////
+
+use std::fmt::Display;
+
+/// The reason a rule failed to parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// the chunk does not start with a rule header
+    NotARuleStart,
+    /// the rule header is not a known rule
+    UnknownRule,
+    /// the rule has no body
+    EmptyBody,
+    /// the rule body is not valid for this rule
+    MalformedRule(String),
+    /// an expression inside the rule body could not be parsed
+    MalformedExpression(String),
+    /// expressions are nested deeper than the parser allows
+    MaxDepthExceeded(usize),
+    /// any other error, e.g. IO
+    Other(String),
+}
+
+impl ParseError {
+    /// Extracts a ParseError from an io error, falling back to ParseError::Other
+    pub fn from_io(err: &std::io::Error) -> Self {
+        if let Some(e) = err.get_ref().and_then(|e| e.downcast_ref::<ParseError>()) {
+            e.clone()
+        } else {
+            ParseError::Other(err.to_string())
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::NotARuleStart => write!(f, "Parsing error: Not a rule start"),
+            ParseError::UnknownRule => write!(f, "Parsing error: unknown rule"),
+            ParseError::EmptyBody => write!(f, "Parsing error: empty rule body"),
+            ParseError::MalformedRule(msg) => write!(f, "{}", msg),
+            ParseError::MalformedExpression(msg) => write!(f, "{}", msg),
+            ParseError::MaxDepthExceeded(depth) => {
+                write!(f, "Parsing error: expressions nested deeper than {}", depth)
+            }
+            ParseError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A rule that failed to parse and where it is located in the rules file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleParseError {
+    /// the rule kind from the header, e.g. "order" or "note"
+    pub rule: Option<String>,
+    /// the line the rule starts at
+    pub line: usize,
+    pub error: ParseError,
+}
+
+impl Display for RuleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.rule {
+            Some(rule) => write!(f, "[{}] at line {}: {}", rule, self.line, self.error),
+            None => write!(f, "line {}: {}", self.line, self.error),
+        }
+    }
+}
+
+/// The reason sorting failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopoError {
+    /// there are no order rules to sort by
+    NoRules,
+    /// the order rules contain a cycle, the plugins are listed in cycle order
+    Cycle(Vec<String>),
+    /// the stable sort did not converge
+    OutOfIterations,
+    /// two pin rules target the same index
+    PinConflict(usize, String, String),
+    /// two order rules put the same pair of plugins in opposite orders
+    Contradiction(String, String),
+}
+
+impl Display for TopoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopoError::NoRules => write!(f, "No order rules found"),
+            TopoError::Cycle(cycle) => write!(f, "Graph contains a cycle: {}", cycle.join(" -> ")),
+            TopoError::OutOfIterations => write!(f, "Out of iterations"),
+            TopoError::PinConflict(index, a, b) => {
+                write!(
+                    f,
+                    "Pin conflict: {} and {} are both pinned to index {}",
+                    a, b, index
+                )
+            }
+            TopoError::Contradiction(a, b) => {
+                write!(
+                    f,
+                    "Contradicting order rules: {} loads both before and after {}",
+                    a, b
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TopoError {}
@@ -2,19 +2,215 @@
 // EXPRESSIONS
 ////////////////////////////////////////////////////////////////////////
 
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
 
+use regex::Regex;
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 
-use crate::{wild_contains, wild_contains_data, PluginData};
+use crate::{wild_contains, PluginData};
 
 // An expression may be evaluated against a load order
 pub trait TExpression {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>>;
+    /// Returns the plugins that satisfied the expression, or None if the expression is false
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>>;
+
+    /// Convenience wrapper around eval that builds the context from the plugins.
+    /// Build an EvalContext once instead when evaluating many expressions
+    fn eval_plugins(&self, items: &[PluginData]) -> Option<Vec<String>> {
+        self.eval(&EvalContext::from_plugins(items))
+    }
+
+    /// Evaluates the expression against mod names with an in-memory map of plugin descriptions,
+    /// e.g. to evaluate [DESC] without reading plugin headers. Descriptions are looked up case-insensitively
+    fn eval_with_descriptions(
+        &self,
+        mods: &[String],
+        descriptions: &HashMap<String, String>,
+    ) -> Option<Vec<String>> {
+        self.eval(&EvalContext::from_mods(mods).with_descriptions(descriptions))
+    }
+}
+
+/// The inputs of an evaluation: the indexed mods with their descriptions, sizes and versions.
+/// Build one from mod names and add the data the predicates need, e.g. DESC needs descriptions,
+/// SIZE needs sizes and VER versions. Names and map keys are compared case-insensitively.
+/// The index is built once, share the context when evaluating many expressions
+#[derive(Debug, Clone)]
+pub struct EvalContext {
+    index: ModIndex,
+}
+
+impl EvalContext {
+    /// A context with just the mod names, without descriptions, sizes or versions.
+    /// The names are kept as given like from_plugins
+    pub fn from_mods(mods: &[String]) -> Self {
+        let plugins = mods
+            .iter()
+            .map(|m| PluginData::new(m.to_owned(), 0))
+            .collect();
+        Self {
+            index: ModIndex::new(plugins),
+        }
+    }
+
+    /// A context with the gathered plugin data
+    pub fn from_plugins(plugins: &[PluginData]) -> Self {
+        Self {
+            index: ModIndex::new(plugins.to_vec()),
+        }
+    }
+
+    /// Matches plugin names exactly instead of case-insensitively
+    pub fn exact(mut self) -> Self {
+        self.index.case_insensitive = false;
+        self
+    }
+
+    /// Sets the description of the plugins in the map
+    pub fn with_descriptions(mut self, descriptions: &HashMap<String, String>) -> Self {
+        self.set_each(descriptions, |p, d| p.description = Some(d.to_owned()));
+        self
+    }
+
+    /// Sets the file size of the plugins in the map
+    pub fn with_sizes(mut self, sizes: &HashMap<String, u64>) -> Self {
+        self.set_each(sizes, |p, size| p.size = *size);
+        self
+    }
+
+    /// Sets the version of the plugins in the map
+    pub fn with_versions(mut self, versions: &HashMap<String, semver::Version>) -> Self {
+        self.set_each(versions, |p, v| p.version = Some(v.clone()));
+        self
+    }
+
+    /// The lookup of the mods the expressions are evaluated with
+    pub fn index(&self) -> &ModIndex {
+        &self.index
+    }
+
+    pub fn plugins(&self) -> &[PluginData] {
+        &self.index.plugins
+    }
+
+    /// Changes plugin data in place, the names and with them the lookup stay the same
+    fn set_each<T, F>(&mut self, values: &HashMap<String, T>, mut set: F)
+    where
+        F: FnMut(&mut PluginData, &T),
+    {
+        let values = values
+            .iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .collect::<HashMap<_, _>>();
+        for plugin in &mut self.index.plugins {
+            if let Some(value) = values.get(&plugin.name.to_lowercase()) {
+                set(plugin, value);
+            }
+        }
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A lookup of the installed plugins, built once per EvalContext and shared by all evaluated expressions
+#[derive(Debug, Clone)]
+pub struct ModIndex {
+    plugins: Vec<PluginData>,
+    names: Vec<String>,
+    lowercase_names: Vec<String>,
+    by_name: HashMap<String, usize>,
+    by_lowercase_name: HashMap<String, usize>,
+    /// compare plugin names case-insensitively, rules are lowercased by the parser
+    case_insensitive: bool,
+}
+
+impl ModIndex {
+    /// Builds an index that matches plugin names case-insensitively
+    fn new(plugins: Vec<PluginData>) -> Self {
+        let names = plugins
+            .iter()
+            .map(|f| f.name.to_owned())
+            .collect::<Vec<_>>();
+        let lowercase_names = names.iter().map(|f| f.to_lowercase()).collect::<Vec<_>>();
+        let mut by_name = HashMap::new();
+        let mut by_lowercase_name = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            by_name.entry(name.to_owned()).or_insert(i);
+            by_lowercase_name
+                .entry(lowercase_names[i].to_owned())
+                .or_insert(i);
+        }
+
+        Self {
+            plugins,
+            names,
+            lowercase_names,
+            by_name,
+            by_lowercase_name,
+            case_insensitive: true,
+        }
+    }
+
+    pub fn plugins(&self) -> &[PluginData] {
+        &self.plugins
+    }
+
+    /// Exact indices match names case-sensitively
+    pub fn is_exact(&self) -> bool {
+        !self.case_insensitive
+    }
+
+    fn is_wildcard(item: &str) -> bool {
+        item.contains('*') || item.contains('?') || item.contains("<ver>")
+    }
+
+    /// Returns the names of all plugins matching the item, which may contain wildcards.
+    /// The names are returned as installed, i.e. with their original case
+    pub fn contains(&self, item: &str) -> Option<Vec<String>> {
+        if !self.case_insensitive {
+            if Self::is_wildcard(item) {
+                return wild_contains(&self.names, &item.to_owned());
+            }
+            return self.by_name.get(item).map(|_| vec![item.to_owned()]);
+        }
+
+        let item = item.to_lowercase();
+        if Self::is_wildcard(&item) {
+            let mut results: Vec<String> = vec![];
+            for name in wild_contains(&self.lowercase_names, &item)? {
+                union(
+                    &mut results,
+                    vec![self.names[self.by_lowercase_name[&name]].to_owned()],
+                );
+            }
+            return Some(results);
+        }
+        self.by_lowercase_name
+            .get(&item)
+            .map(|i| vec![self.names[*i].to_owned()])
+    }
+
+    /// Returns all plugins matching the item like contains, which may contain wildcards
+    pub fn contains_data(&self, item: &str) -> Option<Vec<PluginData>> {
+        let names = self.contains(item)?;
+        Some(
+            names
+                .iter()
+                .map(|name| self.plugins[self.by_name[name]].to_owned())
+                .collect(),
+        )
+    }
+}
+
+/// Adds all plugins that are not yet in results
+fn union(results: &mut Vec<String>, plugins: Vec<String>) {
+    for p in plugins {
+        if !results.contains(&p) {
+            results.push(p);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
     Atomic(Atomic),
     ALL(ALL),
@@ -42,16 +238,58 @@ impl Display for Expression {
     }
 }
 impl TExpression for Expression {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
+        match self {
+            Expression::Atomic(x) => x.eval(context),
+            Expression::ALL(x) => x.eval(context),
+            Expression::ANY(x) => x.eval(context),
+            Expression::NOT(x) => x.eval(context),
+            Expression::DESC(x) => x.eval(context),
+            Expression::SIZE(x) => x.eval(context),
+            Expression::VER(x) => x.eval(context),
+            Expression::GVER(x) => x.eval(context),
+        }
+    }
+}
+
+impl Expression {
+    /// Calls f for every atomic in the expression tree, depth first
+    pub fn visit_atomics<F>(&self, f: &mut F)
+    where
+        F: FnMut(&Atomic),
+    {
+        match self {
+            Expression::Atomic(x) => f(x),
+            Expression::ALL(x) => x.expressions.iter().for_each(|e| e.visit_atomics(f)),
+            Expression::ANY(x) => x.expressions.iter().for_each(|e| e.visit_atomics(f)),
+            Expression::NOT(x) => x.expression.visit_atomics(f),
+            Expression::DESC(x) => f(&x.expression),
+            Expression::SIZE(x) => f(&x.expression),
+            Expression::VER(x) => f(&x.expression),
+            Expression::GVER(x) => f(&x.expression),
+        }
+    }
+
+    /// Calls f for every atomic in the expression like visit_atomics, the atomics may be changed
+    pub fn visit_atomics_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Atomic),
+    {
         match self {
-            Expression::Atomic(x) => x.eval(items),
-            Expression::ALL(x) => x.eval(items),
-            Expression::ANY(x) => x.eval(items),
-            Expression::NOT(x) => x.eval(items),
-            Expression::DESC(x) => x.eval(items),
-            Expression::SIZE(x) => x.eval(items),
-            Expression::VER(x) => x.eval(items),
-            Expression::GVER(x) => x.eval(items),
+            Expression::Atomic(x) => f(x),
+            Expression::ALL(x) => x
+                .expressions
+                .iter_mut()
+                .for_each(|e| e.visit_atomics_mut(f)),
+            Expression::ANY(x) => x
+                .expressions
+                .iter_mut()
+                .for_each(|e| e.visit_atomics_mut(f)),
+            Expression::NOT(x) => x.expression.visit_atomics_mut(f),
+            Expression::DESC(x) => f(&mut x.expression),
+            Expression::SIZE(x) => f(&mut x.expression),
+            Expression::VER(x) => f(&mut x.expression),
+            Expression::GVER(x) => f(&mut x.expression),
         }
     }
 }
@@ -110,37 +348,70 @@ impl From<GVER> for Expression {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Atomic {
     pub item: String,
+    /// the name as written in the rules file, if it differs from the lowercase item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
 }
 
 impl Atomic {
     pub fn get_item(&self) -> String {
         self.item.to_owned()
     }
+
+    /// The name as written in the rules file, matching uses the lowercase item unless the context is exact
+    pub fn display_name(&self) -> &str {
+        self.display.as_deref().unwrap_or(&self.item)
+    }
+
+    /// The name to look up in the context, exact contexts use the name as written in the rules file
+    fn match_name(&self, context: &EvalContext) -> &str {
+        if context.index().is_exact() {
+            self.display_name()
+        } else {
+            &self.item
+        }
+    }
+
+    /// Returns the data of all plugins matching the atomic, used by the predicates
+    fn eval_data(&self, context: &EvalContext) -> Option<Vec<PluginData>> {
+        context.index().contains_data(self.match_name(context))
+    }
+}
+
+/// Atomics are equal if they match the same plugins, the display case is ignored
+impl PartialEq for Atomic {
+    fn eq(&self, other: &Self) -> bool {
+        self.item == other.item
+    }
 }
 impl TExpression for Atomic {
-    /// atomics evaluate as true if the input list contains the item
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
-        wild_contains(
-            &items.iter().map(|f| f.name.to_owned()).collect::<Vec<_>>(),
-            &self.item,
-        )
+    /// atomics evaluate as true if the input list contains the item.
+    /// The parser lowercases the item, exact matching uses the name as written in the rules file
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
+        context.index().contains(self.match_name(context))
     }
 }
 
 impl From<&str> for Atomic {
     fn from(value: &str) -> Self {
-        Atomic { item: value.into() }
+        Atomic {
+            item: value.into(),
+            display: None,
+        }
     }
 }
 impl From<String> for Atomic {
     fn from(value: String) -> Self {
-        Atomic { item: value }
+        Atomic {
+            item: value,
+            display: None,
+        }
     }
 }
 
 impl Display for Atomic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.item)
+        write!(f, "{}", self.display_name())
     }
 }
 
@@ -148,8 +419,9 @@ impl Display for Atomic {
 // ALL
 
 /// The ALL expression
-/// ALL evaluates as true if all expressions evaluate as true
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// ALL evaluates as true if all expressions evaluate as true.
+/// An ALL without expressions is vacuously true, the parser rejects empty bodies
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ALL {
     pub expressions: Vec<Expression>,
 }
@@ -160,13 +432,13 @@ impl ALL {
 }
 impl TExpression for ALL {
     /// ALL evaluates as true if all expressions evaluate as true
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         let mut result = true;
         let mut results: Vec<String> = vec![];
 
         for e in &self.expressions {
-            if let Some(plugins) = e.eval(items) {
-                results.extend(plugins);
+            if let Some(plugins) = e.eval(context) {
+                union(&mut results, plugins);
             } else {
                 // any failure can set it to false
                 result = false;
@@ -199,8 +471,9 @@ impl Display for ALL {
 // ANY
 
 /// The ANY expression (OR)
-/// ANY evaluates as true if any expressions evaluates as true
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// ANY evaluates as true if any expressions evaluates as true.
+/// An ANY without expressions is false, the parser rejects empty bodies
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ANY {
     pub expressions: Vec<Expression>,
 }
@@ -211,14 +484,14 @@ impl ANY {
 }
 impl TExpression for ANY {
     // ANY evaluate as true if any expressions evaluates as true
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         let mut result = false;
         let mut results: Vec<String> = vec![];
 
         for e in &self.expressions {
-            if let Some(plugins) = e.eval(items) {
+            if let Some(plugins) = e.eval(context) {
                 result = true;
-                results.extend(plugins);
+                union(&mut results, plugins);
             }
         }
 
@@ -248,8 +521,9 @@ impl Display for ANY {
 // NOT
 
 /// The NOT expression
-/// NOT evaluates as true if the wrapped expression evaluates as true
-#[derive(Debug, Serialize, Deserialize)]
+/// NOT evaluates as true if the wrapped expression evaluates as false.
+/// No plugin matched, so a true NOT returns Some with an empty list
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NOT {
     pub expression: Box<Expression>,
 }
@@ -261,13 +535,13 @@ impl NOT {
     }
 }
 impl TExpression for NOT {
-    // NOT evaluates as true if the wrapped expression evaluates as true
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
-        if let Some(_plugins) = self.expression.eval(items) {
+    // NOT evaluates as true if the wrapped expression evaluates as false
+    // no plugin matched, so the result is empty
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
+        if let Some(_plugins) = self.expression.eval(context) {
             None
         } else {
-            // NOT and resolving names
-            Some(vec![self.to_string()])
+            Some(vec![])
         }
     }
 }
@@ -295,24 +569,48 @@ pub struct DESC {
     pub expression: Atomic,
     pub regex: String,
     pub is_negated: bool,
+
+    /// the compiled regex, compiled on first use after deserializing
+    #[serde(skip)]
+    pattern: OnceLock<Option<Regex>>,
 }
 impl DESC {
-    pub fn new(expression: Atomic, regex: String, is_negated: bool) -> Self {
-        Self {
+    /// Creates a DESC predicate and compiles its regex
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the regex is invalid
+    pub fn new(expression: Atomic, regex: String, is_negated: bool) -> Result<Self, regex::Error> {
+        let pattern = Regex::new(&regex)?;
+        Ok(Self {
             expression,
             regex,
             is_negated,
-        }
+            pattern: OnceLock::from(Some(pattern)),
+        })
+    }
+
+    /// Returns the compiled regex, or None if the regex is invalid
+    fn pattern(&self) -> Option<&Regex> {
+        self.pattern
+            .get_or_init(|| match Regex::new(&self.regex) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    log::warn!("Invalid DESC regex {}: {}", self.regex, e);
+                    None
+                }
+            })
+            .as_ref()
     }
 }
 impl TExpression for DESC {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         // check the version
-        if let Some(plugins) = wild_contains_data(items, &self.expression.item) {
+        if let Some(plugins) = self.expression.eval_data(context) {
             let mut results = vec![];
             for p in &plugins {
                 if let Some(description) = &p.description {
-                    if let Ok(pattern) = regex::Regex::new(&self.regex) {
+                    if let Some(pattern) = self.pattern() {
                         let description = &description.to_lowercase();
                         match self.is_negated {
                             true => {
@@ -344,9 +642,17 @@ impl Clone for DESC {
             expression: self.expression.clone(),
             regex: self.regex.clone(),
             is_negated: self.is_negated,
+            pattern: self.pattern.clone(),
         }
     }
 }
+impl PartialEq for DESC {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+            && self.regex == other.regex
+            && self.is_negated == other.is_negated
+    }
+}
 
 impl Display for DESC {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -363,7 +669,7 @@ impl Display for DESC {
 
 /// The Size predicate is a special predicate that matches the filesize of the plugin
 /// [SIZE ### A.esp] or [SIZE !### A.esp]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct SIZE {
     pub expression: Atomic,
     pub size: u64,
@@ -379,9 +685,9 @@ impl SIZE {
     }
 }
 impl TExpression for SIZE {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         // check the size
-        if let Some(plugins) = wild_contains_data(items, &self.expression.item) {
+        if let Some(plugins) = self.expression.eval_data(context) {
             let mut results = vec![];
             for p in &plugins {
                 if self.is_negated {
@@ -424,7 +730,7 @@ impl Display for SIZE {
 ////////////////////////////////////////////////////////////////////////
 // VER
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
 pub enum EVerOperator {
     Less,
     Equal,
@@ -445,7 +751,7 @@ impl Display for EVerOperator {
 /// and if that fails it tries to match the version number from the plugin filename.
 /// If a version number is found, it can be used in a comparison.
 /// Syntax: [VER operator version plugin.esp]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct VER {
     pub expression: Atomic,
     pub operator: EVerOperator,
@@ -461,14 +767,17 @@ impl VER {
     }
 }
 impl TExpression for VER {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         // check the version
-        if let Some(plugins) = wild_contains_data(items, &self.expression.item) {
+        if let Some(plugins) = self.expression.eval_data(context) {
             let mut results = vec![];
             for p in &plugins {
                 if let Some(plugin_version) = &p.version {
-                    // we can unwrap here because we know the version is valid
-                    let semversion = semver::Version::parse(&self.version).unwrap();
+                    // the parser only creates valid versions, but rules may also be deserialized
+                    let Ok(semversion) = semver::Version::parse(&self.version) else {
+                        log::warn!("Invalid VER version: {}", self.version);
+                        return None;
+                    };
                     let matches = match self.operator {
                         EVerOperator::Less => {
                             let req =
@@ -526,7 +835,7 @@ impl Display for VER {
 ////////////////////////////////////////////////////////////////////////
 // GVER
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
 pub enum EGVerOperator {
     Less,
     Equal,
@@ -546,7 +855,7 @@ impl Display for EGVerOperator {
 /// The GVER predicate is a special predicate that matches against the game version
 /// If a version number is found, it can be used in a comparison.
 /// Syntax: [GVER operator version plugin.esp]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct GVER {
     pub expression: Atomic,
     pub operator: EGVerOperator,
@@ -562,14 +871,17 @@ impl GVER {
     }
 }
 impl TExpression for GVER {
-    fn eval(&self, items: &[PluginData]) -> Option<Vec<String>> {
+    fn eval(&self, context: &EvalContext) -> Option<Vec<String>> {
         // check the version
-        if let Some(plugins) = wild_contains_data(items, &self.expression.item) {
+        if let Some(plugins) = self.expression.eval_data(context) {
             let mut results = vec![];
             for p in &plugins {
                 if let Some(game_version) = &p.game_version {
-                    // we can unwrap here because we know the version is valid
-                    let semversion = semver::Version::parse(&self.version).unwrap();
+                    // the parser only creates valid versions, but rules may also be deserialized
+                    let Ok(semversion) = semver::Version::parse(&self.version) else {
+                        log::warn!("Invalid GVER version: {}", self.version);
+                        return None;
+                    };
                     let matches = match self.operator {
                         EGVerOperator::Less => {
                             let req =
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, Read, Seek, Write};
@@ -10,10 +10,12 @@ use clap::ValueEnum;
 pub mod commands;
 pub use commands::*;
 
+pub mod error;
 pub mod expressions;
 pub mod parser;
 pub mod rules;
 pub mod sorter;
+pub mod writer;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use filetime::set_file_mtime;
@@ -44,31 +46,86 @@ pub fn log_level_to_str(level: ELogLevel) -> String {
     }
 }
 
+/// How command results are printed
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq, Eq)]
+pub enum EOutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How the text output of the list command separates the mods
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq, Eq)]
+pub enum EListFormat {
+    /// One mod per line
+    #[default]
+    Lines,
+    /// One mod per line, prefixed with its load order index
+    Numbered,
+    /// Each mod terminated by a null byte, e.g. for xargs -0
+    NullDelimited,
+    /// All mods on one comma separated line
+    Csv,
+}
+
+/// Formats a list of mod names for printing
+pub fn format_mod_list(names: &[String], format: EListFormat) -> String {
+    match format {
+        EListFormat::Lines => names.iter().map(|n| format!("{}\n", n)).collect(),
+        EListFormat::Numbered => names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| format!("{} {}\n", i, n))
+            .collect(),
+        EListFormat::NullDelimited => names.iter().map(|n| format!("{}\0", n)).collect(),
+        EListFormat::Csv => {
+            // quote names with separators or quotes in them
+            let fields = names
+                .iter()
+                .map(|n| {
+                    if n.contains([',', '"', '\n']) {
+                        format!("\"{}\"", n.replace('"', "\"\""))
+                    } else {
+                        n.to_owned()
+                    }
+                })
+                .collect::<Vec<_>>();
+            format!("{}\n", fields.join(","))
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ESupportedGame {
     Morrowind,
     Openmw,
     Cyberpunk,
+    Skyrim,
 }
 pub const PLOX_RULES_BASE: &str = "plox_base.txt";
 pub const GRAPH_FILE: &str = "graphviz.dot";
+/// plugins listed in this file in the game root folder are not gathered
+pub const DISABLED_PLUGINS_FILE: &str = "disabled.txt";
+/// The version of the json rules document, bump this when the serialized format of a rule changes
+pub const RULES_SCHEMA_VERSION: u32 = 1;
 
 ////////////////////////////////////////////////////////////////////////
 // GAMES
 ////////////////////////////////////////////////////////////////////////
 
-/// Detect game from current working directory
-pub fn detect_game() -> Option<ESupportedGame> {
-    if PathBuf::from("Morrowind.exe").exists() {
+/// Detect game from the contents of a game directory, e.g. the current working directory.
+/// Checked in order: Cyberpunk, Morrowind, OpenMW, Skyrim
+pub fn detect_game(root: &Path) -> Option<ESupportedGame> {
+    let exists = |path: &str| root.join(path).exists();
+
+    if exists("bin/x64/Cyberpunk2077.exe") || exists("archive/pc/mod") {
+        Some(ESupportedGame::Cyberpunk)
+    } else if exists("Morrowind.exe") || exists("Morrowind.ini") || exists("Data Files") {
         Some(ESupportedGame::Morrowind)
-    } else if PathBuf::from("openmw.cfg").exists() {
+    } else if exists("openmw.cfg") {
         Some(ESupportedGame::Openmw)
-    } else if PathBuf::from("bin")
-        .join("x64")
-        .join("Cyberpunk2077.exe")
-        .exists()
-    {
-        Some(ESupportedGame::Cyberpunk)
+    } else if exists("SkyrimSE.exe") || exists("TESV.exe") {
+        Some(ESupportedGame::Skyrim)
     } else {
         None
     }
@@ -77,32 +134,54 @@ pub fn detect_game() -> Option<ESupportedGame> {
 /// Detect game from current working directory and read version from exe
 /// Does not log errors
 fn read_pe_version(path: &PathBuf) -> Option<String> {
-    use pelite::pe64::{Pe, PeFile};
-
-    if let Ok(map) = pelite::FileMap::open(path) {
-        if let Ok(file) = PeFile::from_bytes(&map) {
-            if let Ok(resources) = file.resources() {
-                if let Ok(version_info) = resources.version_info() {
-                    if let Some(language) = version_info.translation().first() {
-                        if let Some(version) = version_info.value(*language, "ProductVersion") {
-                            return Some(version);
-                        }
-                    }
-                }
-            }
-        }
+    let map = pelite::FileMap::open(path).ok()?;
+    // Morrowind.exe is a 32-bit executable, newer games are 64-bit
+    let resources = match pelite::pe64::PeFile::from_bytes(&map) {
+        Ok(file) => pelite::pe64::Pe::resources(file),
+        Err(_) => pelite::pe32::Pe::resources(pelite::pe32::PeFile::from_bytes(&map).ok()?),
     }
+    .ok()?;
 
-    None
+    let version_info = resources.version_info().ok()?;
+    let language = *version_info.translation().first()?;
+    version_info.value(language, "ProductVersion")
 }
 
 /// Detect game from current working directory and read version from exe
 pub fn get_game_version(game: ESupportedGame) -> Option<String> {
+    detect_game_version(Path::new("."), game)
+}
+
+/// Reads the game version from the game installation at root:
+/// the version info of the exe, or the resources/version file for OpenMW
+pub fn detect_game_version(root: &Path, game: ESupportedGame) -> Option<String> {
     match game {
-        ESupportedGame::Morrowind => None,
-        ESupportedGame::Openmw => None,
+        ESupportedGame::Morrowind => {
+            let exe_path = root.join("Morrowind.exe");
+            if exe_path.exists() {
+                return read_pe_version(&exe_path);
+            }
+            None
+        }
+        ESupportedGame::Openmw => {
+            // the first line of resources/version is the version number
+            let version_path = root.join("resources").join("version");
+            let version = fs::read_to_string(version_path).ok()?;
+            version
+                .lines()
+                .next()
+                .map(|l| l.trim().to_owned())
+                .filter(|l| !l.is_empty())
+        }
+        ESupportedGame::Skyrim => {
+            let exe_path = root.join("SkyrimSE.exe");
+            if exe_path.exists() {
+                return read_pe_version(&exe_path);
+            }
+            None
+        }
         ESupportedGame::Cyberpunk => {
-            let exe_path = PathBuf::from("bin").join("x64").join("Cyberpunk2077.exe");
+            let exe_path = root.join("bin").join("x64").join("Cyberpunk2077.exe");
             if exe_path.exists() {
                 return read_pe_version(&exe_path);
             }
@@ -150,18 +229,89 @@ pub fn debug_get_mods_from_ordering(order: &[(String, String)]) -> Vec<PluginDat
 pub fn get_default_rules_dir(game: ESupportedGame) -> PathBuf {
     match game {
         ESupportedGame::Morrowind | ESupportedGame::Openmw => PathBuf::from("mlox"),
-        ESupportedGame::Cyberpunk => PathBuf::from("plox"),
+        ESupportedGame::Cyberpunk | ESupportedGame::Skyrim => PathBuf::from("plox"),
     }
 }
 
+/// Resolves a user supplied rules dir: expands a leading ~ to the home dir, substitutes
+/// environment variables written as $VAR, ${VAR} or %VAR% and canonicalizes the result,
+/// so relative paths are resolved against the current working directory
+///
+/// # Errors
+///
+/// This function will return an error if an environment variable is not set or the path does not exist
+pub fn resolve_rules_dir(input: &str) -> std::io::Result<PathBuf> {
+    // environment variables
+    let pattern = Regex::new(r"\$\{(\w+)\}|\$(\w+)|%(\w+)%").unwrap();
+    let mut expanded = String::new();
+    let mut last = 0;
+    for captures in pattern.captures_iter(input) {
+        let all = captures.get(0).unwrap();
+        let name = (1..=3)
+            .find_map(|i| captures.get(i))
+            .map(|m| m.as_str())
+            .unwrap_or_default();
+        let value = env::var(name).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Environment variable {} in rules dir {} is not set",
+                    name, input
+                ),
+            )
+        })?;
+        expanded += &input[last..all.start()];
+        expanded += &value;
+        last = all.end();
+    }
+    expanded += &input[last..];
+
+    // home dir
+    let path = match expanded.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let home = env::var("HOME")
+                .or_else(|_| env::var("USERPROFILE"))
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("No home dir to expand in rules dir {}", input),
+                    )
+                })?;
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(expanded),
+    };
+
+    fs::canonicalize(&path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Rules dir {} does not exist: {}", path.display(), e),
+        )
+    })
+}
+
 /// Download latest rules from the internet
 pub fn download_latest_rules(game: ESupportedGame, rules_dir: &PathBuf) {
     match game {
         ESupportedGame::Morrowind | ESupportedGame::Openmw => download_mlox_rules(rules_dir),
         ESupportedGame::Cyberpunk => download_plox_rules(rules_dir),
+        ESupportedGame::Skyrim => info!("No rules download available for Skyrim"),
     }
 }
 
+#[cfg(not(feature = "net"))]
+fn download_file<P>(url: &str, _output_path: &P) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    Err(format!(
+        "Could not download {}: plox was built without the net feature",
+        url
+    )
+    .into())
+}
+
+#[cfg(feature = "net")]
 fn download_file<P>(url: &str, output_path: &P) -> Result<(), Box<dyn Error>>
 where
     P: AsRef<Path>,
@@ -279,6 +429,62 @@ impl PluginData {
             masters: None,
         }
     }
+
+    pub fn kind(&self) -> EPluginKind {
+        EPluginKind::from_name(&self.name)
+    }
+}
+
+/// The kind of a plugin, derived from its file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EPluginKind {
+    /// .omwgame
+    Game,
+    /// .esm
+    Master,
+    /// .esl
+    Light,
+    /// .esp
+    Plugin,
+    /// .omwaddon
+    Addon,
+    /// .omwscripts
+    Scripts,
+    /// .archive
+    Archive,
+    Unknown,
+}
+
+impl EPluginKind {
+    pub fn from_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        match Path::new(&name).extension().and_then(|e| e.to_str()) {
+            Some("omwgame") => EPluginKind::Game,
+            Some("esm") => EPluginKind::Master,
+            Some("esl") => EPluginKind::Light,
+            Some("esp") => EPluginKind::Plugin,
+            Some("omwaddon") => EPluginKind::Addon,
+            Some("omwscripts") => EPluginKind::Scripts,
+            Some("archive") => EPluginKind::Archive,
+            _ => EPluginKind::Unknown,
+        }
+    }
+
+    /// Masters load before all other plugins
+    pub fn is_master(&self) -> bool {
+        matches!(
+            self,
+            EPluginKind::Game | EPluginKind::Master | EPluginKind::Light
+        )
+    }
+
+    /// Content files change game data, scripts and archives don't
+    pub fn is_content(&self) -> bool {
+        !matches!(
+            self,
+            EPluginKind::Scripts | EPluginKind::Archive | EPluginKind::Unknown
+        )
+    }
 }
 
 /// Gets a list of mod names from the game root folder
@@ -294,11 +500,177 @@ pub fn gather_mods<P>(
 where
     P: AsRef<Path>,
 {
-    match game {
+    gather_mods_with_depth(root, game, game_version, 1)
+}
+
+/// Gets a list of mod names from the game root folder like gather_mods, without the excluded plugins.
+/// Names are compared case-insensitively
+pub fn gather_mods_excluding<P>(
+    root: &P,
+    game: ESupportedGame,
+    game_version: &Option<String>,
+    exclude: &[String],
+) -> Vec<PluginData>
+where
+    P: AsRef<Path>,
+{
+    let exclude = exclude.iter().map(|e| e.to_lowercase()).collect::<Vec<_>>();
+    gather_mods(root, game, game_version)
+        .into_iter()
+        .filter(|m| !exclude.contains(&m.name.to_lowercase()))
+        .collect()
+}
+
+/// The kind of an installed mod
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EModKind {
+    /// A loose .archive in archive/pc/mod
+    Archive,
+    /// A REDmod folder in mods
+    Redmod,
+    /// An esp, esm or other content file
+    Plugin,
+}
+
+/// A mod in the load order with its position and location on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModEntry {
+    pub name: String,
+    /// Position in the load order, starting at 0
+    pub index: usize,
+    pub path: PathBuf,
+    pub kind: EModKind,
+}
+
+/// Gets the load order like gather_mods, with the index, path and kind of each mod
+pub fn gather_mods_detailed<P>(root: &P, game: ESupportedGame) -> Vec<ModEntry>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref();
+
+    // openmw content files can be in any data directory
+    let openmw_cfg = if game == ESupportedGame::Openmw {
+        let cfg_path = openmw_cfg::config_path();
+        fs::read_to_string(&cfg_path).ok().and_then(|contents| {
+            openmw_cfg::parse_config(&contents, &cfg_path, openmw_cfg::Ini::default()).ok()
+        })
+    } else {
+        None
+    };
+
+    gather_mods(&root, game, &None)
+        .into_iter()
+        .enumerate()
+        .map(|(index, m)| {
+            let (kind, path) = match game {
+                ESupportedGame::Cyberpunk => {
+                    // duplicate names are qualified with their folder
+                    if m.name.starts_with("mods/") {
+                        (EModKind::Redmod, root.join(&m.name))
+                    } else if m.name.starts_with("archive/pc/mod/") {
+                        (EModKind::Archive, root.join(&m.name))
+                    } else if EPluginKind::from_name(&m.name) == EPluginKind::Archive {
+                        let path = root.join("archive").join("pc").join("mod").join(&m.name);
+                        (EModKind::Archive, path)
+                    } else {
+                        (EModKind::Redmod, root.join("mods").join(&m.name))
+                    }
+                }
+                ESupportedGame::Morrowind => {
+                    (EModKind::Plugin, root.join("Data Files").join(&m.name))
+                }
+                ESupportedGame::Skyrim => (EModKind::Plugin, root.join("Data").join(&m.name)),
+                ESupportedGame::Openmw => {
+                    let path = openmw_cfg
+                        .as_ref()
+                        .and_then(|cfg| openmw_cfg::find_file(cfg, &m.name).ok())
+                        .unwrap_or_else(|| PathBuf::from(&m.name));
+                    (EModKind::Plugin, path)
+                }
+            };
+            ModEntry {
+                name: m.name,
+                index,
+                path,
+                kind,
+            }
+        })
+        .collect()
+}
+
+/// Reads the names of disabled plugins from disabled.txt in the game root folder, one name per line.
+/// Empty lines and lines starting with ; are ignored
+pub fn read_disabled_plugins(root: &Path) -> Vec<String> {
+    let Ok(file) = File::open(root.join(DISABLED_PLUGINS_FILE)) else {
+        return vec![];
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .map(|l| l.trim().to_owned())
+        .filter(|l| !l.is_empty() && !l.starts_with(';'))
+        .collect()
+}
+
+/// Gets a list of mod names from the game root folder, reading mod folders up to max_depth levels deep.
+/// A depth of 1 only reads the mod folder itself. Nested mods are named relative to the mod folder,
+/// e.g. "subfolder/a.archive". Only Cyberpunk archives are gathered recursively
+pub fn gather_mods_with_depth<P>(
+    root: &P,
+    game: ESupportedGame,
+    game_version: &Option<String>,
+    max_depth: usize,
+) -> Vec<PluginData>
+where
+    P: AsRef<Path>,
+{
+    let mut mods = match game {
         ESupportedGame::Morrowind => gather_tes3_mods(root),
-        ESupportedGame::Cyberpunk => gather_cp77_mods(root, game_version),
+        ESupportedGame::Cyberpunk => gather_cp77_mods_with_depth(root, game_version, max_depth),
         ESupportedGame::Openmw => gather_openmw_mods(),
+        ESupportedGame::Skyrim => gather_skyrim_mods(root),
+    };
+
+    // all plugins share the game version, cyberpunk mods are gathered with it
+    if game != ESupportedGame::Cyberpunk {
+        if let Some(version) = game_version {
+            match lenient_semver::parse(version) {
+                Ok(v) => mods
+                    .iter_mut()
+                    .for_each(|m| m.game_version = Some(v.clone())),
+                Err(_) => error!("Invalid game version: {}", version),
+            }
+        }
     }
+
+    // disabled plugins are not sorted or checked
+    let disabled = read_disabled_plugins(root.as_ref())
+        .iter()
+        .map(|d| d.to_lowercase())
+        .collect::<Vec<_>>();
+    mods.retain(|m| !disabled.contains(&m.name.to_lowercase()));
+    mods
+}
+
+/// Get all files in a folder and its subfolders up to max_depth levels deep
+fn get_files_recursive(path: &Path, max_depth: usize) -> Vec<PathBuf> {
+    let mut results = vec![];
+    if max_depth == 0 {
+        return results;
+    }
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(Result::ok) {
+            let entry_path = entry.path();
+            if entry_path.is_dir() {
+                results.extend(get_files_recursive(&entry_path, max_depth - 1));
+            } else {
+                results.push(entry_path);
+            }
+        }
+    }
+    results
 }
 
 /// Get all plugins (esp, omwaddon, omwscripts) in a folder
@@ -391,18 +763,109 @@ where
     names
 }
 
-pub fn gather_openmw_mods() -> Vec<PluginData> {
-    // parse cfg
-    if let Ok(cfg) = openmw_cfg::get_config() {
-        if let Ok(files) = openmw_cfg::get_plugins(&cfg) {
-            let names = files.iter().filter_map(|f| map_data(f)).collect::<Vec<_>>();
-            return names;
+/// Gets the plugins.txt for Skyrim, next to the game or in the local app data folder
+fn skyrim_plugins_txt_path(root: &Path) -> PathBuf {
+    let local = root.join("plugins.txt");
+    if local.exists() {
+        return local;
+    }
+    if let Ok(app_data) = env::var("LOCALAPPDATA") {
+        let path = PathBuf::from(app_data)
+            .join("Skyrim Special Edition")
+            .join("plugins.txt");
+        if path.exists() {
+            return path;
         }
+    }
+    local
+}
+
+pub fn gather_skyrim_mods<P>(root: &P) -> Vec<PluginData>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref();
+    gather_skyrim_mods_from_plugins_txt(&skyrim_plugins_txt_path(root), &root.join("Data"))
+}
+
+/// Gets the enabled plugins from a plugins.txt in load order.
+/// Plugins that can't be found in the data folder are skipped
+pub fn gather_skyrim_mods_from_plugins_txt(path: &Path, data_dir: &Path) -> Vec<PluginData> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        error!("No plugins.txt found");
+        return vec![];
+    };
+
+    let names = parse_plugins_txt(&contents);
+    info!("Found {} active plugins", names.len());
+    names
+        .iter()
+        .filter_map(|name| {
+            let plugin_path = data_dir.join(name);
+            if plugin_path.exists() {
+                map_data(&plugin_path)
+            } else {
+                warn!("Could not find plugin {}", name);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the enabled plugin names of a plugins.txt in order.
+/// If any plugin is marked with '*' only marked plugins are enabled, otherwise all listed plugins are
+pub fn parse_plugins_txt(contents: &str) -> Vec<String> {
+    let lines = contents
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect::<Vec<_>>();
+
+    if lines.iter().any(|l| l.starts_with('*')) {
+        lines
+            .iter()
+            .filter_map(|l| l.strip_prefix('*'))
+            .map(|l| l.trim().to_owned())
+            .collect()
     } else {
-        error!("No openmw.cfg found");
+        lines.iter().map(|l| l.to_string()).collect()
     }
+}
 
-    vec![]
+pub fn gather_openmw_mods() -> Vec<PluginData> {
+    gather_openmw_mods_from_cfg(&openmw_cfg::config_path())
+}
+
+/// Gets the content files from an openmw.cfg in load order.
+/// Content files that can't be found in any data directory are skipped.
+/// Masters are read from the tes3 header of .omwgame, .omwaddon, .esm and .esp files
+pub fn gather_openmw_mods_from_cfg(cfg_path: &Path) -> Vec<PluginData> {
+    // parse cfg
+    let Ok(contents) = fs::read_to_string(cfg_path) else {
+        error!("No openmw.cfg found");
+        return vec![];
+    };
+
+    match openmw_cfg::parse_config(&contents, cfg_path, openmw_cfg::Ini::default()) {
+        Ok(cfg) => cfg
+            .plugin_names()
+            .iter()
+            .filter_map(|name| {
+                let name = name.display().to_string();
+                match openmw_cfg::find_file(&cfg, &name) {
+                    Ok(path) => map_data(&path),
+                    Err(_) => {
+                        warn!("Could not find content file {}", name);
+                        None
+                    }
+                }
+            })
+            .collect(),
+        Err(e) => {
+            error!("Could not parse {}: {}", cfg_path.display(), e);
+            vec![]
+        }
+    }
 }
 
 fn map_data(f: &Path) -> Option<PluginData> {
@@ -413,7 +876,7 @@ fn map_data(f: &Path) -> Option<PluginData> {
             description: None,
             version: None,
             masters: None,
-            game_version: None, // TODO add game version
+            game_version: None, // set in gather_mods_with_depth
         };
 
         match parse_header(f) {
@@ -522,6 +985,19 @@ fn read_file_to_vec(file_path: &PathBuf) -> io::Result<Vec<String>> {
 }
 
 pub fn gather_cp77_mods<P>(root: &P, game_version: &Option<String>) -> Vec<PluginData>
+where
+    P: AsRef<Path>,
+{
+    gather_cp77_mods_with_depth(root, game_version, 1)
+}
+
+/// Gathers archives from archive/pc/mod and up to max_depth - 1 levels of subfolders,
+/// nested archives are named relative to archive/pc/mod
+pub fn gather_cp77_mods_with_depth<P>(
+    root: &P,
+    game_version: &Option<String>,
+    max_depth: usize,
+) -> Vec<PluginData>
 where
     P: AsRef<Path>,
 {
@@ -538,11 +1014,8 @@ where
         }
     }
 
-    if let Ok(plugins) = fs::read_dir(&archive_path) {
-        let mut mods: Vec<PathBuf> = plugins
-            .map(|res| res.map(|e| e.path()))
-            .filter_map(Result::ok)
-            .collect::<Vec<_>>();
+    if archive_path.is_dir() {
+        let mut mods = get_files_recursive(&archive_path, max_depth);
 
         // load order
         mods.sort_by(|a, b| {
@@ -571,18 +1044,23 @@ where
             final_order = mods;
         }
 
-        // TODO Redmods
-
-        let vms = final_order
+        let mut vms = final_order
             .iter()
             .filter_map(|e| {
                 if !e.is_dir() {
                     if let Some(os_ext) = e.extension() {
                         if let Some(ext) = os_ext.to_ascii_lowercase().to_str() {
-                            if ext.contains("archive") {
-                                if let Some(file_name) = e.file_name().and_then(|n| n.to_str()) {
+                            // only the final extension counts, renamed files like a.archive.disabled are disabled
+                            if ext == "archive" {
+                                // nested archives are named relative to the mod folder
+                                if let Ok(relative) = e.strip_prefix(&archive_path) {
+                                    let name = relative
+                                        .components()
+                                        .map(|c| c.as_os_str().to_string_lossy())
+                                        .collect::<Vec<_>>()
+                                        .join("/");
                                     let data = PluginData {
-                                        name: file_name.to_owned(),
+                                        name,
                                         size: e.metadata().unwrap().len(),
                                         description: None,
                                         version: None,
@@ -599,10 +1077,112 @@ where
             })
             .collect::<Vec<_>>();
 
+        // REDmods load after archives
+        let mut taken = HashSet::new();
+        qualify_duplicate_names(&mut vms, &mut taken, "archive/pc/mod");
+        let mut redmods = gather_cp77_redmods(root, &game_version_semver);
+        qualify_duplicate_names(&mut redmods, &mut taken, "mods");
+        vms.extend(redmods);
         return vms;
     }
 
-    vec![]
+    let mut redmods = gather_cp77_redmods(root, &game_version_semver);
+    qualify_duplicate_names(&mut redmods, &mut HashSet::new(), "mods");
+    redmods
+}
+
+/// Qualifies mods whose name is already taken with the folder they are in, relative to the game root.
+/// Cyberpunk mods are identified by their path relative to archive/pc/mod for archives and by their folder
+/// name for REDmods, so two REDmods with the same archive inside are distinct. Names can still collide when
+/// they only differ in case, or when a REDmod folder is named like an archive: the first mod keeps its name
+/// and later ones are named e.g. "mods/a.archive". The sorter needs unique names, names are compared case-insensitively.
+/// Qualified archives are written to the modlist without the folder, REDmods are never written to it
+fn qualify_duplicate_names(mods: &mut [PluginData], taken: &mut HashSet<String>, folder: &str) {
+    for m in mods.iter_mut() {
+        if !taken.insert(m.name.to_lowercase()) {
+            let qualified = format!("{}/{}", folder, m.name);
+            warn!("Duplicate mod name {}, using {}", m.name, qualified);
+            taken.insert(qualified.to_lowercase());
+            m.name = qualified;
+        }
+    }
+}
+
+/// Returns the lowercase names of mods that appear more than once, names are compared case-insensitively.
+/// gather_mods qualifies duplicate Cyberpunk mods, use this to report duplicates for other games
+pub fn find_duplicate_mods(mods: &[PluginData]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates = vec![];
+    for m in mods {
+        let name = m.name.to_lowercase();
+        if !seen.insert(name.clone()) && !duplicates.contains(&name) {
+            duplicates.push(name);
+        }
+    }
+    duplicates
+}
+
+/// Gathers REDmods from mods/<NAME>, the folder name is the mod name
+fn gather_cp77_redmods<P>(root: &P, game_version: &Option<Version>) -> Vec<PluginData>
+where
+    P: AsRef<Path>,
+{
+    let mods_path = root.as_ref().join("mods");
+    let Ok(entries) = fs::read_dir(mods_path) else {
+        return vec![];
+    };
+
+    let mut folders = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect::<Vec<_>>();
+    folders.sort();
+
+    folders
+        .iter()
+        .filter_map(|folder| {
+            let name = folder.file_name()?.to_str()?.to_owned();
+
+            // the size of a REDmod is the size of all its archives
+            let size = fs::read_dir(folder.join("archives"))
+                .map(|archives| {
+                    archives
+                        .filter_map(Result::ok)
+                        .filter(|e| {
+                            e.path()
+                                .extension()
+                                .is_some_and(|ext| ext.eq_ignore_ascii_case("archive"))
+                        })
+                        .filter_map(|e| e.metadata().ok())
+                        .map(|m| m.len())
+                        .sum()
+                })
+                .unwrap_or(0);
+
+            // description and version from info.json
+            let info = fs::read_to_string(folder.join("info.json"))
+                .ok()
+                .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok());
+            let description = info
+                .as_ref()
+                .and_then(|i| i["description"].as_str())
+                .map(|s| s.to_owned());
+            let version = info
+                .as_ref()
+                .and_then(|i| i["version"].as_str())
+                .and_then(|v| lenient_semver::parse(v).ok());
+
+            Some(PluginData {
+                name,
+                size,
+                description,
+                version,
+                masters: None,
+                game_version: game_version.clone(),
+            })
+        })
+        .collect()
 }
 
 /// Update on disk
@@ -611,12 +1191,131 @@ pub fn update_new_load_order(game: ESupportedGame, result: &[String]) -> std::io
         ESupportedGame::Morrowind => update_tes3(PathBuf::from("Morrowind.ini"), result, false),
         ESupportedGame::Openmw => update_openmw(result),
         ESupportedGame::Cyberpunk => update_cp77(result),
+        ESupportedGame::Skyrim => {
+            write_plugins_txt(&skyrim_plugins_txt_path(Path::new("")), result)
+        }
     }
 }
 
-fn update_cp77(_result: &[String]) -> std::io::Result<()> {
-    // TODO CP77 update mods
-    panic!("Not implemented")
+fn update_cp77(result: &[String]) -> std::io::Result<()> {
+    write_cp77_modlist(Path::new(""), result)
+}
+
+/// Writes the load order to disk for the game installed at root.
+/// For Cyberpunk this is archive/pc/mod/modlist.txt, for Morrowind the Morrowind.ini and the plugin file times,
+/// for OpenMW the openmw.cfg (the root is not used), for Skyrim the plugins.txt.
+/// The existing file is backed up to a timestamped .bak file first.
+///
+/// # Errors
+///
+/// This function will return an error if file io fails
+pub fn write_load_order(root: &Path, game: ESupportedGame, order: &[String]) -> io::Result<()> {
+    match game {
+        ESupportedGame::Morrowind => {
+            let ini_path = root.join("Morrowind.ini");
+            backup_file(&ini_path)?;
+            update_tes3(&ini_path, order, true)?;
+
+            // redate files
+            let files = order
+                .iter()
+                .map(|f| root.join("Data Files").join(f))
+                .filter(|f| f.exists())
+                .collect::<Vec<_>>();
+            redate_mods(&files)
+        }
+        ESupportedGame::Openmw => {
+            backup_file(&openmw_cfg::config_path())?;
+            update_openmw(order)
+        }
+        ESupportedGame::Cyberpunk => write_cp77_modlist(root, order),
+        ESupportedGame::Skyrim => write_plugins_txt(&skyrim_plugins_txt_path(root), order),
+    }
+}
+
+/// Writes the sorted plugins as enabled to a plugins.txt.
+/// The sorted plugins take the places of their existing entries, comments and other entries
+/// (e.g. disabled plugins) are kept, new plugins are appended
+fn write_plugins_txt(path: &Path, order: &[String]) -> io::Result<()> {
+    backup_file(path)?;
+
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let mut sorted = order
+        .iter()
+        .map(|name| name.to_lowercase())
+        .collect::<HashSet<_>>();
+    let mut order = order.iter();
+
+    let mut buf = Vec::new();
+    for line in existing.lines() {
+        let name = line.trim().trim_start_matches('*').trim().to_lowercase();
+        if line.trim().starts_with('#') || !sorted.contains(&name) {
+            writeln!(buf, "{}", line)?;
+            continue;
+        }
+
+        // duplicate entries are dropped
+        sorted.remove(&name);
+        if let Some(next) = order.next() {
+            writeln!(buf, "*{}", next)?;
+        }
+    }
+    for name in order {
+        writeln!(buf, "*{}", name)?;
+    }
+
+    let mut file = File::create(path)?;
+    file.write_all(&buf)
+}
+
+fn write_cp77_modlist(root: &Path, order: &[String]) -> io::Result<()> {
+    let modlist_path = root
+        .join("archive")
+        .join("pc")
+        .join("mod")
+        .join("modlist.txt");
+    backup_file(&modlist_path)?;
+
+    // REDmods are not part of the modlist. A REDmod name may end in .archive too (a folder named
+    // like an archive, or a qualified duplicate like mods/x.archive), so only names of archive files are written
+    let archive_path = root.join("archive").join("pc").join("mod");
+    let mut buf = Vec::new();
+    for name in order.iter().filter_map(|f| {
+        let name = f.strip_prefix("archive/pc/mod/").unwrap_or(f);
+        archive_path.join(name).is_file().then_some(name)
+    }) {
+        writeln!(buf, "{}", name)?;
+    }
+
+    let mut file = File::create(modlist_path)?;
+    file.write_all(&buf)
+}
+
+/// Copies the file to <name>.<unix time>.bak if it exists,
+/// a counter is added if a backup with that name already exists
+fn backup_file(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let backup_path = |suffix: String| {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        PathBuf::from(backup)
+    };
+    let mut backup = backup_path(format!(".{}.bak", timestamp));
+    let mut i = 1;
+    while backup.exists() {
+        backup = backup_path(format!(".{}.{}.bak", timestamp, i));
+        i += 1;
+    }
+    fs::copy(path, backup)?;
+
+    Ok(())
 }
 
 fn update_openmw(result: &[String]) -> std::io::Result<()> {
@@ -697,9 +1396,42 @@ pub fn update_tes3<P: AsRef<Path>>(
     Ok(())
 }
 
+/// Evaluates the warning rules against a list of mod names and returns all rules that apply
+pub fn check_warnings(mods: &[String], rules: &[EWarningRule]) -> Vec<parser::Warning> {
+    let context = expressions::EvalContext::from_mods(mods);
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let mut rule = rule.clone();
+            if rule.eval_context(&context) {
+                Some(parser::Warning { rule })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns all pairs of mod names that only differ by case
+pub fn detect_case_conflicts(mods: &[String]) -> Vec<(String, String)> {
+    let mut seen: HashMap<String, &String> = HashMap::new();
+    let mut conflicts = vec![];
+    for m in mods {
+        let lower_case = m.to_lowercase();
+        if let Some(first) = seen.get(&lower_case) {
+            if *first != m {
+                conflicts.push(((*first).to_owned(), m.to_owned()));
+            }
+        } else {
+            seen.insert(lower_case, m);
+        }
+    }
+    conflicts
+}
+
 /// Checks if the list of mods is in the correct order
 pub fn check_order(result: &[String], order_rules: &[EOrderRule]) -> bool {
-    let order = get_ordering_from_order_rules(order_rules);
+    let order = get_ordering_from_order_rules_for(order_rules, result);
     let pairs = order;
     for (a, b) in pairs {
         if let Some(results_for_a) = wild_contains(result, &a) {
@@ -720,6 +1452,35 @@ pub fn check_order(result: &[String], order_rules: &[EOrderRule]) -> bool {
     true
 }
 
+/// Returns the soft order pairs (first should load before second) that the list of mods violates,
+/// e.g. because a hard order rule contradicts them.
+/// Matching ignores case, the pairs are the mod names as in the list
+pub fn find_soft_order_violations(
+    result: &[String],
+    order_rules: &[EOrderRule],
+) -> Vec<(String, String)> {
+    let mods = result.iter().map(|m| m.to_lowercase()).collect::<Vec<_>>();
+    let mut violations = vec![];
+    for (a, b) in get_soft_ordering_from_order_rules_for(order_rules, &mods) {
+        if let Some(results_for_a) = wild_contains(&mods, &a) {
+            if let Some(results_for_b) = wild_contains(&mods, &b) {
+                for i in &results_for_a {
+                    for j in &results_for_b {
+                        let pos_a = mods.iter().position(|x| x == i).unwrap();
+                        let pos_b = mods.iter().position(|x| x == j).unwrap();
+                        let pair = (result[pos_a].clone(), result[pos_b].clone());
+                        if pos_a > pos_b && !violations.contains(&pair) {
+                            violations.push(pair);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    violations
+}
+
 ////////////////////////////////////////////////////////////////////////
 /// TES3
 ////////////////////////////////////////////////////////////////////////
@@ -755,6 +1516,29 @@ pub fn parse_header(f: &Path) -> std::io::Result<Tes3Header> {
     Ok(header)
 }
 
+/// Reads the description field from a TES3 plugin header
+///
+/// # Errors
+///
+/// This function will return an error if the file is not a valid TES3 plugin
+pub fn read_tes3_description(f: &Path) -> std::io::Result<String> {
+    Ok(parse_header(f)?.description)
+}
+
+/// Reads the master file names from a TES3 plugin header
+///
+/// # Errors
+///
+/// This function will return an error if the file is not a valid TES3 plugin
+pub fn read_tes3_masters(f: &Path) -> std::io::Result<Vec<String>> {
+    Ok(parse_header(f)?
+        .masters
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect())
+}
+
 fn parse_hedr<R: Read + Seek>(reader: &mut R, stream_size: u64) -> std::io::Result<Tes3Header> {
     let magic: u32 = 1380205896;
     // check magic
@@ -925,6 +1709,57 @@ pub fn get_ordering_from_order_rules(rules: &[EOrderRule]) -> Vec<(String, Strin
     orders
 }
 
+/// Extracts a list of ordering-pairs from the order rules that apply to the given mods.
+/// Order rules with a guard are skipped if the guard is false for the mods, soft order rules are always skipped
+pub fn get_ordering_from_order_rules_for(
+    rules: &[EOrderRule],
+    mods: &[String],
+) -> Vec<(String, String)> {
+    get_active_ordering(rules, mods, false)
+}
+
+/// Extracts a list of ordering-pairs from the soft order rules that apply to the given mods
+pub fn get_soft_ordering_from_order_rules_for(
+    rules: &[EOrderRule],
+    mods: &[String],
+) -> Vec<(String, String)> {
+    get_active_ordering(rules, mods, true)
+}
+
+fn get_active_ordering(rules: &[EOrderRule], mods: &[String], soft: bool) -> Vec<(String, String)> {
+    let context = expressions::EvalContext::from_mods(mods);
+
+    let active_rules = rules
+        .iter()
+        .filter(|r| match r {
+            EOrderRule::Order(o) => o.soft == soft && o.applies(&context),
+            _ => true,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    get_ordering_from_order_rules(&active_rules)
+}
+
+/// Extracts a list of ordering-pairs from the patch rules: the original loads before the patch.
+/// Only patch rules where both the patch (A) and the original (B) are atomics are considered
+pub fn get_ordering_from_patch_rules(rules: &[EWarningRule]) -> Vec<(String, String)> {
+    let mut orders: Vec<(String, String)> = vec![];
+
+    for r in rules {
+        if let EWarningRule::Patch(p) = r {
+            if let (
+                Some(expressions::Expression::Atomic(patch)),
+                Some(expressions::Expression::Atomic(original)),
+            ) = (&p.expression_a, &p.expression_b)
+            {
+                orders.push((original.get_item(), patch.get_item()));
+            }
+        }
+    }
+
+    orders
+}
+
 /// Extracts a list of ordering-pairs from the order rules
 pub fn get_ordering_from_orders(rules: &Vec<Order>) -> Vec<(String, String)> {
     let mut orders: Vec<(String, String)> = vec![];
@@ -1081,6 +1916,17 @@ pub fn wild_contains_data(list: &[PluginData], str: &str) -> Option<Vec<PluginDa
     None
 }
 
+/// Parses the rules and adds them to the order and warning rules of the parser
+pub fn add_rules(parser: &mut parser::Parser, input: &str) -> io::Result<()> {
+    for rule in parser.parse_rules_from_reader(io::Cursor::new(input.as_bytes()))? {
+        match rule {
+            ERule::EOrderRule(o) => parser.order_rules.push(o),
+            ERule::EWarningRule(w) => parser.warning_rules.push(w),
+        }
+    }
+    Ok(())
+}
+
 pub fn note(f: ERule) -> Option<Note> {
     match f {
         ERule::EWarningRule(EWarningRule::Note(n)) => Some(n),
@@ -1112,6 +1958,18 @@ pub fn patch(f: ERule) -> Option<Patch> {
         _ => None,
     }
 }
+pub fn requires_version(f: ERule) -> Option<RequiresVersion> {
+    match f {
+        ERule::EWarningRule(EWarningRule::RequiresVersion(n)) => Some(n),
+        _ => None,
+    }
+}
+pub fn blacklist(f: ERule) -> Option<Blacklist> {
+    match f {
+        ERule::EWarningRule(EWarningRule::Blacklist(n)) => Some(n),
+        _ => None,
+    }
+}
 
 // order
 pub fn order(f: ERule) -> Option<Order> {
@@ -1150,6 +2008,24 @@ pub fn nearend2(f: &EOrderRule) -> Option<NearEnd> {
         _ => None,
     }
 }
+pub fn group(f: ERule) -> Option<Group> {
+    match f {
+        ERule::EOrderRule(EOrderRule::Group(o)) => Some(o),
+        _ => None,
+    }
+}
+pub fn pin(f: ERule) -> Option<Pin> {
+    match f {
+        ERule::EOrderRule(EOrderRule::Pin(o)) => Some(o),
+        _ => None,
+    }
+}
+pub fn pin2(f: &EOrderRule) -> Option<Pin> {
+    match f {
+        EOrderRule::Pin(o) => Some(o.clone()),
+        _ => None,
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -1,10 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 use clap::{Parser, Subcommand};
 use env_logger::Env;
 use log::{error, info};
 
+use plox::rules::ESeverity;
 use plox::*;
 
 #[derive(Parser)]
@@ -54,11 +55,61 @@ enum Command {
         #[arg(short, long)]
         mod_list: Option<PathBuf>,
     },
+    /// Prints the sorted load order without changing the current load order
+    Print {
+        /// Root game folder (e.g. "Cyberpunk 2077" or "Morrowind"). Default is current working directory
+        #[arg(short, long)]
+        game_folder: Option<PathBuf>,
+
+        /// Folder to read sorting rules from. Default is ./mlox for TES3
+        #[arg(short, long)]
+        rules_dir: Option<PathBuf>,
+    },
     /// Lists the current mod load order
     List {
         /// Root game folder (e.g. "Cyberpunk 2077" or "Morrowind"). Default is current working directory
         #[arg(short, long)]
         root: Option<PathBuf>,
+
+        /// Output format, json prints the mods with their index, path and kind
+        #[arg(short, long, value_enum, default_value_t)]
+        format: EOutputFormat,
+
+        /// How the text output separates the mods
+        #[arg(short, long, value_enum, default_value_t)]
+        separator: EListFormat,
+    },
+    /// Shows the changes sorting would make to the current load order
+    Diff {
+        /// Root game folder (e.g. "Cyberpunk 2077" or "Morrowind"). Default is current working directory
+        #[arg(short, long)]
+        game_folder: Option<PathBuf>,
+
+        /// Folder to read sorting rules from. Default is ./mlox for TES3
+        #[arg(short, long)]
+        rules_dir: Option<String>,
+
+        /// Also print plugins that did not move
+        #[arg(short, long)]
+        full: bool,
+    },
+    /// Evaluates the warning rules against the current mods, fails if any warning applies
+    Check {
+        /// Root game folder (e.g. "Cyberpunk 2077" or "Morrowind"). Default is current working directory
+        #[arg(short, long)]
+        game_folder: Option<PathBuf>,
+
+        /// Folder to read sorting rules from. Default is ./mlox for TES3
+        #[arg(short, long)]
+        rules_dir: Option<String>,
+
+        /// Output format, json prints the warnings as a json array
+        #[arg(short, long, value_enum, default_value_t)]
+        format: EOutputFormat,
+
+        /// Fail only if a warning of at least this severity applies
+        #[arg(long, value_enum, default_value_t)]
+        fail_on: ESeverity,
     },
     /// Verifies integrity of the specified rules
     Verify {
@@ -66,6 +117,16 @@ enum Command {
         #[arg(short, long)]
         rules_dir: Option<String>,
     },
+    /// Checks the syntax of a rules file, no game install is needed
+    Validate {
+        /// The rules file to check
+        rules_file: PathBuf,
+    },
+    /// Checks the syntax of a rules file again whenever it changes
+    Watch {
+        /// The rules file to watch
+        rules_file: PathBuf,
+    },
     /// Outputs the rules as a graphviz dot file
     Graph {
         /// Root game folder (e.g. "Cyberpunk 2077" or "Morrowind"). Default is current working directory
@@ -112,7 +173,7 @@ fn main() -> ExitCode {
     let game = if let Some(game) = cli.game {
         info!("Set game to: {:?}", game);
         game
-    } else if let Some(g) = detect_game() {
+    } else if let Some(g) = detect_game(Path::new(".")) {
         info!("Detected game: {:?}", g);
         g
     } else {
@@ -126,8 +187,35 @@ fn main() -> ExitCode {
     };
 
     let code = match &cli.command {
-        Command::List { root } => list_mods(root, game),
+        Command::List {
+            root,
+            format,
+            separator,
+        } => list_mods(root, game, *format, *separator),
+        Command::Print {
+            game_folder,
+            rules_dir,
+        } => sort_mods(
+            game_folder,
+            game,
+            &rules_dir
+                .clone()
+                .unwrap_or_else(|| get_default_rules_dir(game)),
+        ),
         Command::Verify { rules_dir } => verify(game, rules_dir),
+        Command::Check {
+            game_folder,
+            rules_dir,
+            format,
+            fail_on,
+        } => check(game, game_folder, rules_dir, *format, *fail_on),
+        Command::Validate { rules_file } => validate_rules(rules_file, game),
+        Command::Watch { rules_file } => watch_rules(rules_file, game),
+        Command::Diff {
+            game_folder,
+            rules_dir,
+            full,
+        } => diff_order(game, game_folder, rules_dir, *full),
         Command::Graph {
             game_folder,
             rules_dir,
@@ -1,13 +1,24 @@
 ////////////////////////////////////////////////////////////////////////
 
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::File;
+use std::hash::Hasher;
 use std::io::{BufRead, BufReader, Cursor, Error, Read, Result, Seek, SeekFrom};
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use byteorder::ReadBytesExt;
 use log::*;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
-use crate::{expressions::*, rules::*, ESupportedGame, PluginData, TParser};
+use crate::{
+    error::{ParseError, RuleParseError, TopoError},
+    expressions::*,
+    get_ordering_from_order_rules, resolve_rules_dir,
+    rules::*,
+    sorter, ESupportedGame, PluginData, TParser, RULES_SCHEMA_VERSION,
+};
 
 pub fn get_parser(game: ESupportedGame, game_version: Option<String>) -> Parser {
     match game {
@@ -32,9 +43,26 @@ pub fn get_parser(game: ESupportedGame, game_version: Option<String>) -> Parser
             ESupportedGame::Cyberpunk,
             game_version,
         ),
+        ESupportedGame::Skyrim => Parser::new(
+            vec![".esp".into(), ".esm".into(), ".esl".into()],
+            ESupportedGame::Skyrim,
+            game_version,
+        ),
     }
 }
 
+/// The rules file names read for a game, in the order they are read
+pub fn default_rules_files(game: ESupportedGame) -> Vec<String> {
+    let files = match game {
+        ESupportedGame::Morrowind | ESupportedGame::Openmw => {
+            ["mlox_base.txt", "mlox_user.txt", "mlox_my_rules.txt"].as_slice()
+        }
+        ESupportedGame::Cyberpunk => ["plox_base.txt", "plox_my_rules.txt"].as_slice(),
+        ESupportedGame::Skyrim => ["skyrim_base.txt", "skyrim_user.txt"].as_slice(),
+    };
+    files.iter().map(|f| f.to_string()).collect()
+}
+
 pub fn new_cyberpunk_parser() -> Parser {
     Parser::new(vec![".archive".into()], ESupportedGame::Cyberpunk, None)
 }
@@ -47,6 +75,14 @@ pub fn new_tes3_parser() -> Parser {
     )
 }
 
+pub fn new_skyrim_parser() -> Parser {
+    Parser::new(
+        vec![".esp".into(), ".esm".into(), ".esl".into()],
+        ESupportedGame::Skyrim,
+        None,
+    )
+}
+
 pub fn new_openmw_parser() -> Parser {
     Parser::new(
         vec![
@@ -64,15 +100,330 @@ pub fn new_openmw_parser() -> Parser {
 #[derive(Debug)]
 struct ChunkWrapper {
     data: Vec<u8>,
-    info: String,
+    /// the chunk before lowercasing, to restore the case of plugin names
+    original: String,
+    line: usize,
 }
 
 impl ChunkWrapper {
-    fn new(data: Vec<u8>, info: String) -> Self {
-        Self { data, info }
+    fn new(data: Vec<u8>, original: String, line: usize) -> Self {
+        Self {
+            data,
+            original,
+            line,
+        }
+    }
+
+    /// Parses the chunk and sets where the rule starts and the original case of its plugin names
+    fn parse(&self, parser: &Parser) -> Result<ERule> {
+        let mut rule = parser.parse_chunk(Cursor::new(&self.data))?;
+        rule.set_source(RuleSource {
+            file: None,
+            line: self.line,
+        });
+
+        // lowercasing may change the length of non-ascii text, then the positions don't match up
+        let lower_case = String::from_utf8_lossy(&self.data);
+        if lower_case.len() == self.original.len() {
+            let mut restore = |atomic: &mut Atomic| {
+                let original = lower_case
+                    .find(&atomic.item)
+                    .and_then(|pos| self.original.get(pos..pos + atomic.item.len()));
+                if let Some(original) = original.filter(|o| *o != atomic.item) {
+                    atomic.display = Some(original.to_owned());
+                }
+            };
+            match &mut rule {
+                ERule::EWarningRule(w) => {
+                    for expression in w.expressions_mut() {
+                        expression.visit_atomics_mut(&mut restore);
+                    }
+                }
+                ERule::EOrderRule(EOrderRule::Order(o)) => {
+                    if let Some(guard) = &mut o.guard {
+                        guard.visit_atomics_mut(&mut restore);
+                    }
+                }
+                ERule::EOrderRule(_) => {}
+            }
+        }
+
+        Ok(rule)
+    }
+
+    /// Returns the rule kind from the chunk header, e.g. "order" for "[order]"
+    fn rule_name(&self) -> Option<String> {
+        let data = String::from_utf8_lossy(&self.data);
+        let header = data.trim_start().strip_prefix('[')?;
+        let header = header.strip_prefix('!').unwrap_or(header);
+        let name: String = header
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+}
+
+/// Splits a rules document into rule chunks line by line, a chunk ends when the next rule starts
+struct ChunkReader<R: BufRead> {
+    lines: std::iter::Enumerate<std::io::Lines<R>>,
+    chunk: Option<ChunkWrapper>,
+}
+
+impl<R: BufRead> ChunkReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines().enumerate(),
+            chunk: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ChunkReader<R> {
+    type Item = ChunkWrapper;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (idx, line) in self.lines.by_ref() {
+            let Ok(mut line) = line else {
+                break;
+            };
+            // files edited on windows may start with a BOM and contain stray carriage returns
+            if idx == 0 {
+                if let Some(rest) = line.strip_prefix('\u{feff}') {
+                    line = rest.to_owned();
+                }
+            }
+            let line = line.replace('\r', "");
+            // ignore comments
+            if line.trim_start().starts_with(';') {
+                continue;
+            }
+            // lowercase all, keep the original for display names
+            let original = strip_comment(&line).to_owned() + "\n";
+            let mut line = line.to_lowercase();
+
+            // trim inline comments
+            line = strip_comment(&line).to_owned();
+
+            // we skip empty lines
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // read to current chunk, preserving newline delimeters
+            let delimited_line = line + "\n";
+            let new_chunk = ChunkWrapper::new(
+                delimited_line.as_bytes().to_vec(),
+                original.clone(),
+                idx + 1,
+            );
+
+            // we are inside a chunk
+            if let Some(chunk) = &mut self.chunk {
+                if is_rule_start(&delimited_line) {
+                    // end current chunk
+                    return self.chunk.replace(new_chunk);
+                }
+                chunk.data.extend(delimited_line.as_bytes());
+                chunk.original.push_str(&original);
+            } else {
+                self.chunk = Some(new_chunk);
+            }
+        }
+        // last chunk
+        self.chunk.take()
+    }
+}
+
+/// Removes an inline ';' comment and trailing whitespace from a line
+pub fn strip_comment(line: &str) -> &str {
+    match line.find(';') {
+        Some(index) => line[..index].trim_end(),
+        None => line.trim_end(),
     }
 }
 
+/// The rules bundled with plox, used as a fallback if no rules files are found on disk
+pub fn bundled_rules(game: ESupportedGame) -> Option<&'static str> {
+    match game {
+        ESupportedGame::Morrowind | ESupportedGame::Openmw => {
+            Some(include_str!("bundled/mlox_bundled.txt"))
+        }
+        ESupportedGame::Cyberpunk => Some(include_str!("bundled/plox_bundled.txt")),
+        ESupportedGame::Skyrim => None,
+    }
+}
+
+/// Expands the group references in names recursively, visited holds the groups being expanded
+fn expand_group_names(
+    names: &[String],
+    groups: &HashMap<String, Vec<String>>,
+    visited: &mut Vec<String>,
+) -> Vec<String> {
+    let mut result = vec![];
+    for name in names {
+        let Some(group) = name.strip_prefix('@') else {
+            result.push(name.to_owned());
+            continue;
+        };
+        match groups.get(group) {
+            Some(_) if visited.iter().any(|v| v == group) => {
+                warn!("Group {} references itself", group);
+            }
+            Some(members) => {
+                visited.push(group.to_owned());
+                result.extend(expand_group_names(members, groups, visited));
+                visited.pop();
+            }
+            None => warn!("Unknown group {}", group),
+        }
+    }
+    result
+}
+
+/// check if a new rule has started by matching the first chars to the rules names
+fn is_rule_start(line: &str) -> bool {
+    let Some(name) = line.strip_prefix('[') else {
+        return false;
+    };
+    // disabled rules start with [!
+    let name = name.strip_prefix('!').unwrap_or(name);
+    name.starts_with("order")
+        || name.starts_with("nearstart")
+        || name.starts_with("nearend")
+        || name.starts_with("pin")
+        || name.starts_with("remove")
+        || name.starts_with("group")
+        || name.starts_with("note")
+        || name.starts_with("conflict")
+        || name.starts_with("requires")
+        || name.starts_with("patch")
+        || name.starts_with("blacklist")
+}
+
+/// Parses the optional priority of a near rule header, e.g. "10" in "[nearstart 10]".
+/// Headers without a leading number (or with a message) have priority 0
+fn parse_priority(rest: &str) -> i32 {
+    rest.split_whitespace()
+        .next()
+        .and_then(|f| f.parse::<i32>().ok())
+        .unwrap_or_default()
+}
+
+/// Parses the optional severity marker right after a warning rule name, e.g. "!" in "[note! message]".
+/// Returns the severity and the rest of the header without the marker
+fn parse_severity(rest: &str) -> (ESeverity, &str) {
+    if let Some(rest) = rest.strip_prefix('!') {
+        (ESeverity::Error, rest)
+    } else if let Some(rest) = rest.strip_prefix('?') {
+        (ESeverity::Info, rest)
+    } else {
+        (ESeverity::Warning, rest)
+    }
+}
+
+/// Gets the body of a url, file:// urls are read from disk
+#[cfg(feature = "net")]
+fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return std::fs::read(path);
+    }
+
+    let response = reqwest::blocking::get(url)
+        .map_err(|e| Error::other(format!("Could not download {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::other(format!(
+            "Could not download {}: {}",
+            url,
+            response.status()
+        )));
+    }
+    response
+        .bytes()
+        .map(|b| b.to_vec())
+        .map_err(|e| Error::other(format!("Could not download {}: {}", url, e)))
+}
+
+/// Evaluates a copy of the rule so the rule itself keeps no plugins
+fn eval_warning_rule(rule: &EWarningRule, context: &EvalContext) -> Option<EvaluatedWarning> {
+    let mut rule = rule.clone();
+    if rule.eval_context(context) {
+        Some(EvaluatedWarning::from(&Warning { rule }))
+    } else {
+        None
+    }
+}
+
+/// Checks if an expression can never be true: an ALL that requires a plugin and its absence,
+/// or an ALL or ANY whose children can't be satisfied
+fn is_unsatisfiable(expression: &Expression) -> bool {
+    match expression {
+        Expression::ALL(x) => {
+            let required = x
+                .expressions
+                .iter()
+                .filter_map(|e| match e {
+                    Expression::Atomic(a) => Some(a.item.to_lowercase()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            x.expressions.iter().any(|e| match e {
+                Expression::NOT(n) => match n.expression.as_ref() {
+                    Expression::Atomic(a) => required.contains(&a.item.to_lowercase()),
+                    _ => false,
+                },
+                _ => is_unsatisfiable(e),
+            })
+        }
+        Expression::ANY(x) => x.expressions.iter().all(is_unsatisfiable),
+        _ => false,
+    }
+}
+
+/// The default for how deep expressions may be nested
+pub const MAX_EXPRESSION_DEPTH: usize = 64;
+
+/// How plugin names in rules are compared to installed plugins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EMatchMode {
+    /// names must match exactly, rules are compared as written in the rules file
+    Exact,
+    /// names are compared lowercase
+    #[default]
+    CaseInsensitive,
+    /// names are compared lowercase, without diacritics and with collapsed whitespace
+    Normalized,
+}
+
+/// Normalizes a plugin name for comparison according to the match mode
+pub fn normalize_name(name: &str, mode: EMatchMode) -> String {
+    match mode {
+        EMatchMode::Exact => name.to_owned(),
+        EMatchMode::CaseInsensitive => name.to_lowercase(),
+        EMatchMode::Normalized => name
+            .nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .collect::<String>()
+            .to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// The parsed rules as a json document
+#[derive(Debug, Serialize, Deserialize)]
+struct RulesDocument {
+    /// the schema version, see RULES_SCHEMA_VERSION
+    version: u32,
+    order_rules: Vec<EOrderRule>,
+    warning_rules: Vec<EWarningRule>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Warning {
     pub rule: EWarningRule,
@@ -91,12 +442,49 @@ impl Warning {
         self.rule.get_plugins()
     }
 
+    /// The missing dependencies of a fired Requires rule
+    pub fn get_missing(&self) -> Vec<String> {
+        match &self.rule {
+            EWarningRule::Requires(x) => x.missing.clone(),
+            _ => vec![],
+        }
+    }
+
     pub fn get_rule_name(&self) -> String {
         match self.rule {
             EWarningRule::Conflict(_) => "Conflict".to_owned(),
             EWarningRule::Note(_) => "Note".to_owned(),
             EWarningRule::Patch(_) => "Patch".to_owned(),
             EWarningRule::Requires(_) => "Requires".to_owned(),
+            EWarningRule::RequiresVersion(_) => "RequiresVersion".to_owned(),
+            EWarningRule::Blacklist(_) => "Blacklist".to_owned(),
+        }
+    }
+}
+
+/// A warning rule that applies to a mod list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EvaluatedWarning {
+    /// the rule kind, e.g. "Conflict"
+    #[serde(rename = "kind")]
+    pub rule: String,
+    pub comment: String,
+    /// the plugins that triggered the rule
+    pub plugins: Vec<String>,
+    pub severity: ESeverity,
+    /// the missing dependencies of a Requires rule
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+impl From<&Warning> for EvaluatedWarning {
+    fn from(warning: &Warning) -> Self {
+        Self {
+            rule: warning.get_rule_name(),
+            comment: warning.get_comment(),
+            plugins: warning.get_plugins(),
+            severity: warning.rule.severity(),
+            missing: warning.get_missing(),
         }
     }
 }
@@ -106,10 +494,27 @@ pub struct Parser {
     pub game: ESupportedGame,
     pub game_version: Option<String>,
     pub ext: Vec<String>,
+    pub match_mode: EMatchMode,
+    /// how deep expressions may be nested
+    pub max_depth: usize,
 
     pub order_rules: Vec<EOrderRule>,
     pub warning_rules: Vec<EWarningRule>,
     pub warnings: Vec<Warning>,
+    /// rules that were skipped because they failed to parse
+    pub parse_errors: Vec<RuleParseError>,
+    /// parse the bundled rules if no rules files are found on disk
+    pub use_bundled_fallback: bool,
+    /// plugin groups defined by [Group] rules, by name
+    pub groups: HashMap<String, Vec<String>>,
+    /// rules file names read from the rules directory, later files add to earlier ones
+    pub rules_files: Vec<String>,
+    /// stop at the first rule that fails to parse instead of skipping it
+    pub strict: bool,
+    /// where rules fetched from a url are cached, defaults to a folder in the temp dir
+    pub cache_dir: Option<PathBuf>,
+    /// hash of the raw rules files in read order, see rules_hash
+    pub source_hash: u64,
 }
 
 impl Parser {
@@ -118,29 +523,82 @@ impl Parser {
             ext,
             game,
             game_version,
+            match_mode: EMatchMode::default(),
+            max_depth: MAX_EXPRESSION_DEPTH,
             warning_rules: vec![],
             order_rules: vec![],
             warnings: vec![],
+            parse_errors: vec![],
+            use_bundled_fallback: false,
+            groups: HashMap::new(),
+            rules_files: default_rules_files(game),
+            strict: false,
+            cache_dir: None,
+            source_hash: 0,
+        }
+    }
+
+    /// Sets the rules file names read from the rules directory, in the order they are read
+    pub fn set_rules_files(&mut self, files: Vec<String>) {
+        self.rules_files = files;
+    }
+
+    /// Sets how plugin names are compared, rules must be parsed after setting this
+    pub fn with_match_mode(mut self, mode: EMatchMode) -> Self {
+        self.match_mode = mode;
+        self
+    }
+
+    /// Sets how deep expressions may be nested before parsing fails
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets whether the bundled rules are parsed if no rules files are found on disk
+    pub fn with_bundled_fallback(mut self, use_bundled_fallback: bool) -> Self {
+        self.use_bundled_fallback = use_bundled_fallback;
+        self
+    }
+
+    /// Sets whether parsing stops with an error at the first malformed rule, e.g. to lint rules in CI.
+    /// By default malformed rules are skipped
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets where rules fetched with parse_rules_from_url are cached
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    /// Builds the context for evaluating rules, plugin names must be normalized already
+    fn eval_context(&self, plugins: &[PluginData]) -> EvalContext {
+        match self.match_mode {
+            EMatchMode::Exact => EvalContext::from_plugins(plugins).exact(),
+            _ => EvalContext::from_plugins(plugins),
         }
     }
 
     /// Evaluates all warning rules and stores a copy of them in self
     /// Retrieve them with self.warnings
     pub fn evaluate_plugins(&mut self, plugins: &[PluginData]) {
-        // lowercase all plugin names
+        // normalize all plugin names
         let mods_cpy: Vec<_> = plugins
             .iter()
             .map(|f| {
                 let mut x = f.clone();
-                let name_lc = x.name.to_lowercase();
-                x.name = name_lc;
+                x.name = normalize_name(&x.name, self.match_mode);
                 x
             })
             .collect();
 
+        let context = self.eval_context(&mods_cpy);
         let mut result = vec![];
         for rule in &mut self.warning_rules {
-            if rule.eval(&mods_cpy) {
+            if rule.eval_context(&context) {
                 result.push(Warning { rule: rule.clone() });
             }
         }
@@ -148,6 +606,189 @@ impl Parser {
         self.warnings = result;
     }
 
+    /// Evaluates all warning rules against a list of mod names and returns the warnings in rule order.
+    /// Unlike evaluate_plugins this does not store the warnings in self
+    #[cfg(not(feature = "parallel"))]
+    pub fn evaluate(&self, mods: &[String]) -> Vec<EvaluatedWarning> {
+        self.evaluate_serial(mods)
+    }
+
+    /// Evaluates all warning rules against a list of mod names and returns the warnings in rule order.
+    /// Unlike evaluate_plugins this does not store the warnings in self
+    #[cfg(feature = "parallel")]
+    pub fn evaluate(&self, mods: &[String]) -> Vec<EvaluatedWarning> {
+        self.evaluate_parallel(mods)
+    }
+
+    /// Evaluates all warning rules one after another, see evaluate
+    pub fn evaluate_serial(&self, mods: &[String]) -> Vec<EvaluatedWarning> {
+        let plugins = self.normalized_plugins(mods);
+        let context = self.eval_context(&plugins);
+        self.warning_rules
+            .iter()
+            .filter_map(|rule| eval_warning_rule(rule, &context))
+            .collect()
+    }
+
+    /// Evaluates the warning rules on all threads, see evaluate.
+    /// The warnings are returned in rule order like evaluate_serial
+    #[cfg(feature = "parallel")]
+    pub fn evaluate_parallel(&self, mods: &[String]) -> Vec<EvaluatedWarning> {
+        use rayon::prelude::*;
+
+        let plugins = self.normalized_plugins(mods);
+        let context = self.eval_context(&plugins);
+        let mut warnings = self
+            .warning_rules
+            .par_iter()
+            .enumerate()
+            .filter_map(|(i, rule)| eval_warning_rule(rule, &context).map(|w| (i, w)))
+            .collect::<Vec<_>>();
+
+        warnings.sort_by_key(|(i, _)| *i);
+        warnings.into_iter().map(|(_, w)| w).collect()
+    }
+
+    fn normalized_plugins(&self, mods: &[String]) -> Vec<PluginData> {
+        mods.iter()
+            .map(|m| PluginData::new(normalize_name(m, self.match_mode), 0))
+            .collect()
+    }
+
+    /// Evaluates all warning rules against the mods in load order like evaluate,
+    /// ordered notes only fire here since they need the position of each mod
+    pub fn evaluate_ordered(&self, ordered_mods: &[String]) -> Vec<EvaluatedWarning> {
+        let names = ordered_mods
+            .iter()
+            .map(|m| normalize_name(m, self.match_mode))
+            .collect::<Vec<_>>();
+
+        self.warning_rules
+            .iter()
+            .filter_map(|rule| {
+                let mut rule = rule.clone();
+                if rule.eval_ordered(&names) {
+                    Some(EvaluatedWarning::from(&Warning { rule }))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Evaluates the warning rule at index against a list of mod names like evaluate.
+    /// Returns None if the rule does not apply or the index is out of bounds
+    pub fn eval_rule(&self, index: usize, mods: &[String]) -> Option<EvaluatedWarning> {
+        let rule = self.warning_rules.get(index)?;
+        eval_warning_rule(rule, &self.eval_context(&self.normalized_plugins(mods)))
+    }
+
+    /// Returns the indices of warning rules that can never fire: every atomic lacks a valid plugin extension,
+    /// or every top-level expression is unsatisfiable, e.g. [ALL a.esp [NOT a.esp]]
+    pub fn find_dead_rules(&self) -> Vec<usize> {
+        self.warning_rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| {
+                // ordered notes compare positions and have no expressions to check
+                let expressions = rule.expressions();
+                if expressions.is_empty()
+                    || matches!(rule, EWarningRule::Note(n) if n.order.is_some())
+                {
+                    return false;
+                }
+
+                let mut atomics = vec![];
+                for expression in &expressions {
+                    expression.visit_atomics(&mut |a| atomics.push(a.item.clone()));
+                }
+
+                let no_valid_atomic = atomics.iter().all(|a| !self.ends_with_vec(a));
+                let unsatisfiable = expressions.iter().all(|e| is_unsatisfiable(e));
+                no_valid_atomic || unsatisfiable
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Sorts each group of mod names independently with the parsed rules, e.g. the load orders of several profiles.
+    /// The rules are parsed once, the order edges are built per group since they depend on the mods in the group
+    pub fn sort_many(
+        &self,
+        groups: &[Vec<String>],
+    ) -> Vec<std::result::Result<Vec<String>, TopoError>> {
+        let mut sorter = sorter::new_stable_sorter();
+        groups
+            .iter()
+            .map(|mods| {
+                let plugins = mods
+                    .iter()
+                    .map(|m| PluginData::new(m.to_owned(), 0))
+                    .collect::<Vec<_>>();
+                sorter.topo_sort(self.game, &plugins, &self.order_rules, &self.warning_rules)
+            })
+            .collect()
+    }
+
+    /// Collects every plugin name referenced by the order rules, the warning rule expressions and the groups.
+    /// Unresolved group references (@name) are skipped
+    pub fn referenced_plugins(&self) -> BTreeSet<String> {
+        let mut result = BTreeSet::new();
+        for rule in &self.order_rules {
+            let (names, guard) = rule.names();
+            result.extend(names.into_iter().filter(|n| !n.starts_with('@')));
+            if let Some(guard) = guard {
+                guard.visit_atomics(&mut |a| {
+                    result.insert(a.item.clone());
+                });
+            }
+        }
+        for rule in &self.warning_rules {
+            for expression in rule.expressions() {
+                expression.visit_atomics(&mut |a| {
+                    result.insert(a.item.clone());
+                });
+            }
+        }
+        for names in self.groups.values() {
+            result.extend(names.iter().filter(|n| !n.starts_with('@')).cloned());
+        }
+        result
+    }
+
+    /// Serializes all parsed rules to json
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if serialization fails
+    pub fn export_rules_json(&self) -> serde_json::Result<String> {
+        let document = RulesDocument {
+            version: RULES_SCHEMA_VERSION,
+            order_rules: self.order_rules.clone(),
+            warning_rules: self.warning_rules.clone(),
+        };
+        serde_json::to_string_pretty(&document)
+    }
+
+    /// Replaces all rules in self with the rules from a json document created with export_rules_json
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the json is not a valid rules document
+    /// or was written with a different schema version
+    pub fn import_rules_json(&mut self, json: &str) -> serde_json::Result<()> {
+        let document: RulesDocument = serde_json::from_str(json)?;
+        if document.version != RULES_SCHEMA_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported rules schema version {}, expected {}",
+                document.version, RULES_SCHEMA_VERSION
+            )));
+        }
+        self.order_rules = document.order_rules;
+        self.warning_rules = document.warning_rules;
+        Ok(())
+    }
+
     /// Parse rules for a specific game from a file and stores them in self.
     ///
     /// # Errors
@@ -162,15 +803,72 @@ impl Parser {
             return Ok(());
         }
 
-        let rules = self.parse_rules_from_path(&path)?;
+        self.hash_source(&std::fs::read(&path)?);
+        let (rules, errors) = self.parse_rules_from_path(&path)?;
+        if !errors.is_empty() {
+            warn!(
+                "Skipped {} malformed rules in {}",
+                errors.len(),
+                path.as_ref().display()
+            );
+        }
+        self.parse_errors.extend(errors);
         info!(
             "Parsed file {} with {} rules",
             path.as_ref().display(),
             rules.len()
         );
 
+        self.add_rules(rules);
+        Ok(())
+    }
+
+    /// Parses the rules bundled with plox for this game and stores them in self
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if parsing fails
+    pub fn init_from_bundled(&mut self) -> Result<()> {
+        let Some(bundled) = bundled_rules(self.game) else {
+            warn!("No bundled rules for {:?}", self.game);
+            return Ok(());
+        };
+
+        self.hash_source(bundled.as_bytes());
+        let rules = self.parse_rules_from_reader(Cursor::new(bundled.as_bytes()))?;
+        info!("Parsed bundled rules with {} rules", rules.len());
+
+        self.add_rules(rules);
+        Ok(())
+    }
+
+    /// Adds the raw bytes of a rules source to the source hash
+    fn hash_source(&mut self, bytes: &[u8]) {
+        let mut hasher = seahash::SeaHasher::new();
+        hasher.write_u64(self.source_hash);
+        hasher.write(bytes);
+        self.source_hash = hasher.finish();
+    }
+
+    /// A stable hash of the rules files read so far, changes when the content or the read order of the files changes.
+    /// Tools can compare it to a stored hash to detect changed rules
+    pub fn rules_hash(&self) -> String {
+        format!("{:016x}", self.source_hash)
+    }
+
+    fn add_rules(&mut self, rules: Vec<ERule>) {
         for r in rules {
+            if r.is_disabled() {
+                debug!(
+                    "Skipping disabled rule at line {:?}",
+                    r.source().map(|s| s.line)
+                );
+                continue;
+            }
             match r {
+                ERule::EOrderRule(EOrderRule::Group(g)) => {
+                    self.groups.insert(g.name, g.names);
+                }
                 ERule::EOrderRule(o) => {
                     self.order_rules.push(o);
                 }
@@ -179,8 +877,6 @@ impl Parser {
                 }
             }
         }
-
-        Ok(())
     }
 
     /// Parse rules for a specific game, expects the path to be the rules directory
@@ -194,18 +890,26 @@ impl Parser {
     {
         self.warning_rules.clear();
         self.order_rules.clear();
-
-        let rules_files = match self.game {
-            ESupportedGame::Morrowind | ESupportedGame::Openmw => {
-                ["mlox_base.txt", "mlox_user.txt", "mlox_my_rules.txt"].as_slice()
-            }
-            ESupportedGame::Cyberpunk => ["plox_base.txt", "plox_my_rules.txt"].as_slice(),
-        };
-
-        for file in rules_files {
-            let path = path.as_ref().join(file);
+        self.parse_errors.clear();
+        self.groups.clear();
+        self.source_hash = 0;
+
+        // expand ~ and environment variables, a path that does not resolve is used as is
+        let rules_dir = resolve_rules_dir(&path.as_ref().to_string_lossy())
+            .unwrap_or_else(|_| path.as_ref().to_path_buf());
+
+        let mut found_any = false;
+        for file in self.rules_files.clone() {
+            let path = rules_dir.join(file);
+            found_any |= path.exists();
             self.init_from_file(path)?;
         }
+        if !found_any && self.use_bundled_fallback {
+            info!("No rules files found, using bundled rules");
+            self.init_from_bundled()?;
+        }
+        self.expand_groups();
+        self.merge_rules();
 
         info!(
             "Parser initialized with {} order rules",
@@ -218,111 +922,225 @@ impl Parser {
         Ok(())
     }
 
+    /// Replaces the group references (@name) in order rules with the plugins of the group.
+    /// Call this after all rules files were read, so that groups may be defined in any file
+    pub fn expand_groups(&mut self) {
+        for rule in &mut self.order_rules {
+            let names = match rule {
+                EOrderRule::Order(x) => &mut x.names,
+                EOrderRule::NearStart(x) => &mut x.names,
+                EOrderRule::NearEnd(x) => &mut x.names,
+                EOrderRule::Remove(x) => &mut x.names,
+                EOrderRule::Pin(_) | EOrderRule::Group(_) => continue,
+            };
+            if names.iter().any(|n| n.starts_with('@')) {
+                *names = expand_group_names(names, &self.groups, &mut vec![]);
+            }
+        }
+    }
+
+    /// Applies [Remove] rules and deduplicates [Order] rules, call this after all rules files were read.
+    /// Two ordering pairs are equal if both names are equal ignoring case, wildcards are compared literally.
+    /// Order rules that lose a pair are split into one rule per remaining pair
+    pub fn merge_rules(&mut self) {
+        let key = |(a, b): &(String, String)| (a.to_lowercase(), b.to_lowercase());
+
+        // pairs cancelled by remove rules
+        let removed = self
+            .order_rules
+            .iter()
+            .filter_map(|r| match r {
+                EOrderRule::Remove(x) if x.names.len() > 1 => Some(x),
+                _ => None,
+            })
+            .flat_map(|x| get_ordering_from_order_rules(&[Order::new(x.names.clone()).into()]))
+            .map(|p| key(&p))
+            .collect::<HashSet<_>>();
+
+        let mut seen: HashSet<(String, String)> = HashSet::new();
+        let mut merged = vec![];
+        for rule in self.order_rules.drain(..) {
+            match rule {
+                EOrderRule::Remove(_) => {}
+                // guarded and soft orders are conditional and never merged
+                EOrderRule::Order(order) if order.guard.is_some() || order.soft => {
+                    merged.push(order.into())
+                }
+                EOrderRule::Order(order) => {
+                    let pairs = get_ordering_from_order_rules(&[order.clone().into()]);
+                    let keep = pairs
+                        .into_iter()
+                        .filter(|p| {
+                            let k = key(p);
+                            !removed.contains(&k) && seen.insert(k)
+                        })
+                        .collect::<Vec<_>>();
+
+                    let all_pairs = order.names.len() * order.names.len().saturating_sub(1) / 2;
+                    if keep.len() == all_pairs {
+                        merged.push(order.into());
+                    } else {
+                        // split rules keep the source of the original rule
+                        merged.extend(keep.into_iter().map(|(a, b)| {
+                            Order {
+                                source: order.source.clone(),
+                                ..Order::from(&a, &b)
+                            }
+                            .into()
+                        }));
+                    }
+                }
+                other => merged.push(other),
+            }
+        }
+
+        if !removed.is_empty() {
+            info!("Removed {} order pairs", removed.len());
+        }
+        self.order_rules = merged;
+    }
+
     /// Parse rules from a rules file
     ///
     /// # Errors
     ///
     /// This function will return an error if file io or parsing fails
-    fn parse_rules_from_path<P>(&self, path: P) -> Result<Vec<ERule>>
+    fn parse_rules_from_path<P>(&self, path: P) -> Result<(Vec<ERule>, Vec<RuleParseError>)>
     where
         P: AsRef<Path>,
     {
-        let file = File::open(path)?;
+        let file = File::open(&path)?;
         let reader = BufReader::new(file);
-        let rules = self.parse_rules_from_reader(reader)?;
-        Ok(rules)
+        let (mut rules, errors) = self.parse_rules_with_errors(reader)?;
+
+        // attach the file to the source of each rule
+        for rule in &mut rules {
+            let line = rule.source().map(|s| s.line).unwrap_or_default();
+            rule.set_source(RuleSource {
+                file: Some(path.as_ref().to_path_buf()),
+                line,
+            });
+        }
+
+        Ok((rules, errors))
     }
 
-    /// Parse rules from a reader
+    /// Downloads a rules file and parses it like parse_rules_from_reader.
+    /// The download is cached in cache_dir, if the url can't be fetched the cached copy is parsed instead
     ///
     /// # Errors
     ///
-    /// This function will return an error if parsing fails
+    /// This function will return an error if the download fails without a cached copy, e.g. on a non-200 response,
+    /// or if parsing fails
+    #[cfg(feature = "net")]
+    pub fn parse_rules_from_url(&self, url: &str) -> Result<Vec<ERule>> {
+        let cache_dir = self
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("plox_rules_cache"));
+        // different urls may end in the same file name
+        let file_name = url
+            .rsplit('/')
+            .next()
+            .filter(|n| !n.is_empty())
+            .unwrap_or("rules.txt");
+        let cache_path =
+            cache_dir.join(format!("{:x}_{}", seahash::hash(url.as_bytes()), file_name));
+
+        let body = match fetch_url(url) {
+            Ok(body) => {
+                std::fs::create_dir_all(&cache_dir)?;
+                std::fs::write(&cache_path, &body)?;
+                body
+            }
+            Err(err) if cache_path.exists() => {
+                warn!("{}, using cached rules {}", err, cache_path.display());
+                std::fs::read(&cache_path)?
+            }
+            Err(err) => {
+                warn!("{}", err);
+                return Err(err);
+            }
+        };
+
+        self.parse_rules_from_reader(Cursor::new(body))
+    }
+
+    /// Parse rules from a reader, malformed rules are skipped unless the parser is strict
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if parsing fails, or at the first malformed rule in strict mode
     pub fn parse_rules_from_reader<R>(&self, reader: R) -> Result<Vec<ERule>>
     where
         R: Read + BufRead + Seek,
     {
-        // pre-parse into rule blocks
-        let mut chunks: Vec<ChunkWrapper> = vec![];
-        let mut chunk: Option<ChunkWrapper> = None;
-        for (idx, line) in reader.lines().map_while(Result::ok).enumerate() {
-            // ignore comments
-            if line.trim_start().starts_with(';') {
-                continue;
-            }
-            // lowercase all
-            let mut line = line.to_lowercase();
-
-            // trim inline comments
-            line = if let Some(index) = line.find(';') {
-                line[..index].trim_end().to_owned()
-            } else {
-                line.trim_end().to_owned()
-            };
-
-            // we skip empty lines
-            if line.trim().is_empty() {
-                continue;
-            }
-
-            fn new_rule(line: &str) -> bool {
-                // check if a new rule has started by matching the first chars to the rules names
-                line.starts_with("[order")
-                    || line.starts_with("[nearstart")
-                    || line.starts_with("[nearend")
-                    || line.starts_with("[note")
-                    || line.starts_with("[conflict")
-                    || line.starts_with("[requires")
-                    || line.starts_with("[patch")
-            }
+        if self.strict {
+            return self.parse_rules_iter(reader).collect();
+        }
 
-            // we are inside a chunk
-            if chunk.is_some() && new_rule(&line) {
-                // end current chunk
-                if let Some(chunk) = chunk.take() {
-                    chunks.push(chunk);
+        // skip rules that fail to parse
+        Ok(self
+            .parse_rules_iter(reader)
+            .filter_map(|rule| match rule {
+                Ok(rule) => Some(rule),
+                Err(err) => {
+                    debug!("Skipping rule: {}", err);
+                    None
                 }
-            }
+            })
+            .collect())
+    }
 
-            // read to current chunk, preserving newline delimeters
-            let delimited_line = line + "\n";
-            if let Some(chunk) = &mut chunk {
-                chunk.data.extend(delimited_line.as_bytes());
-            } else {
-                chunk = Some(ChunkWrapper::new(
-                    delimited_line.as_bytes().to_vec(),
-                    (idx + 1).to_string(),
-                ));
-            }
-        }
-        // parse last chunk
-        if let Some(chunk) = chunk.take() {
-            chunks.push(chunk);
-        }
+    /// Lazily parse rules from a reader, yielding each rule (or its error) as soon as its chunk is read.
+    /// Rules that fail to parse are yielded as errors and parsing continues with the next rule
+    pub fn parse_rules_iter<'a, R>(&'a self, reader: R) -> impl Iterator<Item = Result<ERule>> + 'a
+    where
+        R: BufRead + 'a,
+    {
+        ChunkReader::new(reader).map(|chunk| chunk.parse(self))
+    }
 
-        // process chunks
+    /// Parse rules from a reader and also return all rules that were skipped
+    /// together with the line they start at
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading fails, or at the first malformed rule in strict mode
+    pub fn parse_rules_with_errors<R>(&self, reader: R) -> Result<(Vec<ERule>, Vec<RuleParseError>)>
+    where
+        R: Read + BufRead + Seek,
+    {
         let mut rules: Vec<ERule> = vec![];
-        for (idx, chunk) in chunks.into_iter().enumerate() {
-            let info = &chunk.info;
-
-            let cursor = Cursor::new(&chunk.data);
-            match self.parse_chunk(cursor) {
+        let mut errors: Vec<RuleParseError> = vec![];
+        for (idx, chunk) in ChunkReader::new(reader).enumerate() {
+            match chunk.parse(self) {
                 Ok(it) => {
                     rules.push(it);
                 }
                 Err(err) => {
+                    let error = RuleParseError {
+                        rule: chunk.rule_name(),
+                        line: chunk.line,
+                        error: ParseError::from_io(&err),
+                    };
+                    if self.strict {
+                        warn!("{}", error);
+                        return Err(err);
+                    }
+
                     // log error and skip chunk
                     debug!(
                         "Error '{}' at chunk #{}, starting at line: {}",
-                        err, idx, info
+                        err, idx, chunk.line
                     );
-                    let string = String::from_utf8(chunk.data).expect("not valid utf8");
-                    debug!("{}", string);
+                    errors.push(error);
+                    debug!("{}", String::from_utf8_lossy(&chunk.data));
                 }
             };
         }
 
-        Ok(rules)
+        Ok((rules, errors))
     }
 
     /// Parses on rule section. Note: Order rules are returned as vec
@@ -342,34 +1160,103 @@ impl Parser {
                 // read until the end of the rule expression: e.g. [NOTE comment] body
                 if let Ok((mut rule_expression, ruletype)) = parse_rule_expression(&mut reader) {
                     rule_expression.pop();
+                    // disabled rules are parsed as usual: [!Note comment]
+                    let disabled = rule_expression.starts_with('!');
+                    if disabled {
+                        rule_expression.remove(0);
+                    }
                     let mut rule: ERule;
                     // parse rule name
                     {
-                        if rule_expression.strip_prefix("order").is_some() {
-                            rule = Order::default().into();
-                        } else if rule_expression.strip_prefix("nearstart").is_some() {
-                            rule = NearStart::default().into();
-                        } else if rule_expression.strip_prefix("nearend").is_some() {
-                            rule = NearEnd::default().into();
+                        if let Some(rest) = rule_expression.strip_prefix("order") {
+                            // [Order?] is a soft order
+                            rule = Order::default().with_soft(rest.starts_with('?')).into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("nearstart") {
+                            rule = NearStart::default()
+                                .with_priority(parse_priority(rest))
+                                .into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("nearend") {
+                            rule = NearEnd::default()
+                                .with_priority(parse_priority(rest))
+                                .into();
+                        } else if rule_expression.strip_prefix("remove").is_some() {
+                            rule = Remove::default().into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("group") {
+                            rule = Group::new(rest.trim().to_owned(), vec![]).into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("pin") {
+                            let index = rest.trim().parse::<usize>().map_err(|_| {
+                                Error::other(ParseError::MalformedRule(format!(
+                                    "Parsing error: invalid Pin index '{}'",
+                                    rest.trim()
+                                )))
+                            })?;
+                            rule = Pin::new(String::new(), index).into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("noteorder") {
+                            // [NoteOrder comment] a.esp b.esp fires when a loads after b
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Note {
+                                severity,
+                                order: Some(Default::default()),
+                                ..Default::default()
+                            };
+                            x.set_comment(rest.trim().to_owned());
+                            rule = x.into();
                         } else if let Some(rest) = rule_expression.strip_prefix("note") {
-                            let mut x = Note::default();
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Note {
+                                severity,
+                                ..Default::default()
+                            };
                             x.set_comment(rest.trim().to_owned());
                             rule = x.into();
                         } else if let Some(rest) = rule_expression.strip_prefix("conflict") {
-                            let mut x = Conflict::default();
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Conflict {
+                                severity,
+                                ..Default::default()
+                            };
                             x.set_comment(rest.trim().to_owned());
                             rule = x.into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("requiresversion") {
+                            // [RequiresVersion >=0.48 comment]
+                            let (severity, rest) = parse_severity(rest);
+                            let rest = rest.trim();
+                            let (version, comment) =
+                                rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                            let mut x = RequiresVersion {
+                                version: version.to_owned(),
+                                severity,
+                                ..Default::default()
+                            };
+                            x.set_comment(comment.trim().to_owned());
+                            rule = x.into();
                         } else if let Some(rest) = rule_expression.strip_prefix("requires") {
-                            let mut x = Requires::default();
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Requires {
+                                severity,
+                                ..Default::default()
+                            };
                             x.set_comment(rest.trim().to_owned());
                             rule = x.into();
                         } else if let Some(rest) = rule_expression.strip_prefix("patch") {
-                            let mut x = Patch::default();
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Patch {
+                                severity,
+                                ..Default::default()
+                            };
+                            x.set_comment(rest.trim().to_owned());
+                            rule = x.into();
+                        } else if let Some(rest) = rule_expression.strip_prefix("blacklist") {
+                            let (severity, rest) = parse_severity(rest);
+                            let mut x = Blacklist {
+                                severity,
+                                ..Default::default()
+                            };
                             x.set_comment(rest.trim().to_owned());
                             rule = x.into();
                         } else {
                             // unknown rule, skip
-                            return Err(Error::other("Parsing error: unknown rule"));
+                            return Err(Error::other(ParseError::UnknownRule));
                         }
                     }
 
@@ -379,61 +1266,56 @@ impl Parser {
                             // inline rules don't have comments, we just parse the resst of the chunk
                             // now parse rule body
                             ERule::parse(&mut rule, reader, self)?;
+                            rule.set_disabled(disabled);
                             Ok(rule)
                         }
                         ERuleType::Multiline => {
                             // construct the body out of each line with comments trimmed
-                            let mut is_first_line = false;
-                            let mut comment = String::new();
+                            // indented lines before the first expression are the message:
+                            // [Note]
+                            //  message line
+                            //  more message
+                            // plugin.esp
+                            let mut comment_lines: Vec<String> = vec![];
+                            let mut is_comment = true;
                             let mut body = String::new();
-                            for (idx, line) in reader
+                            for line in reader
                                 .lines()
                                 .map_while(Result::ok)
-                                .map(|f| f.to_owned())
                                 .filter(|p| !p.trim().is_empty())
-                                .enumerate()
                             {
-                                if idx == 0 {
-                                    is_first_line = true;
-                                }
-
-                                // check for those darned comments
-                                if is_first_line {
-                                    if let Some(first_char) = line.chars().next() {
-                                        if first_char.is_ascii_whitespace() {
-                                            comment += line.as_str();
-                                            continue;
-                                        }
-                                    }
-
-                                    if !comment.is_empty() {
-                                        if let ERule::EWarningRule(w) = &mut rule {
-                                            w.set_comment(comment.clone().trim().into());
-                                        }
-                                        comment.clear();
-                                    }
-
-                                    is_first_line = false;
+                                if is_comment && line.starts_with(|c: char| c.is_whitespace()) {
+                                    comment_lines.push(line.trim().to_owned());
+                                    continue;
                                 }
+                                is_comment = false;
 
                                 // this is a proper line
                                 body += format!("{}\n", line).as_str();
                             }
 
+                            // multi-line messages are joined with a space
+                            if !comment_lines.is_empty() {
+                                if let ERule::EWarningRule(w) = &mut rule {
+                                    w.set_comment(comment_lines.join(" "));
+                                }
+                            }
+
                             // now parse rule body
                             let body = body.trim();
                             let body_cursor = Cursor::new(body);
                             ERule::parse(&mut rule, body_cursor, self)?;
+                            rule.set_disabled(disabled);
                             Ok(rule)
                         }
                     }
                 } else {
-                    Err(Error::other("Parsing error: unknown rule"))
+                    Err(Error::other(ParseError::UnknownRule))
                 }
             }
             _ => {
                 // error
-                Err(Error::other("Parsing error: Not a rule start"))
+                Err(Error::other(ParseError::NotARuleStart))
             }
         }
     }
@@ -474,55 +1356,85 @@ impl Parser {
         b
     }
 
+    /// Splits a line of plugin names into tokens like tokenize, group references (@name) end at whitespace
+    pub fn tokenize_names(&self, line: String) -> Vec<String> {
+        let mut tokens = vec![];
+        for token in self.tokenize(line) {
+            match token.strip_prefix('@') {
+                Some(rest) if rest.contains(char::is_whitespace) => {
+                    let (reference, rest) =
+                        token.split_once(char::is_whitespace).unwrap_or_default();
+                    tokens.push(reference.to_owned());
+                    tokens.extend(self.tokenize_names(rest.trim().to_owned()));
+                }
+                _ => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
     /// Splits a String into string tokens (either separated by extension or wrapped in quotation marks)
+    /// Inside quotation marks \" is a literal quote and \\ a literal backslash
     pub fn tokenize(&self, line: String) -> Vec<String> {
-        let mut tokens: Vec<String> = vec![];
+        self.tokenize_spans(&line)
+            .into_iter()
+            .map(|(_, token)| token)
+            .collect()
+    }
+
+    /// Splits a line into tokens like tokenize and returns each token with its byte range in the line
+    ///
+    /// The range covers the token text only: surrounding whitespace and the quotes of a quoted token are excluded
+    pub fn tokenize_spans(&self, line: &str) -> Vec<(Range<usize>, String)> {
+        let mut tokens: Vec<(Range<usize>, String)> = vec![];
 
         // ignore everything after ;
-        let line = line.clone();
-        if line.contains(';') {
-            line.split(';')
-                .next()
-                .unwrap_or("")
-                .trim()
-                .clone_into(&mut line.to_owned());
-        }
+        let line = strip_comment(line);
 
         let mut is_quoted = false;
         let mut current_token: String = "".to_owned();
-        for c in line.chars() {
-            // check quoted and read in chars
+        // byte range of the non-whitespace chars of the current token
+        let mut span: Option<Range<usize>> = None;
+        let mut flush = |token: &mut String, span: &mut Option<Range<usize>>, pos: usize| {
+            let range = span.take().unwrap_or(pos..pos);
+            tokens.push((range, token.trim().to_owned()));
+            token.clear();
+        };
+
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            // inside quotes \" is a literal quote and \\ a literal backslash, other backslashes are kept
+            if is_quoted && c == '\\' {
+                if let Some(&(_, next)) = chars.peek().filter(|(_, n)| *n == '"' || *n == '\\') {
+                    chars.next();
+                    current_token.push(next);
+                    span = Some(span.map_or(i..i + 2, |s| s.start..i + 2));
+                    continue;
+                }
+            }
+
+            // quoted segments are one token and only end at the closing quote
             if c == '"' {
-                // started a quoted segment
                 if is_quoted {
-                    is_quoted = false;
-                    // end token
-                    tokens.push(current_token.trim().to_owned());
-                    current_token.clear();
-                } else {
-                    is_quoted = true;
+                    flush(&mut current_token, &mut span, i);
                 }
+                is_quoted = !is_quoted;
                 continue;
             }
-            current_token += c.to_string().as_str();
-
-            // check if we found an end
-            if self.ends_with_vec_whitespace(&current_token) {
-                // ignore whitespace in quoted segments
-                if !is_quoted {
-                    // end token
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.trim().to_owned());
-                        current_token.clear();
-                    }
-                } else {
-                    current_token += c.to_string().as_str();
-                }
+            current_token.push(c);
+            if !c.is_whitespace() {
+                let end = i + c.len_utf8();
+                span = Some(span.map_or(i..end, |s| s.start..end));
+            }
+
+            // outside of quotes a token ends with an extension followed by whitespace
+            if !is_quoted && self.ends_with_vec_whitespace(&current_token) {
+                flush(&mut current_token, &mut span, i);
             }
         }
 
         if !current_token.is_empty() {
-            tokens.push(current_token.trim().to_owned());
+            flush(&mut current_token, &mut span, line.len());
         }
 
         tokens
@@ -533,7 +1445,15 @@ impl Parser {
     /// # Errors
     ///
     /// This function will return an error if parsing fails anywhere
-    pub fn parse_expressions<R>(&self, mut reader: R) -> Result<Vec<Expression>>
+    pub fn parse_expressions<R>(&self, reader: R) -> Result<Vec<Expression>>
+    where
+        R: Read + BufRead,
+    {
+        self.parse_expressions_at(reader, 0)
+    }
+
+    /// Parses all expressions from a buffer, depth is the nesting level of the buffer
+    fn parse_expressions_at<R>(&self, mut reader: R, depth: usize) -> Result<Vec<Expression>>
     where
         R: Read + BufRead,
     {
@@ -545,22 +1465,23 @@ impl Parser {
         let mut current_buffer: String = String::new();
         let mut is_expr = false;
         let mut is_token = false;
-        let mut depth = 0;
+        let mut bracket_depth = 0;
 
-        for b in buffer {
+        // iterate chars, not bytes, so that non-ascii names stay intact
+        for b in String::from_utf8_lossy(&buffer).chars() {
             if is_expr {
                 // if parsing an expression, just count brackets and read the rest into the buffer
-                if b == b'[' {
-                    depth += 1;
-                } else if b == b']' {
-                    depth -= 1;
+                if b == '[' {
+                    bracket_depth += 1;
+                } else if b == ']' {
+                    bracket_depth -= 1;
                 }
-                current_buffer += &(b as char).to_string();
+                current_buffer.push(b);
 
                 // check if really an expression
                 // valid expressions are [ANY], [ALL], [NOT], [DESC], [SIZE], [VER], [GVER]
 
-                if depth == 0 {
+                if bracket_depth == 0 {
                     // TODO get the list from the traits
                     // we reached the end of the current expression
                     let trimmed = current_buffer.trim();
@@ -585,7 +1506,7 @@ impl Parser {
                 }
             } else if is_token {
                 // if parsing tokens, check when ".archive" was parsed into the buffer and end
-                current_buffer += &(b as char).to_string();
+                current_buffer.push(b);
 
                 if self.ends_with_vec_whitespace_or_newline(&current_buffer) {
                     is_token = false;
@@ -594,16 +1515,16 @@ impl Parser {
                 }
             } else {
                 // this marks the beginning
-                if b == b'[' {
+                if b == '[' {
                     // start an expression
                     is_expr = true;
-                    depth += 1;
+                    bracket_depth += 1;
                 }
                 // ignore whitespace
                 else if !b.is_ascii_whitespace() {
                     is_token = true;
                 }
-                current_buffer += &(b as char).to_string();
+                current_buffer.push(b);
             }
         }
 
@@ -621,7 +1542,7 @@ impl Parser {
 
         let mut expressions: Vec<Expression> = vec![];
         for (chunk, is_expr) in chunks {
-            match self.parse_expression(chunk.as_str(), is_expr) {
+            match self.parse_expression_at(chunk.as_str(), is_expr, depth) {
                 Ok(it) => {
                     expressions.push(it);
                 }
@@ -638,72 +1559,130 @@ impl Parser {
     ///
     /// This function will return an error if parsing fails
     pub fn parse_expression(&self, reader: &str, is_expression: bool) -> Result<Expression> {
+        self.parse_expression_at(reader, is_expression, 0)
+    }
+
+    /// Parses a single expression, depth is the nesting level of the expression
+    fn parse_expression_at(
+        &self,
+        reader: &str,
+        is_expression: bool,
+        depth: usize,
+    ) -> Result<Expression> {
         // an expression may start with
         if !is_expression {
             // is a token
             // in this case just return an atomic
             if !self.ends_with_vec(reader) {
-                return Err(Error::other("Parsing error: Not an atomic"));
+                return Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: Not an atomic".into(),
+                )));
             }
 
+            // rule text is already lowercased, only normalized mode changes the name further
+            if self.match_mode == EMatchMode::Normalized {
+                return Ok(Atomic::from(normalize_name(reader, self.match_mode)).into());
+            }
             return Ok(Atomic::from(reader).into());
         }
 
         if reader.starts_with('[') {
+            // guard against overflowing the stack
+            if depth >= self.max_depth {
+                return Err(Error::other(ParseError::MaxDepthExceeded(self.max_depth)));
+            }
+            // an unclosed expression would cut off its last char below
+            if !reader.ends_with(']') {
+                return Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unclosed expression".into(),
+                )));
+            }
+
             // is an expression
             // parse the kind and reurse down
             if let Some(rest) = reader.strip_prefix("[any") {
-                let expressions =
-                    self.parse_expressions(rest[..rest.len() - 1].trim_start().as_bytes())?;
+                let expressions = self.parse_expressions_at(
+                    rest[..rest.len() - 1].trim_start().as_bytes(),
+                    depth + 1,
+                )?;
+                if expressions.is_empty() {
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: ANY expression must have at least one child expression"
+                            .into(),
+                    )));
+                }
                 let expr = ANY::new(expressions);
                 Ok(expr.into())
             } else if let Some(rest) = reader.strip_prefix("[all") {
-                let expressions =
-                    self.parse_expressions(rest[..rest.len() - 1].trim_start().as_bytes())?;
+                let expressions = self.parse_expressions_at(
+                    rest[..rest.len() - 1].trim_start().as_bytes(),
+                    depth + 1,
+                )?;
+                if expressions.is_empty() {
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: ALL expression must have at least one child expression"
+                            .into(),
+                    )));
+                }
                 let expr = ALL::new(expressions);
                 Ok(expr.into())
             } else if let Some(rest) = reader.strip_prefix("[not") {
-                let expressions =
-                    self.parse_expressions(rest[..rest.len() - 1].trim_start().as_bytes())?;
-                if let Some(first) = expressions.into_iter().last() {
-                    let expr = NOT::new(first);
-                    Ok(expr.into())
-                } else {
-                    Err(Error::other("Parsing error: unknown expression"))
+                let expressions = self.parse_expressions_at(
+                    rest[..rest.len() - 1].trim_start().as_bytes(),
+                    depth + 1,
+                )?;
+                // check that it is of len 1
+                if expressions.len() != 1 {
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: NOT expression must have exactly one child expression"
+                            .into(),
+                    )));
                 }
+                let expr = NOT::new(expressions.into_iter().next().unwrap());
+                Ok(expr.into())
             } else if let Some(rest) = reader.strip_prefix("[desc") {
                 let body = rest[..rest.len() - 1].trim_start();
                 if let Some((expr, regex, negated)) = parse_desc(body) {
                     // do something
-                    let expressions = self.parse_expressions(expr.as_bytes())?;
+                    let expressions = self.parse_expressions_at(expr.as_bytes(), depth + 1)?;
                     // check that it is of len 1
                     if expressions.len() != 1 {
-                        return Err(Error::other(
-                            "Parsing error: DESC expression must have exactly one child expression",
-                        ));
+                        return Err(Error::other(ParseError::MalformedExpression(
+                            "Parsing error: DESC expression must have exactly one child expression"
+                                .into(),
+                        )));
                     }
 
                     // check that the child expression is an atomic
                     if let Some(Expression::Atomic(atomic)) = expressions.first() {
-                        let expr = DESC::new(atomic.clone(), regex, negated);
+                        let expr = DESC::new(atomic.clone(), regex, negated).map_err(|e| {
+                            Error::other(ParseError::MalformedExpression(format!(
+                                "Parsing error: invalid DESC regex: {}",
+                                e
+                            )))
+                        })?;
                         return Ok(expr.into());
                     }
 
-                    return Err(Error::other(
-                        "Parsing error: DESC expression must have an atomic child expression",
-                    ));
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: DESC expression must have an atomic child expression"
+                            .into(),
+                    )));
                 }
-                Err(Error::other("Parsing error: unknown expression"))
+                Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unknown expression".into(),
+                )))
             } else if let Some(rest) = reader.strip_prefix("[size") {
                 let body = rest[..rest.len() - 1].trim_start();
                 if let Some((expr, size, negated)) = parse_size(body) {
                     // do something
-                    let expressions = self.parse_expressions(expr.as_bytes())?;
+                    let expressions = self.parse_expressions_at(expr.as_bytes(), depth + 1)?;
                     // check that it is of len 1
                     if expressions.len() != 1 {
-                        return Err(Error::other(
-                            "Parsing error: SIZE expression must have exactly one child expression",
-                        ));
+                        return Err(Error::other(ParseError::MalformedExpression(
+                            "Parsing error: SIZE expression must have exactly one child expression"
+                                .into(),
+                        )));
                     }
                     // check that the child expression is an atomic
                     if let Some(Expression::Atomic(atomic)) = expressions.first() {
@@ -711,21 +1690,25 @@ impl Parser {
                         return Ok(expr.into());
                     }
 
-                    return Err(Error::other(
-                        "Parsing error: SIZE expression must have an atomic child expression",
-                    ));
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: SIZE expression must have an atomic child expression"
+                            .into(),
+                    )));
                 }
-                Err(Error::other("Parsing error: unknown expression"))
+                Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unknown expression".into(),
+                )))
             } else if let Some(rest) = reader.strip_prefix("[ver") {
                 let body = rest[..rest.len() - 1].trim_start();
                 if let Some((expr, operator, version)) = parse_ver(body) {
                     // do something
-                    let expressions = self.parse_expressions(expr.as_bytes())?;
+                    let expressions = self.parse_expressions_at(expr.as_bytes(), depth + 1)?;
                     // check that it is of len 1
                     if expressions.len() != 1 {
-                        return Err(Error::other(
-                            "Parsing error: VER expression must have exactly one child expression",
-                        ));
+                        return Err(Error::other(ParseError::MalformedExpression(
+                            "Parsing error: VER expression must have exactly one child expression"
+                                .into(),
+                        )));
                     }
 
                     // check that the child expression is an atomic
@@ -734,21 +1717,24 @@ impl Parser {
                         return Ok(expr.into());
                     }
 
-                    return Err(Error::other(
-                        "Parsing error: VER expression must have an atomic child expression",
-                    ));
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: VER expression must have an atomic child expression".into(),
+                    )));
                 }
-                Err(Error::other("Parsing error: unknown expression"))
+                Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unknown expression".into(),
+                )))
             } else if let Some(rest) = reader.strip_prefix("[gver") {
                 let body = rest[..rest.len() - 1].trim_start();
                 if let Some((expr, operator, version)) = parse_gver(body) {
                     // do something
-                    let expressions = self.parse_expressions(expr.as_bytes())?;
+                    let expressions = self.parse_expressions_at(expr.as_bytes(), depth + 1)?;
                     // check that it is of len 1
                     if expressions.len() != 1 {
-                        return Err(Error::other(
-                            "Parsing error: GVER expression must have exactly one child expression",
-                        ));
+                        return Err(Error::other(ParseError::MalformedExpression(
+                            "Parsing error: GVER expression must have exactly one child expression"
+                                .into(),
+                        )));
                     }
 
                     // check that the child expression is an atomic
@@ -757,17 +1743,24 @@ impl Parser {
                         return Ok(expr.into());
                     }
 
-                    return Err(Error::other(
-                        "Parsing error: GVER expression must have an atomic child expression",
-                    ));
+                    return Err(Error::other(ParseError::MalformedExpression(
+                        "Parsing error: GVER expression must have an atomic child expression"
+                            .into(),
+                    )));
                 }
-                Err(Error::other("Parsing error: unknown expression"))
+                Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unknown expression".into(),
+                )))
             } else {
                 // unknown expression
-                Err(Error::other("Parsing error: unknown expression"))
+                Err(Error::other(ParseError::MalformedExpression(
+                    "Parsing error: unknown expression".into(),
+                )))
             }
         } else {
-            Err(Error::other("Parsing error: Not an expression"))
+            Err(Error::other(ParseError::MalformedExpression(
+                "Parsing error: Not an expression".into(),
+            )))
         }
     }
 }
@@ -934,7 +1927,7 @@ where
                 }
             }
             Err(err) => {
-                eprintln!("Error reading input: {}", err);
+                error!("Error reading input: {}", err);
                 return Err(err);
             }
         }
@@ -4,12 +4,14 @@
 use std::{
     fmt::Display,
     io::{BufRead, Error, Read, Result, Seek},
+    path::PathBuf,
 };
 
+use clap::ValueEnum;
 use log::warn;
 use serde::{Deserialize, Serialize};
 
-use crate::{expressions::*, parser, PluginData};
+use crate::{error::ParseError, expressions::*, parser, PluginData};
 
 ///////////////////////////////////////////////////
 // ENUMS
@@ -25,6 +27,9 @@ pub enum EOrderRule {
     Order(Order),
     NearStart(NearStart),
     NearEnd(NearEnd),
+    Pin(Pin),
+    Remove(Remove),
+    Group(Group),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,7 +37,225 @@ pub enum EWarningRule {
     Note(Note),
     Conflict(Conflict),
     Requires(Requires),
+    RequiresVersion(RequiresVersion),
     Patch(Patch),
+    Blacklist(Blacklist),
+}
+
+/// How serious a warning is, rules are warnings unless marked otherwise:
+/// [Note?] is info and [Note!] is an error
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ESeverity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+}
+
+impl ESeverity {
+    pub fn is_warning(&self) -> bool {
+        *self == ESeverity::Warning
+    }
+}
+
+/// Where a rule was parsed from: the rules file, if any, and the line the rule starts at
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleSource {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+}
+
+impl ERule {
+    /// Returns where the rule was parsed from
+    pub fn source(&self) -> Option<&RuleSource> {
+        match self {
+            ERule::EOrderRule(x) => x.source(),
+            ERule::EWarningRule(x) => x.source(),
+        }
+    }
+    pub fn set_source(&mut self, source: RuleSource) {
+        match self {
+            ERule::EOrderRule(x) => x.set_source(source),
+            ERule::EWarningRule(x) => x.set_source(source),
+        }
+    }
+
+    /// Returns if the rule was disabled with [!Rule ...] and should not be used
+    pub fn is_disabled(&self) -> bool {
+        match self {
+            ERule::EOrderRule(x) => x.is_disabled(),
+            ERule::EWarningRule(x) => x.is_disabled(),
+        }
+    }
+    pub fn set_disabled(&mut self, disabled: bool) {
+        match self {
+            ERule::EOrderRule(x) => x.set_disabled(disabled),
+            ERule::EWarningRule(x) => x.set_disabled(disabled),
+        }
+    }
+}
+
+impl EOrderRule {
+    /// Returns where the rule was parsed from
+    pub fn source(&self) -> Option<&RuleSource> {
+        match self {
+            EOrderRule::Order(x) => x.source.as_ref(),
+            EOrderRule::NearStart(x) => x.source.as_ref(),
+            EOrderRule::NearEnd(x) => x.source.as_ref(),
+            EOrderRule::Pin(x) => x.source.as_ref(),
+            EOrderRule::Remove(x) => x.source.as_ref(),
+            EOrderRule::Group(x) => x.source.as_ref(),
+        }
+    }
+    pub fn set_source(&mut self, source: RuleSource) {
+        let source = Some(source);
+        match self {
+            EOrderRule::Order(x) => x.source = source,
+            EOrderRule::NearStart(x) => x.source = source,
+            EOrderRule::NearEnd(x) => x.source = source,
+            EOrderRule::Pin(x) => x.source = source,
+            EOrderRule::Remove(x) => x.source = source,
+            EOrderRule::Group(x) => x.source = source,
+        }
+    }
+
+    /// Returns if the rule was disabled with [!Rule ...] and should not be used
+    pub fn is_disabled(&self) -> bool {
+        match self {
+            EOrderRule::Order(x) => x.disabled,
+            EOrderRule::NearStart(x) => x.disabled,
+            EOrderRule::NearEnd(x) => x.disabled,
+            EOrderRule::Pin(x) => x.disabled,
+            EOrderRule::Remove(x) => x.disabled,
+            EOrderRule::Group(x) => x.disabled,
+        }
+    }
+    pub fn set_disabled(&mut self, disabled: bool) {
+        match self {
+            EOrderRule::Order(x) => x.disabled = disabled,
+            EOrderRule::NearStart(x) => x.disabled = disabled,
+            EOrderRule::NearEnd(x) => x.disabled = disabled,
+            EOrderRule::Pin(x) => x.disabled = disabled,
+            EOrderRule::Remove(x) => x.disabled = disabled,
+            EOrderRule::Group(x) => x.disabled = disabled,
+        }
+    }
+
+    /// Returns the plugin names the rule orders, and the guard expression of an order rule
+    pub fn names(&self) -> (Vec<String>, Option<&Expression>) {
+        match self {
+            EOrderRule::Order(x) => (x.names.clone(), x.guard.as_ref()),
+            EOrderRule::NearStart(x) => (x.names.clone(), None),
+            EOrderRule::NearEnd(x) => (x.names.clone(), None),
+            EOrderRule::Pin(x) => (vec![x.name.clone()], None),
+            EOrderRule::Remove(x) => (x.names.clone(), None),
+            EOrderRule::Group(x) => (x.names.clone(), None),
+        }
+    }
+}
+
+impl EWarningRule {
+    /// Returns where the rule was parsed from
+    pub fn source(&self) -> Option<&RuleSource> {
+        match self {
+            EWarningRule::Note(x) => x.source.as_ref(),
+            EWarningRule::Conflict(x) => x.source.as_ref(),
+            EWarningRule::Requires(x) => x.source.as_ref(),
+            EWarningRule::RequiresVersion(x) => x.source.as_ref(),
+            EWarningRule::Patch(x) => x.source.as_ref(),
+            EWarningRule::Blacklist(x) => x.source.as_ref(),
+        }
+    }
+    pub fn set_source(&mut self, source: RuleSource) {
+        let source = Some(source);
+        match self {
+            EWarningRule::Note(x) => x.source = source,
+            EWarningRule::Conflict(x) => x.source = source,
+            EWarningRule::Requires(x) => x.source = source,
+            EWarningRule::RequiresVersion(x) => x.source = source,
+            EWarningRule::Patch(x) => x.source = source,
+            EWarningRule::Blacklist(x) => x.source = source,
+        }
+    }
+
+    /// Returns if the rule was disabled with [!Rule ...] and should not be used
+    pub fn is_disabled(&self) -> bool {
+        match self {
+            EWarningRule::Note(x) => x.disabled,
+            EWarningRule::Conflict(x) => x.disabled,
+            EWarningRule::Requires(x) => x.disabled,
+            EWarningRule::RequiresVersion(x) => x.disabled,
+            EWarningRule::Patch(x) => x.disabled,
+            EWarningRule::Blacklist(x) => x.disabled,
+        }
+    }
+    pub fn set_disabled(&mut self, disabled: bool) {
+        match self {
+            EWarningRule::Note(x) => x.disabled = disabled,
+            EWarningRule::Conflict(x) => x.disabled = disabled,
+            EWarningRule::Requires(x) => x.disabled = disabled,
+            EWarningRule::RequiresVersion(x) => x.disabled = disabled,
+            EWarningRule::Patch(x) => x.disabled = disabled,
+            EWarningRule::Blacklist(x) => x.disabled = disabled,
+        }
+    }
+
+    /// Returns how serious the warning is
+    pub fn severity(&self) -> ESeverity {
+        match self {
+            EWarningRule::Note(x) => x.severity,
+            EWarningRule::Conflict(x) => x.severity,
+            EWarningRule::Requires(x) => x.severity,
+            EWarningRule::RequiresVersion(x) => x.severity,
+            EWarningRule::Patch(x) => x.severity,
+            EWarningRule::Blacklist(x) => x.severity,
+        }
+    }
+    pub fn set_severity(&mut self, severity: ESeverity) {
+        match self {
+            EWarningRule::Note(x) => x.severity = severity,
+            EWarningRule::Conflict(x) => x.severity = severity,
+            EWarningRule::Requires(x) => x.severity = severity,
+            EWarningRule::RequiresVersion(x) => x.severity = severity,
+            EWarningRule::Patch(x) => x.severity = severity,
+            EWarningRule::Blacklist(x) => x.severity = severity,
+        }
+    }
+
+    /// Returns the top level expressions of the rule
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            EWarningRule::Note(x) => x.expressions.iter().collect(),
+            EWarningRule::Conflict(x) => x.expressions.iter().collect(),
+            EWarningRule::Requires(x) => x.expression_a.iter().chain(&x.expression_b).collect(),
+            EWarningRule::RequiresVersion(x) => x.expression.iter().collect(),
+            EWarningRule::Patch(x) => x.expression_a.iter().chain(&x.expression_b).collect(),
+            EWarningRule::Blacklist(x) => x.expressions.iter().collect(),
+        }
+    }
+
+    /// Returns all expressions of the rule for changing them, see expressions
+    pub fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            EWarningRule::Note(x) => x.expressions.iter_mut().collect(),
+            EWarningRule::Conflict(x) => x.expressions.iter_mut().collect(),
+            EWarningRule::Requires(x) => x
+                .expression_a
+                .iter_mut()
+                .chain(&mut x.expression_b)
+                .collect(),
+            EWarningRule::RequiresVersion(x) => x.expression.iter_mut().collect(),
+            EWarningRule::Patch(x) => x
+                .expression_a
+                .iter_mut()
+                .chain(&mut x.expression_b)
+                .collect(),
+            EWarningRule::Blacklist(x) => x.expressions.iter_mut().collect(),
+        }
+    }
 }
 
 ///////////////////////////////////////////////////
@@ -47,7 +270,21 @@ pub trait TWarningRule {
 
     fn set_comment(&mut self, comment: String);
     /// every rule may be evaluated
-    fn eval(&mut self, items: &[PluginData]) -> bool;
+    fn eval_context(&mut self, context: &EvalContext) -> bool;
+    /// evaluates the rule against the plugins, build an EvalContext once when evaluating many rules
+    fn eval(&mut self, items: &[PluginData]) -> bool {
+        self.eval_context(&EvalContext::from_plugins(items))
+    }
+    /// evaluates the rule against the mods in load order,
+    /// rules that don't care about the order evaluate the names like eval
+    fn eval_ordered(&mut self, ordered_mods: &[String]) -> bool {
+        eval_unordered(self, ordered_mods)
+    }
+}
+
+/// The default of eval_ordered: evaluates the rule by the presence of the mods, ignoring their order
+fn eval_unordered<T: TWarningRule + ?Sized>(rule: &mut T, ordered_mods: &[String]) -> bool {
+    rule.eval_context(&EvalContext::from_mods(ordered_mods))
 }
 
 impl TWarningRule for EWarningRule {
@@ -56,7 +293,9 @@ impl TWarningRule for EWarningRule {
             EWarningRule::Note(x) => x.get_comment(),
             EWarningRule::Conflict(x) => x.get_comment(),
             EWarningRule::Requires(x) => x.get_comment(),
+            EWarningRule::RequiresVersion(x) => x.get_comment(),
             EWarningRule::Patch(x) => x.get_comment(),
+            EWarningRule::Blacklist(x) => x.get_comment(),
         }
     }
 
@@ -65,7 +304,9 @@ impl TWarningRule for EWarningRule {
             EWarningRule::Note(x) => x.get_ui_comment(),
             EWarningRule::Conflict(x) => x.get_ui_comment(),
             EWarningRule::Requires(x) => x.get_ui_comment(),
+            EWarningRule::RequiresVersion(x) => x.get_ui_comment(),
             EWarningRule::Patch(x) => x.get_ui_comment(),
+            EWarningRule::Blacklist(x) => x.get_ui_comment(),
         }
     }
 
@@ -74,7 +315,9 @@ impl TWarningRule for EWarningRule {
             EWarningRule::Note(x) => x.get_plugins(),
             EWarningRule::Conflict(x) => x.get_plugins(),
             EWarningRule::Requires(x) => x.get_plugins(),
+            EWarningRule::RequiresVersion(x) => x.get_plugins(),
             EWarningRule::Patch(x) => x.get_plugins(),
+            EWarningRule::Blacklist(x) => x.get_plugins(),
         }
     }
 
@@ -83,20 +326,46 @@ impl TWarningRule for EWarningRule {
             EWarningRule::Note(x) => x.set_comment(comment),
             EWarningRule::Conflict(x) => x.set_comment(comment),
             EWarningRule::Requires(x) => x.set_comment(comment),
+            EWarningRule::RequiresVersion(x) => x.set_comment(comment),
             EWarningRule::Patch(x) => x.set_comment(comment),
+            EWarningRule::Blacklist(x) => x.set_comment(comment),
         }
     }
 
-    fn eval(&mut self, items: &[PluginData]) -> bool {
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
         match self {
-            EWarningRule::Note(o) => o.eval(items),
-            EWarningRule::Conflict(o) => o.eval(items),
-            EWarningRule::Requires(o) => o.eval(items),
-            EWarningRule::Patch(o) => o.eval(items),
+            EWarningRule::Note(o) => o.eval_context(context),
+            EWarningRule::Conflict(o) => o.eval_context(context),
+            EWarningRule::Requires(o) => o.eval_context(context),
+            EWarningRule::RequiresVersion(o) => o.eval_context(context),
+            EWarningRule::Patch(o) => o.eval_context(context),
+            EWarningRule::Blacklist(o) => o.eval_context(context),
+        }
+    }
+
+    fn eval_ordered(&mut self, ordered_mods: &[String]) -> bool {
+        match self {
+            EWarningRule::Note(o) => o.eval_ordered(ordered_mods),
+            EWarningRule::Conflict(o) => o.eval_ordered(ordered_mods),
+            EWarningRule::Requires(o) => o.eval_ordered(ordered_mods),
+            EWarningRule::RequiresVersion(o) => o.eval_ordered(ordered_mods),
+            EWarningRule::Patch(o) => o.eval_ordered(ordered_mods),
+            EWarningRule::Blacklist(o) => o.eval_ordered(ordered_mods),
         }
     }
 }
 
+/// Replaces the placeholders %1, %2, ... in a comment with the matched plugins.
+/// Placeholders without a matching plugin are left as is
+pub fn format_comment(comment: &str, plugins: &[String]) -> String {
+    let mut result = comment.to_owned();
+    // replace higher numbers first so that %1 does not match %10
+    for (i, plugin) in plugins.iter().enumerate().rev() {
+        result = result.replace(&format!("%{}", i + 1), plugin);
+    }
+    result
+}
+
 pub trait TParser<T> {
     fn parse<R: Read + BufRead + Seek>(
         rule: &mut T,
@@ -128,7 +397,9 @@ impl TParser<EWarningRule> for EWarningRule {
             EWarningRule::Note(rule) => Note::parse(rule, reader, parser),
             EWarningRule::Conflict(rule) => Conflict::parse(rule, reader, parser),
             EWarningRule::Requires(rule) => Requires::parse(rule, reader, parser),
+            EWarningRule::RequiresVersion(rule) => RequiresVersion::parse(rule, reader, parser),
             EWarningRule::Patch(rule) => Patch::parse(rule, reader, parser),
+            EWarningRule::Blacklist(rule) => Blacklist::parse(rule, reader, parser),
         }
     }
 }
@@ -143,6 +414,9 @@ impl TParser<EOrderRule> for EOrderRule {
             EOrderRule::Order(rule) => Order::parse(rule, reader, parser),
             EOrderRule::NearStart(rule) => NearStart::parse(rule, reader, parser),
             EOrderRule::NearEnd(rule) => NearEnd::parse(rule, reader, parser),
+            EOrderRule::Pin(rule) => Pin::parse(rule, reader, parser),
+            EOrderRule::Remove(rule) => Remove::parse(rule, reader, parser),
+            EOrderRule::Group(rule) => Group::parse(rule, reader, parser),
         }
     }
 }
@@ -176,6 +450,21 @@ impl From<NearEnd> for ERule {
         ERule::EOrderRule(val.into())
     }
 }
+impl From<Pin> for ERule {
+    fn from(val: Pin) -> Self {
+        ERule::EOrderRule(val.into())
+    }
+}
+impl From<Remove> for ERule {
+    fn from(val: Remove) -> Self {
+        ERule::EOrderRule(val.into())
+    }
+}
+impl From<Group> for ERule {
+    fn from(val: Group) -> Self {
+        ERule::EOrderRule(val.into())
+    }
+}
 
 impl From<Order> for EOrderRule {
     fn from(val: Order) -> Self {
@@ -192,6 +481,21 @@ impl From<NearEnd> for EOrderRule {
         EOrderRule::NearEnd(val)
     }
 }
+impl From<Pin> for EOrderRule {
+    fn from(val: Pin) -> Self {
+        EOrderRule::Pin(val)
+    }
+}
+impl From<Remove> for EOrderRule {
+    fn from(val: Remove) -> Self {
+        EOrderRule::Remove(val)
+    }
+}
+impl From<Group> for EOrderRule {
+    fn from(val: Group) -> Self {
+        EOrderRule::Group(val)
+    }
+}
 
 // Warnings
 impl From<Note> for ERule {
@@ -214,6 +518,16 @@ impl From<Patch> for ERule {
         ERule::EWarningRule(val.into())
     }
 }
+impl From<RequiresVersion> for ERule {
+    fn from(val: RequiresVersion) -> Self {
+        ERule::EWarningRule(val.into())
+    }
+}
+impl From<Blacklist> for ERule {
+    fn from(val: Blacklist) -> Self {
+        ERule::EWarningRule(val.into())
+    }
+}
 
 impl From<Note> for EWarningRule {
     fn from(val: Note) -> Self {
@@ -235,6 +549,16 @@ impl From<Patch> for EWarningRule {
         EWarningRule::Patch(val)
     }
 }
+impl From<RequiresVersion> for EWarningRule {
+    fn from(val: RequiresVersion) -> Self {
+        EWarningRule::RequiresVersion(val)
+    }
+}
+impl From<Blacklist> for EWarningRule {
+    fn from(val: Blacklist) -> Self {
+        EWarningRule::Blacklist(val)
+    }
+}
 
 ////////////////////////////////////////////////////////////////////////
 // IMPLEMENTATIONS ORDER
@@ -244,39 +568,123 @@ impl From<Patch> for EWarningRule {
 // ORDER
 
 /// The [Order] rule specifies the order of plugins.
+/// An optional leading guard expression makes the order only apply if the guard is true:
+/// [Order] [ALL Guard.esp] A.esp B.esp
+/// A soft order ([Order?]) is only preferred: it never moves plugins or causes a cycle, a violation is a warning
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Order {
     pub names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guard: Option<Expression>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub soft: bool,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl Order {
     pub fn new(names: Vec<String>) -> Self {
-        Self { names }
+        Self {
+            names,
+            guard: None,
+            soft: false,
+            source: None,
+            disabled: false,
+        }
     }
 
     pub fn from(name_a: &str, name_b: &str) -> Self {
         Self {
+            source: None,
+            disabled: false,
             names: [name_a.to_owned(), name_b.to_owned()].to_vec(),
+            guard: None,
+            soft: false,
         }
     }
+
+    /// Makes the order soft, see [Order]
+    pub fn with_soft(mut self, soft: bool) -> Self {
+        self.soft = soft;
+        self
+    }
+
+    pub fn with_guard(mut self, guard: Expression) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Unguarded orders always apply, guarded orders only if the guard evaluates as true
+    pub fn applies(&self, context: &EvalContext) -> bool {
+        self.guard
+            .as_ref()
+            .is_none_or(|guard| guard.eval(context).is_some())
+    }
+}
+/// Checks if an [Order] body starts with a predicate expression
+fn is_guard_predicate(body: &str) -> bool {
+    let lower = body.to_lowercase();
+    ["[all", "[any", "[not", "[desc", "[size", "[ver", "[gver"]
+        .iter()
+        .any(|p| lower.starts_with(p) && lower[p.len()..].starts_with(char::is_whitespace))
 }
+
 impl TParser<Order> for Order {
     fn parse<R: Read + BufRead + Seek>(
         this: &mut Order,
-        reader: R,
+        mut reader: R,
         parser: &parser::Parser,
     ) -> Result<()> {
+        let mut body = String::new();
+        reader.read_to_string(&mut body)?;
+
+        // a leading predicate is the guard if more names follow on the same line
+        // otherwise it is a plugin name, e.g. [Official]Adamantium Armor.esp or [SIZE 123 a.esp]
+        let mut body = body.trim_start();
+        if is_guard_predicate(body) {
+            let mut depth = 0;
+            let end = body
+                .char_indices()
+                .find(|(_, c)| {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map(|(i, _)| i)
+                .ok_or_else(|| {
+                    Error::other(ParseError::MalformedRule(
+                        "Parsing error: unclosed Order guard".into(),
+                    ))
+                })?;
+
+            let rest = body[end + 1..].lines().next().unwrap_or_default();
+            if !parser::strip_comment(rest).trim().is_empty() {
+                this.guard = Some(parser.parse_expression(&body[..=end], true)?);
+                body = &body[end + 1..];
+            }
+        }
+
         // parse each line
         let mut names: Vec<String> = vec![];
-        for line in reader
+        for line in body
             .lines()
-            .map_while(Result::ok)
-            .map(|l| l.trim().to_owned())
+            .map(|l| parser::strip_comment(l).trim().to_owned())
         {
             // HANDLE RULE PARSE
             // each line gets tokenized
-            for token in parser.tokenize(line) {
-                if !token.ends_with(']') && !parser.ends_with_vec(&token) {
-                    return Err(Error::other("Parsing error: tokenize failed"));
+            for token in parser.tokenize_names(line) {
+                if !token.ends_with(']') && !token.starts_with('@') && !parser.ends_with_vec(&token)
+                {
+                    return Err(Error::other(ParseError::MalformedRule(
+                        "Parsing error: tokenize failed".into(),
+                    )));
                 }
                 names.push(token);
             }
@@ -284,12 +692,114 @@ impl TParser<Order> for Order {
 
         this.names = names;
 
+        if this.names.is_empty() {
+            warn!("Malformed Order rule: no plugins");
+            return Err(Error::other(ParseError::EmptyBody));
+        }
         if this.names.len() < 2 {
             warn!("Malformed Order rule: less than 2 expressions");
-            return Err(Error::other(
-                "Malformed Order rule: less than 2 expressions",
-            ));
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Order rule: less than 2 expressions".into(),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// REMOVE
+
+/// The [Remove] rule cancels the ordering of plugins given by [Order] rules of earlier rules files.
+/// It takes the same body as [Order] and removes every pair the equivalent [Order] rule would add
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Remove {
+    pub names: Vec<String>,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
+}
+impl Remove {
+    pub fn new(names: Vec<String>) -> Self {
+        Self {
+            names,
+            source: None,
+            disabled: false,
+        }
+    }
+
+    pub fn from(name_a: &str, name_b: &str) -> Self {
+        Self {
+            source: None,
+            disabled: false,
+            names: [name_a.to_owned(), name_b.to_owned()].to_vec(),
         }
+    }
+}
+impl TParser<Remove> for Remove {
+    fn parse<R: Read + BufRead + Seek>(
+        this: &mut Remove,
+        reader: R,
+        parser: &parser::Parser,
+    ) -> Result<()> {
+        // same body as an order rule
+        let mut order = Order::default();
+        Order::parse(&mut order, reader, parser)?;
+        this.names = order.names;
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+// GROUP
+
+/// The [Group name] rule defines a named list of plugins.
+/// [Order], [NearStart], [NearEnd] and [Remove] rules reference it with @name, which expands to all plugins of the group
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    pub names: Vec<String>,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
+}
+impl Group {
+    pub fn new(name: String, names: Vec<String>) -> Self {
+        Self {
+            name,
+            names,
+            source: None,
+            disabled: false,
+        }
+    }
+}
+impl TParser<Group> for Group {
+    fn parse<R: Read + BufRead + Seek>(
+        this: &mut Group,
+        reader: R,
+        parser: &parser::Parser,
+    ) -> Result<()> {
+        // references end at whitespace, so names are one word
+        if this.name.is_empty() || this.name.contains(char::is_whitespace) {
+            warn!("Malformed Group rule: the name must be one word");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Group rule: the name must be one word".into(),
+            )));
+        }
+
+        // same body as a nearstart rule
+        let mut nearstart = NearStart::default();
+        NearStart::parse(&mut nearstart, reader, parser)?;
+        this.names = nearstart.names;
 
         Ok(())
     }
@@ -299,13 +809,34 @@ impl TParser<Order> for Order {
 // NEARSTART
 
 /// The [NearStart] rule specifies that one or more plugins should appear as near as possible to the Start of the load order.
+/// An optional priority orders conflicting rules: [NearStart 10]
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct NearStart {
     pub names: Vec<String>,
+    /// rules with a higher priority are placed nearer to the start
+    #[serde(default)]
+    pub priority: i32,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl NearStart {
     pub fn new(names: Vec<String>) -> Self {
-        Self { names }
+        Self {
+            names,
+            priority: 0,
+            source: None,
+            disabled: false,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
     }
 }
 impl TParser<NearStart> for NearStart {
@@ -319,18 +850,25 @@ impl TParser<NearStart> for NearStart {
         for line in reader
             .lines()
             .map_while(Result::ok)
-            .map(|l| l.trim().to_owned())
+            .map(|l| parser::strip_comment(&l).trim().to_owned())
         {
             // HANDLE RULE PARSE
             // each line gets tokenized
-            for token in parser.tokenize(line) {
-                if !token.ends_with(']') && !parser.ends_with_vec(&token) {
-                    return Err(Error::other("Parsing error: tokenize failed"));
+            for token in parser.tokenize_names(line) {
+                if !token.ends_with(']') && !token.starts_with('@') && !parser.ends_with_vec(&token)
+                {
+                    return Err(Error::other(ParseError::MalformedRule(
+                        "Parsing error: tokenize failed".into(),
+                    )));
                 }
                 names.push(token);
             }
         }
 
+        if names.is_empty() {
+            warn!("Malformed NearStart rule: no plugins");
+            return Err(Error::other(ParseError::EmptyBody));
+        }
         this.names = names;
 
         Ok(())
@@ -341,13 +879,34 @@ impl TParser<NearStart> for NearStart {
 // NEAREND
 
 /// The [NearEnd] rule specifies that one or more plugins should appear as near as possible to the End of the load order.
+/// An optional priority orders conflicting rules: [NearEnd 10]
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct NearEnd {
     pub names: Vec<String>,
+    /// rules with a higher priority are placed nearer to the end
+    #[serde(default)]
+    pub priority: i32,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl NearEnd {
     pub fn new(names: Vec<String>) -> Self {
-        Self { names }
+        Self {
+            names,
+            priority: 0,
+            source: None,
+            disabled: false,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
     }
 }
 impl TParser<NearEnd> for NearEnd {
@@ -361,24 +920,94 @@ impl TParser<NearEnd> for NearEnd {
         for line in reader
             .lines()
             .map_while(Result::ok)
-            .map(|l| l.trim().to_owned())
+            .map(|l| parser::strip_comment(&l).trim().to_owned())
         {
             // HANDLE RULE PARSE
             // each line gets tokenized
-            for token in parser.tokenize(line) {
-                if !token.ends_with(']') && !parser.ends_with_vec(&token) {
-                    return Err(Error::other("Parsing error: tokenize failed"));
+            for token in parser.tokenize_names(line) {
+                if !token.ends_with(']') && !token.starts_with('@') && !parser.ends_with_vec(&token)
+                {
+                    return Err(Error::other(ParseError::MalformedRule(
+                        "Parsing error: tokenize failed".into(),
+                    )));
                 }
                 names.push(token);
             }
         }
 
+        if names.is_empty() {
+            warn!("Malformed NearEnd rule: no plugins");
+            return Err(Error::other(ParseError::EmptyBody));
+        }
         this.names = names;
 
         Ok(())
     }
 }
 
+////////////////////////////////////////////////////////////////////////
+// PIN
+
+/// The [Pin N] rule specifies that a plugin should appear at index N of the load order.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Pin {
+    pub name: String,
+    pub index: usize,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
+}
+impl Pin {
+    pub fn new(name: String, index: usize) -> Self {
+        Self {
+            name,
+            index,
+            source: None,
+            disabled: false,
+        }
+    }
+}
+impl TParser<Pin> for Pin {
+    fn parse<R: Read + BufRead + Seek>(
+        this: &mut Pin,
+        reader: R,
+        parser: &parser::Parser,
+    ) -> Result<()> {
+        // parse each line
+        let mut names: Vec<String> = vec![];
+        for line in reader
+            .lines()
+            .map_while(Result::ok)
+            .map(|l| parser::strip_comment(&l).trim().to_owned())
+        {
+            // HANDLE RULE PARSE
+            // each line gets tokenized
+            for token in parser.tokenize(line) {
+                if !parser.ends_with_vec(&token) {
+                    return Err(Error::other(ParseError::MalformedRule(
+                        "Parsing error: tokenize failed".into(),
+                    )));
+                }
+                names.push(token);
+            }
+        }
+
+        // a pin applies to exactly one plugin
+        if names.len() != 1 {
+            return Err(Error::other(ParseError::MalformedRule(
+                "Parsing error: Pin rules take exactly one plugin".into(),
+            )));
+        }
+        this.name = names.remove(0);
+
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////
 // IMPLEMENTATIONS WARNINGS
 ////////////////////////////////////////////////////////////////////////
@@ -389,7 +1018,9 @@ impl Display for EWarningRule {
             EWarningRule::Note(x) => x.fmt(f),
             EWarningRule::Conflict(x) => x.fmt(f),
             EWarningRule::Requires(x) => x.fmt(f),
+            EWarningRule::RequiresVersion(x) => x.fmt(f),
             EWarningRule::Patch(x) => x.fmt(f),
+            EWarningRule::Blacklist(x) => x.fmt(f),
         }
     }
 }
@@ -405,23 +1036,51 @@ pub struct Note {
     pub expressions: Vec<Expression>,
 
     pub plugins: Vec<String>,
+
+    /// the note only fires when the first plugin loads after the second, see eval_ordered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<(String, String)>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 
 impl Note {
     pub fn new(comment: String, expressions: &[Expression]) -> Self {
         Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
             comment,
             expressions: expressions.to_vec(),
             plugins: vec![],
+            order: None,
+        }
+    }
+
+    /// A note that fires when a loads after b, it needs the load order and only evaluates with eval_ordered.
+    /// In a rules file this is [NoteOrder comment] with the two plugins in the body
+    pub fn new_ordered(comment: String, a: &str, b: &str) -> Self {
+        Self {
+            order: Some((a.to_lowercase(), b.to_lowercase())),
+            ..Self::new(comment, &[])
         }
     }
 }
 impl TWarningRule for Note {
     fn get_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_ui_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_plugins(&self) -> Vec<String> {
         self.plugins.clone()
@@ -431,10 +1090,15 @@ impl TWarningRule for Note {
         self.comment = comment;
     }
     /// Notes evaluate as true if any of the containing expressions evaluates as true
-    fn eval(&mut self, items: &[PluginData]) -> bool {
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
+        // the index has no positions
+        if self.order.is_some() {
+            return false;
+        }
+
         let mut result = false;
         for expr in &self.expressions {
-            if let Some(plugins) = expr.eval(items) {
+            if let Some(plugins) = expr.eval(context) {
                 result = true;
                 // track plugins
                 self.plugins.extend(plugins);
@@ -442,6 +1106,25 @@ impl TWarningRule for Note {
         }
         result
     }
+    /// Ordered notes evaluate as true if both plugins are installed and the first loads after the second
+    fn eval_ordered(&mut self, ordered_mods: &[String]) -> bool {
+        let Some((a, b)) = &self.order else {
+            return eval_unordered(self, ordered_mods);
+        };
+
+        let position = |name: &str| {
+            ordered_mods
+                .iter()
+                .position(|m| m.eq_ignore_ascii_case(name))
+        };
+        match (position(a), position(b)) {
+            (Some(pos_a), Some(pos_b)) if pos_a > pos_b => {
+                self.plugins = vec![a.to_owned(), b.to_owned()];
+                true
+            }
+            _ => false,
+        }
+    }
 }
 impl TParser<Note> for Note {
     fn parse<R: Read + BufRead + Seek>(
@@ -458,7 +1141,25 @@ impl TParser<Note> for Note {
 
         if this.expressions.is_empty() {
             warn!("Malformed Note rule: no expressions parsed");
-            return Err(Error::other("Malformed Note rule: no expressions parsed"));
+            return Err(Error::other(ParseError::EmptyBody));
+        }
+
+        // ordered notes name the two plugins instead of expressions: [NoteOrder] a.esp b.esp
+        if this.order.is_some() {
+            let names = this
+                .expressions
+                .drain(..)
+                .map(|e| match e {
+                    Expression::Atomic(a) => Some(a.item),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some([a, b]) = names.as_deref() else {
+                return Err(Error::other(ParseError::MalformedRule(
+                    "Malformed NoteOrder rule: expected two plugin names".into(),
+                )));
+            };
+            this.order = Some((a.to_owned(), b.to_owned()));
         }
 
         Ok(())
@@ -478,19 +1179,35 @@ impl Display for Note {
 ////////////////////////////////////////////////////////////////////////
 // CONFLICT
 
-/// The [Conflict] Rule <A conflicts with B>
-/// [Conflict] evaluate as true if both expressions evaluate as true
+/// The [Conflict] Rule <A conflicts with B conflicts with C ...>
+/// [Conflict] evaluates as true if two or more of its expressions evaluate as true
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Conflict {
     pub comment: String,
     pub expressions: Vec<Expression>,
 
+    /// all plugins that collided
     pub plugins: Vec<String>,
+    /// the matched plugins of each expression that evaluated as true
     pub conflicts: Vec<Vec<String>>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl Conflict {
     pub fn new(comment: String, expressions: &[Expression]) -> Self {
         Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
             comment,
             expressions: expressions.to_vec(),
             plugins: vec![],
@@ -500,10 +1217,10 @@ impl Conflict {
 }
 impl TWarningRule for Conflict {
     fn get_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_ui_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_plugins(&self) -> Vec<String> {
         self.plugins.clone()
@@ -512,18 +1229,24 @@ impl TWarningRule for Conflict {
         self.comment = comment;
     }
 
-    /// Conflicts evaluate as true if both expressions evaluate as true
-    fn eval(&mut self, items: &[PluginData]) -> bool {
-        let mut i = 0;
-        for e in &self.expressions {
-            if let Some(plugins) = e.eval(items) {
-                self.plugins.extend(plugins.clone());
-                self.conflicts.push(plugins);
-                i += 1;
-            }
+    /// Conflicts evaluate as true if two or more expressions evaluate as true
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
+        let conflicts = self
+            .expressions
+            .iter()
+            .filter_map(|e| e.eval(context))
+            .collect::<Vec<_>>();
+
+        // only report plugins that actually collided
+        if conflicts.len() > 1 {
+            self.plugins = conflicts.iter().flatten().cloned().collect();
+            self.conflicts = conflicts;
+            true
+        } else {
+            self.plugins.clear();
+            self.conflicts.clear();
+            false
         }
-
-        i > 1
     }
 }
 impl TParser<Conflict> for Conflict {
@@ -537,9 +1260,11 @@ impl TParser<Conflict> for Conflict {
 
         if this.expressions.is_empty() {
             warn!("Malformed Conflict rule: no expressions parsed");
-            return Err(Error::other(
-                "Malformed Conflict rule: no expressions parsed",
-            ));
+            return Err(Error::other(ParseError::EmptyBody));
+        }
+        // accepted like mlox does, it only fires if two or more expressions match
+        if this.expressions.len() < 2 {
+            warn!("Conflict rule with less than 2 expressions");
         }
 
         Ok(())
@@ -568,28 +1293,64 @@ pub struct Requires {
     pub expression_b: Option<Expression>,
 
     pub plugins: Vec<String>,
+    /// the plugins of expression B that are not installed, set when the rule fires
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl Requires {
     pub fn new(comment: String, expression_a: Expression, expression_b: Expression) -> Self {
         Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
             comment,
             expression_a: Some(expression_a),
             expression_b: Some(expression_b),
             plugins: vec![],
+            missing: vec![],
         }
     }
+
+    /// Names the installed plugins and the missing dependencies after the rule fired,
+    /// e.g. "a.esp requires b.esp (not installed)"
+    pub fn missing_message(&self) -> Option<String> {
+        if self.plugins.is_empty() || self.missing.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{} requires {} (not installed)",
+            self.plugins.join(", "),
+            self.missing.join(", ")
+        ))
+    }
 }
 impl TWarningRule for Requires {
     fn get_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_ui_comment(&self) -> String {
-        let mut comment = self.comment.clone();
+        let mut comment = self.get_comment();
+
+        // name the missing plugins once the rule fired
+        if let Some(message) = self.missing_message() {
+            return format!("{} ({})", message, comment);
+        }
 
         // automatically set the comment to the two expressions
         if let Some(expr_a) = &self.expression_a {
             if let Some(expr_b) = &self.expression_b {
-                comment = format!("'{}' requires '{}' ({})", expr_a, expr_b, self.comment);
+                comment = format!("'{}' requires '{}' ({})", expr_a, expr_b, comment);
             }
         }
 
@@ -603,14 +1364,19 @@ impl TWarningRule for Requires {
         self.comment = comment;
     }
     /// Requires evaluates as true if A is true and B is not true
-    fn eval(&mut self, items: &[PluginData]) -> bool {
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
         let mut result = false;
         if let Some(expr_a) = &self.expression_a {
             if let Some(expr_b) = &self.expression_b {
-                if let Some(plugins) = expr_a.eval(items) {
-                    if expr_b.eval(items).is_none() {
+                if let Some(plugins) = expr_a.eval(context) {
+                    if expr_b.eval(context).is_none() {
                         result = true;
                         self.plugins.extend(plugins);
+                        expr_b.visit_atomics(&mut |a| {
+                            if a.eval(context).is_none() && !self.missing.contains(&a.item) {
+                                self.missing.push(a.item.to_owned());
+                            }
+                        });
                     }
                 }
             }
@@ -630,16 +1396,36 @@ impl TParser<Requires> for Requires {
         // }
 
         // add all parsed expressions
-        let expressions = parser.parse_expressions(reader)?;
-        if expressions.len() != 2 {
-            warn!("Malformed Requires rule: more than 2 expressions");
-            return Err(Error::other(
-                "Malformed Requires rule: more than 2 expressions",
-            ));
+        let mut expressions = parser.parse_expressions(reader)?;
+        if expressions.len() < 2 {
+            warn!("Malformed Requires rule: needs a plugin and a dependency");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Requires rule: needs a plugin and a dependency".into(),
+            )));
         }
 
-        this.expression_a = Some(expressions[0].clone());
-        this.expression_b = Some(expressions[1].clone());
+        // alternative dependencies must be explicit
+        if expressions.len() > 2 {
+            warn!("Malformed Requires rule: wrap alternative dependencies in [ANY]");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Requires rule: more than one dependency, wrap alternatives in [ANY]"
+                    .into(),
+            )));
+        }
+
+        let expression_b = expressions.remove(1);
+        let expression_a = expressions.remove(0);
+
+        // a plugin that requires itself never warns
+        if expression_a == expression_b {
+            warn!("Malformed Requires rule: the plugin requires itself");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Requires rule: the plugin requires itself".into(),
+            )));
+        }
+
+        this.expression_a = Some(expression_a);
+        this.expression_b = Some(expression_b);
 
         Ok(())
     }
@@ -658,6 +1444,135 @@ impl Display for Requires {
     }
 }
 
+////////////////////////////////////////////////////////////////////////
+// REQUIRESVERSION
+
+/// The [RequiresVersion] Rule <A requires a game version>
+/// [RequiresVersion] evaluates as true if A is true and the game version of its plugins does not match the version requirement.
+/// Plugins without a known game version never warn
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct RequiresVersion {
+    pub comment: String,
+    pub expression: Option<Expression>,
+    /// the game version requirement, e.g. ">=0.48"
+    pub version: String,
+
+    pub plugins: Vec<String>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
+}
+impl RequiresVersion {
+    pub fn new(comment: String, expression: Expression, version: String) -> Self {
+        Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
+            comment,
+            expression: Some(expression),
+            version,
+            plugins: vec![],
+        }
+    }
+}
+impl TWarningRule for RequiresVersion {
+    fn get_comment(&self) -> String {
+        format_comment(&self.comment, &self.plugins)
+    }
+    fn get_ui_comment(&self) -> String {
+        let mut comment = self.get_comment();
+        if let Some(expr) = &self.expression {
+            comment = format!(
+                "'{}' requires game version '{}' ({})",
+                expr, self.version, comment
+            );
+        }
+
+        comment
+    }
+    fn get_plugins(&self) -> Vec<String> {
+        self.plugins.clone()
+    }
+
+    fn set_comment(&mut self, comment: String) {
+        self.comment = comment;
+    }
+    /// RequiresVersion evaluates as true if A is true and the game version does not match
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
+        let Ok(req) = semver::VersionReq::parse(&self.version) else {
+            warn!("Invalid game version requirement: {}", self.version);
+            return false;
+        };
+
+        let mut result = false;
+        if let Some(expr) = &self.expression {
+            if let Some(plugins) = expr.eval(context) {
+                for plugin in plugins {
+                    let mismatch = context
+                        .index()
+                        .contains_data(&plugin)
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|p| p.game_version.as_ref().is_some_and(|v| !req.matches(v)));
+                    if mismatch {
+                        result = true;
+                        self.plugins.push(plugin);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl TParser<RequiresVersion> for RequiresVersion {
+    fn parse<R: Read + BufRead + Seek>(
+        this: &mut RequiresVersion,
+        reader: R,
+        parser: &parser::Parser,
+    ) -> Result<()> {
+        if semver::VersionReq::parse(&this.version).is_err() {
+            warn!(
+                "Malformed RequiresVersion rule: invalid version '{}'",
+                this.version
+            );
+            return Err(Error::other(ParseError::MalformedRule(format!(
+                "Malformed RequiresVersion rule: invalid version '{}'",
+                this.version
+            ))));
+        }
+
+        let mut expressions = parser.parse_expressions(reader)?;
+        if expressions.len() != 1 {
+            warn!("Malformed RequiresVersion rule: needs exactly one plugin");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed RequiresVersion rule: needs exactly one plugin".into(),
+            )));
+        }
+        this.expression = Some(expressions.remove(0));
+
+        Ok(())
+    }
+}
+
+impl Display for RequiresVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut block = format!("[REQUIRESVERSION {}]\n\t{}\n", self.version, self.comment);
+        if let Some(e) = &self.expression {
+            block += format!("{}\n", e).as_str();
+        }
+        write!(f, "{}", block)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////
 // PATCH
 
@@ -671,10 +1586,24 @@ pub struct Patch {
     pub expression_b: Option<Expression>,
 
     pub plugins: Vec<String>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
 }
 impl Patch {
     pub fn new(comment: String, expression_a: Expression, expression_b: Expression) -> Self {
         Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
             comment,
             expression_a: Some(expression_a),
             expression_b: Some(expression_b),
@@ -684,10 +1613,10 @@ impl Patch {
 }
 impl TWarningRule for Patch {
     fn get_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_ui_comment(&self) -> String {
-        self.comment.clone()
+        format_comment(&self.comment, &self.plugins)
     }
     fn get_plugins(&self) -> Vec<String> {
         self.plugins.clone()
@@ -697,19 +1626,19 @@ impl TWarningRule for Patch {
         self.comment = comment;
     }
     /// Patch evaluates as true if A is true and B is not true or if B is true and A is not true
-    fn eval(&mut self, items: &[PluginData]) -> bool {
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
         let mut result = false;
         if let Some(expr_a) = &self.expression_a {
             if let Some(expr_b) = &self.expression_b {
-                if let Some(plugins_a) = expr_a.eval(items) {
-                    if expr_b.eval(items).is_none() {
+                if let Some(plugins_a) = expr_a.eval(context) {
+                    if expr_b.eval(context).is_none() {
                         result = true;
                         self.plugins.extend(plugins_a);
                     }
                 }
 
-                if let Some(plugins_b) = expr_b.eval(items) {
-                    if expr_a.eval(items).is_none() {
+                if let Some(plugins_b) = expr_b.eval(context) {
+                    if expr_a.eval(context).is_none() {
                         result = true;
                         self.plugins.extend(plugins_b);
                     }
@@ -734,9 +1663,17 @@ impl TParser<Patch> for Patch {
         let expressions = parser.parse_expressions(reader)?;
         if expressions.len() != 2 {
             warn!("Malformed Patch rule: not exactly 2 expressions");
-            return Err(Error::other(
-                "Malformed Patch rule: not exactly 2 expressions",
-            ));
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Patch rule: not exactly 2 expressions".into(),
+            )));
+        }
+
+        // a plugin that patches itself never warns
+        if expressions[0] == expressions[1] {
+            warn!("Malformed Patch rule: the plugin patches itself");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Patch rule: the plugin patches itself".into(),
+            )));
         }
 
         this.expression_a = Some(expressions[0].clone());
@@ -758,3 +1695,106 @@ impl Display for Patch {
         write!(f, "{}", block)
     }
 }
+
+////////////////////////////////////////////////////////////////////////
+// BLACKLIST
+
+/// The [Blacklist] Rule <A should never be installed>
+/// [Blacklist] evaluates as true if any of the named plugins is installed, the comment is the reason
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Blacklist {
+    pub comment: String,
+    pub expressions: Vec<Expression>,
+
+    pub plugins: Vec<String>,
+
+    /// how serious the warning is, set with ! or ? after the rule name
+    #[serde(default, skip_serializing_if = "ESeverity::is_warning")]
+    pub severity: ESeverity,
+
+    /// where the rule was parsed from, not serialized
+    #[serde(skip)]
+    pub source: Option<RuleSource>,
+    /// disabled rules are parsed but not used, set with ! before the rule name, not serialized
+    #[serde(skip)]
+    pub disabled: bool,
+}
+impl Blacklist {
+    pub fn new(comment: String, expressions: &[Expression]) -> Self {
+        Self {
+            source: None,
+            disabled: false,
+            severity: ESeverity::default(),
+            comment,
+            expressions: expressions.to_vec(),
+            plugins: vec![],
+        }
+    }
+}
+impl TWarningRule for Blacklist {
+    fn get_comment(&self) -> String {
+        format_comment(&self.comment, &self.plugins)
+    }
+    fn get_ui_comment(&self) -> String {
+        format!(
+            "'{}' should not be installed ({})",
+            self.plugins.join("', '"),
+            self.get_comment()
+        )
+    }
+    fn get_plugins(&self) -> Vec<String> {
+        self.plugins.clone()
+    }
+
+    fn set_comment(&mut self, comment: String) {
+        self.comment = comment;
+    }
+    /// Blacklists evaluate as true if any of the named plugins is installed
+    fn eval_context(&mut self, context: &EvalContext) -> bool {
+        self.plugins = self
+            .expressions
+            .iter()
+            .filter_map(|e| e.eval(context))
+            .flatten()
+            .collect();
+        !self.plugins.is_empty()
+    }
+}
+impl TParser<Blacklist> for Blacklist {
+    fn parse<R: Read + BufRead + Seek>(
+        this: &mut Blacklist,
+        reader: R,
+        parser: &parser::Parser,
+    ) -> Result<()> {
+        // add all parsed expressions
+        this.expressions = parser.parse_expressions(reader)?;
+
+        if this.expressions.is_empty() {
+            warn!("Malformed Blacklist rule: no expressions parsed");
+            return Err(Error::other(ParseError::EmptyBody));
+        }
+        // only plugin names can be blacklisted
+        if !this
+            .expressions
+            .iter()
+            .all(|e| matches!(e, Expression::Atomic(_)))
+        {
+            warn!("Malformed Blacklist rule: only plugin names are allowed");
+            return Err(Error::other(ParseError::MalformedRule(
+                "Malformed Blacklist rule: only plugin names are allowed".into(),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Blacklist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut block = format!("[BLACKLIST]\n\t{}\n", self.comment);
+        for e in &self.expressions {
+            block += format!("{}\n", e).as_str();
+        }
+        write!(f, "{}", block)
+    }
+}
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
 
 use log::warn;
 use petgraph::{graph::NodeIndex, stable_graph::StableGraph};
 
 use crate::{
-    get_ordering_from_order_rules, nearend2, nearstart2, wild_contains, EOrderRule, ESupportedGame,
-    EWarningRule, PluginData, GRAPH_FILE,
+    error::TopoError, find_soft_order_violations, get_ordering_from_order_rules_for,
+    get_ordering_from_patch_rules, nearend2, nearstart2, pin2, wild_contains, EOrderRule,
+    EPluginKind, ESupportedGame, EWarningRule, PluginData, GRAPH_FILE,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -23,6 +27,8 @@ pub fn new_stable_sorter() -> Sorter {
     Sorter::new(ESortType::StableOpt, 100)
 }
 
+/// The sort graph of a mod list. The maps are only used for lookups, never iterated,
+/// and the edges are sorted, so the sort result does not depend on the hasher
 pub struct GraphData {
     pub index_dict: HashMap<String, usize>,
     pub index_dict_rev: HashMap<usize, String>,
@@ -44,24 +50,55 @@ impl Sorter {
 
     /// Sorts the input mods topologically. Mods input is case sensitive!
     ///
+    /// The stable sorters keep the input order wherever the rules allow it:
+    /// mods that must load earlier are moved up to the mods that depend on them.
+    /// Mods without any order constraints keep their relative input order,
+    /// and sorting an already sorted list returns it unchanged.
+    ///
     /// # Panics
     ///
     /// Panics if .
     ///
     /// # Errors
     ///
-    /// This function will return an error if any parsing fails
+    /// This function will return an error if there are no order rules or the rules contain a cycle.
+    /// A detected cycle is returned as `TopoError::Cycle` with the plugin names in cycle order.
+    /// Soft order rules never cause an error, a violated soft order is logged as a warning
     pub fn topo_sort(
         &mut self,
         game: ESupportedGame,
         plugins: &[PluginData],
         order_rules: &[EOrderRule],
         warn_rules: &[EWarningRule],
-    ) -> Result<Vec<String>, &'static str> {
+    ) -> Result<Vec<String>, TopoError> {
+        let result = self.sort_hard(game, plugins, order_rules, warn_rules)?;
+
+        // soft orders are not part of the graph, they are only checked against the result
+        for (a, b) in find_soft_order_violations(&result, order_rules) {
+            warn!(
+                "Soft order is not satisfied: {} should load before {}",
+                a, b
+            );
+        }
+        Ok(result)
+    }
+
+    /// Sorts the input mods by the hard order rules only, see topo_sort
+    fn sort_hard(
+        &mut self,
+        game: ESupportedGame,
+        plugins: &[PluginData],
+        order_rules: &[EOrderRule],
+        warn_rules: &[EWarningRule],
+    ) -> Result<Vec<String>, TopoError> {
         // early out
         if order_rules.is_empty() {
             log::info!("No order rules found, nothing to sort");
-            return Err("No order rules found");
+            return Err(TopoError::NoRules);
+        }
+
+        for plugin in detect_near_conflicts(order_rules) {
+            warn!("{} is in both a NearStart and a NearEnd rule", plugin);
         }
 
         let data = get_graph_data(plugins, order_rules, warn_rules);
@@ -74,21 +111,49 @@ impl Sorter {
             ..
         } = data;
 
+        // report two rules that contradict each other before the generic cycle check
+        let order = edges
+            .iter()
+            .map(|(a, b)| (plugins[*a].name.to_owned(), plugins[*b].name.to_owned()))
+            .collect::<Vec<_>>();
+        let contradictions = find_direct_contradictions(&order);
+        for (a, b) in &contradictions {
+            warn!(
+                "Order rules contradict each other: {} -> {} and {} -> {}",
+                a, b, b, a
+            );
+        }
+        if let Some((a, b)) = contradictions.into_iter().next() {
+            return Err(TopoError::Contradiction(a, b));
+        }
+
         // cycle check
         if self.sort_type == ESortType::Unstable {
-            let sort;
             let s = petgraph::algo::toposort(&g, None);
 
-            if let Ok(result) = s {
-                sort = result;
-            } else {
-                return Err("Graph contains a cycle");
-            }
+            let sort = match s {
+                Ok(result) => result,
+                Err(cycle) => {
+                    let cycle = find_cycle(&g, cycle.node_id())
+                        .into_iter()
+                        .map(|idx| plugins[idx.index()].name.to_owned())
+                        .collect();
+                    return Err(TopoError::Cycle(cycle));
+                }
+            };
+
+            // move nearstart and nearend mods as far as their order edges allow
+            let mut mods = sort
+                .into_iter()
+                .map(|idx| index_dict_rev[&idx.index()].to_owned())
+                .collect::<Vec<_>>();
+            place_near_rules(&mut mods, order_rules, &edges, &index_dict, &index_dict_rev);
+            place_pins(&mut mods, order_rules)?;
 
             // map sorted index back to mods
             let mut result = vec![];
-            for idx in sort {
-                let plugin = &plugins[idx.index()];
+            for lower_case_name in mods {
+                let plugin = &plugins[index_dict[&lower_case_name]];
                 result.push(plugin.name.to_owned());
             }
             return Ok(result);
@@ -100,50 +165,22 @@ impl Sorter {
             .map(|f| f.name.to_lowercase())
             .collect::<Vec<String>>();
 
-        // nearstart rules
-        for nearstart in order_rules
-            .iter()
-            .filter_map(nearstart2)
-            .flat_map(|f| f.names)
-            .rev()
-        {
-            if let Some(results) = wild_contains(&mods, &nearstart) {
-                // push to start of mods
-                for r in results {
-                    let index = mods.iter().position(|f| f == &r).unwrap();
-                    let element = mods.remove(index);
-                    mods.insert(0, element);
-                }
-            }
-        }
-
-        // nearend rules
-        for nearend in order_rules
-            .iter()
-            .filter_map(nearend2)
-            .flat_map(|f| f.names)
-            .rev()
-        {
-            if let Some(results) = wild_contains(&mods, &nearend) {
-                // push to end of mods
-                for r in results {
-                    let index = mods.iter().position(|f| f == &r).unwrap();
-                    let element = mods.remove(index);
-                    mods.push(element);
-                }
-            }
-        }
+        // nearstart and nearend rules
+        place_near_rules(&mut mods, order_rules, &edges, &index_dict, &index_dict_rev);
+        let input_order = mods.clone();
 
         let n = plugins.len();
 
         let mut index = 0;
 
         edges.sort_by_key(|k| k.0);
+        let edge_set: HashSet<(usize, usize)> = edges.iter().copied().collect();
 
         for i in 1..self.max_iterations {
             let any_change = self.stable_topo_sort_inner(
                 n,
                 &edges,
+                &edge_set,
                 &index_dict,
                 &index_dict_rev,
                 &mut mods,
@@ -152,12 +189,18 @@ impl Sorter {
 
             // sort again
             if !any_change {
+                // the swaps converge on some valid order, make it the one closest to the input order
+                mods = order_by_position(&input_order, &edges, &index_dict, &index_dict_rev);
+
                 // sort esms now?
-                if game == ESupportedGame::Morrowind || game == ESupportedGame::Openmw {
+                if game == ESupportedGame::Morrowind
+                    || game == ESupportedGame::Openmw
+                    || game == ESupportedGame::Skyrim
+                {
                     // put all items in mods_copy ending with .esm at the start
                     let mut esms = vec![];
                     for (i, m) in mods.iter().enumerate() {
-                        if m.ends_with(".esm") || m.ends_with(".omwgame") {
+                        if EPluginKind::from_name(m).is_master() {
                             esms.push(i);
                         }
                     }
@@ -187,6 +230,9 @@ impl Sorter {
                     }
                 }
 
+                // pinned mods go to their absolute index last
+                place_pins(&mut mods, order_rules)?;
+
                 // Return the sorted vector
                 // map sorted index back to mods
                 let mut result = vec![];
@@ -216,14 +262,64 @@ impl Sorter {
                 .expect("write failed");
         }
 
+        // report the cycle if there is one
+        if let Err(cycle) = petgraph::algo::toposort(&g, None) {
+            let cycle = find_cycle(&g, cycle.node_id())
+                .into_iter()
+                .map(|idx| plugins[idx.index()].name.to_owned())
+                .collect();
+            return Err(TopoError::Cycle(cycle));
+        }
+
         log::error!("Out of iterations");
-        Err("Out of iterations")
+        Err(TopoError::OutOfIterations)
     }
 
+    /// Sorts the input mods topologically like topo_sort, using a priority list as a tie-break:
+    /// mods that appear earlier in priority load earlier when no order rule dictates otherwise.
+    /// Mods not in the priority list keep their input position. Matching ignores case
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there are no order rules or the rules contain a cycle.
+    pub fn topo_sort_with_priority(
+        &mut self,
+        game: ESupportedGame,
+        plugins: &[PluginData],
+        order_rules: &[EOrderRule],
+        warn_rules: &[EWarningRule],
+        priority: &[String],
+    ) -> Result<Vec<String>, TopoError> {
+        let rank = |plugin: &PluginData| {
+            priority
+                .iter()
+                .position(|p| p.eq_ignore_ascii_case(&plugin.name))
+        };
+
+        // put the prioritized mods into their own slots in priority order, the stable sort keeps this order where it can
+        let slots = plugins
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| rank(p).is_some())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let mut prioritized = slots.iter().map(|&i| &plugins[i]).collect::<Vec<_>>();
+        prioritized.sort_by_key(|p| rank(p));
+
+        let mut biased = plugins.to_vec();
+        for (slot, plugin) in slots.into_iter().zip(prioritized) {
+            biased[slot] = plugin.clone();
+        }
+
+        self.topo_sort(game, &biased, order_rules, warn_rules)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn stable_topo_sort_inner(
         &self,
         n: usize,
         edges: &[(usize, usize)],
+        edge_set: &HashSet<(usize, usize)>,
         index_dict: &HashMap<String, usize>,
         index_dict_rev: &HashMap<usize, String>,
         result: &mut Vec<String>,
@@ -235,23 +331,28 @@ impl Sorter {
                 Self::stable_topo_sort_opt2(n, edges, index_dict_rev, result, last_index)
             }
             ESortType::StableFull => {
-                Self::stable_topo_sort_full(n, edges, index_dict, result, last_index)
+                Self::stable_topo_sort_full(n, edge_set, index_dict, result, last_index)
             }
         }
     }
 
     pub fn stable_topo_sort_full(
         n: usize,
-        edges: &[(usize, usize)],
+        edge_set: &HashSet<(usize, usize)>,
         index_dict: &HashMap<String, usize>,
         result: &mut Vec<String>,
         last_index: &mut usize,
     ) -> bool {
+        // resolve the names once per pass
+        let indices = result
+            .iter()
+            .map(|f| index_dict[f.as_str()])
+            .collect::<Vec<_>>();
         for i in 0..n {
             for j in 0..i {
-                let x = index_dict[result[i].as_str()];
-                let y = index_dict[result[j].as_str()];
-                if edges.contains(&(x, y)) {
+                let x = indices[i];
+                let y = indices[j];
+                if edge_set.contains(&(x, y)) {
                     let t = result[i].to_owned();
                     result.remove(i);
                     result.insert(j, t);
@@ -273,6 +374,13 @@ impl Sorter {
         last_index: &mut usize,
     ) -> bool {
         // optimize B: only check edges
+        // keep a lookup of the current position of each mod
+        let mut positions: HashMap<String, usize> = result
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.to_owned(), i))
+            .collect();
+
         let mut b = false;
         for (idx, edge) in edges.iter().enumerate() {
             let i = edge.0;
@@ -281,8 +389,8 @@ impl Sorter {
             let x = &index_dict_rev[&i];
             let y = &index_dict_rev[&j];
 
-            let idx_of_x = result.iter().position(|f| f == x).unwrap();
-            let idx_of_y = result.iter().position(|f| f == y).unwrap();
+            let idx_of_x = positions[x];
+            let idx_of_y = positions[y];
 
             // if i not before j x should be before y
             if idx_of_x > idx_of_y {
@@ -290,6 +398,13 @@ impl Sorter {
                 result.remove(idx_of_x);
                 result.insert(idx_of_y, t);
 
+                // only the moved range shifted
+                for (k, name) in result.iter().enumerate().take(idx_of_x + 1).skip(idx_of_y) {
+                    if let Some(p) = positions.get_mut(name) {
+                        *p = k;
+                    }
+                }
+
                 *last_index = idx;
 
                 b = true;
@@ -303,10 +418,186 @@ impl Sorter {
     }
 }
 
+/// Topologically sorts mods by the edges, greedily staying close to the order of mods:
+/// the order is built from the back, every slot gets the last mod in mods whose successors are all placed.
+/// Mods that must load earlier are pulled up to the mods that depend on them, past unrelated mods if needed,
+/// e.g. [b, x, a] with the edge a -> b becomes [a, b, x].
+/// Mods without any edges keep their relative order, and mods that are already in a valid order are returned unchanged.
+/// Returns mods unchanged if the edges contain a cycle
+pub fn order_by_position(
+    mods: &[String],
+    edges: &[(usize, usize)],
+    index_dict: &HashMap<String, usize>,
+    index_dict_rev: &HashMap<usize, String>,
+) -> Vec<String> {
+    let positions = mods
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (index_dict[f.as_str()], i))
+        .collect::<HashMap<_, _>>();
+
+    let mut out_degree: HashMap<usize, usize> = HashMap::new();
+    let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &(from, to) in edges {
+        *out_degree.entry(from).or_default() += 1;
+        predecessors.entry(to).or_default().push(from);
+    }
+
+    // max-heap of the positions of all mods that have no unsorted successor
+    let mut ready = positions
+        .iter()
+        .filter(|(node, _)| !out_degree.contains_key(node))
+        .map(|(_, &position)| position)
+        .collect::<BinaryHeap<_>>();
+
+    let mut result = Vec::with_capacity(mods.len());
+    while let Some(position) = ready.pop() {
+        let node = index_dict[mods[position].as_str()];
+        result.push(index_dict_rev[&node].to_owned());
+        for previous in predecessors.get(&node).into_iter().flatten() {
+            if let Some(degree) = out_degree.get_mut(previous) {
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(positions[previous]);
+                }
+            }
+        }
+    }
+
+    if result.len() != mods.len() {
+        warn!("Order rules contain a cycle, keeping the current order");
+        return mods.to_vec();
+    }
+    result.reverse();
+    result
+}
+
+/// Returns the plugins that are named in both a nearstart and a nearend rule, in order of first appearance.
+/// Names are compared case-insensitively, wildcards are compared literally
+pub fn detect_near_conflicts(order_rules: &[EOrderRule]) -> Vec<String> {
+    let nearend = order_rules
+        .iter()
+        .filter_map(nearend2)
+        .flat_map(|f| f.names)
+        .map(|f| f.to_lowercase())
+        .collect::<HashSet<_>>();
+
+    let mut conflicts: Vec<String> = vec![];
+    for name in order_rules
+        .iter()
+        .filter_map(nearstart2)
+        .flat_map(|f| f.names)
+        .map(|f| f.to_lowercase())
+    {
+        if nearend.contains(&name) && !conflicts.contains(&name) {
+            conflicts.push(name);
+        }
+    }
+    conflicts
+}
+
+/// Moves mods matched by nearstart rules to the start and mods matched by nearend rules to the end.
+/// Mods that must load before a nearstart mod (or after a nearend mod) are moved along with it,
+/// so a valid order stays valid. Mods matched by the same rule keep their relative order
+pub fn place_near_rules(
+    mods: &mut Vec<String>,
+    order_rules: &[EOrderRule],
+    edges: &[(usize, usize)],
+    index_dict: &HashMap<String, usize>,
+    index_dict_rev: &HashMap<usize, String>,
+) {
+    // collects the mod and all mods connected to it in one direction
+    let closure = |name: &str, forward: bool| -> HashSet<String> {
+        let mut visited = HashSet::from([index_dict[name]]);
+        let mut queue = VecDeque::from([index_dict[name]]);
+        while let Some(node) = queue.pop_front() {
+            for &(from, to) in edges {
+                let (this, next) = if forward { (from, to) } else { (to, from) };
+                if this == node && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        visited
+            .into_iter()
+            .map(|i| index_dict_rev[&i].to_owned())
+            .collect()
+    };
+
+    // nearstart rules, by descending priority then input order
+    let mut nearstart_rules = order_rules
+        .iter()
+        .filter_map(nearstart2)
+        .collect::<Vec<_>>();
+    nearstart_rules.sort_by_key(|f| Reverse(f.priority));
+    for nearstart in nearstart_rules.into_iter().flat_map(|f| f.names).rev() {
+        if let Some(results) = wild_contains(mods, &nearstart) {
+            // pull to start of mods together with everything that must load before it
+            for r in results.iter().rev() {
+                let group = closure(r, false);
+                let (mut front, rest): (Vec<_>, Vec<_>) =
+                    mods.drain(..).partition(|f| group.contains(f));
+                front.extend(rest);
+                *mods = front;
+            }
+        }
+    }
+
+    // nearend rules, by descending priority then input order
+    let mut nearend_rules = order_rules.iter().filter_map(nearend2).collect::<Vec<_>>();
+    nearend_rules.sort_by_key(|f| Reverse(f.priority));
+    for nearend in nearend_rules.into_iter().flat_map(|f| f.names).rev() {
+        if let Some(results) = wild_contains(mods, &nearend) {
+            // push to end of mods together with everything that must load after it
+            for r in results {
+                let group = closure(&r, true);
+                let (back, mut rest): (Vec<_>, Vec<_>) =
+                    mods.drain(..).partition(|f| group.contains(f));
+                rest.extend(back);
+                *mods = rest;
+            }
+        }
+    }
+}
+
+/// Moves mods matched by pin rules to their pinned index, after all other rules were applied.
+/// Indices past the end of the load order put the mod last
+///
+/// # Errors
+///
+/// This function will return an error if two installed mods are pinned to the same index
+pub fn place_pins(mods: &mut Vec<String>, order_rules: &[EOrderRule]) -> Result<(), TopoError> {
+    // only pins of installed mods apply
+    let mut pins: Vec<(usize, String)> = vec![];
+    for pin in order_rules.iter().filter_map(pin2) {
+        if !mods.contains(&pin.name) || pins.iter().any(|(_, name)| name == &pin.name) {
+            continue;
+        }
+        if let Some((_, other)) = pins.iter().find(|(index, _)| *index == pin.index) {
+            return Err(TopoError::PinConflict(
+                pin.index,
+                other.to_owned(),
+                pin.name.to_owned(),
+            ));
+        }
+        pins.push((pin.index, pin.name));
+    }
+
+    // take all pinned mods out and insert them in ascending index order
+    mods.retain(|f| !pins.iter().any(|(_, name)| name == f));
+    pins.sort_by_key(|(index, _)| *index);
+    for (index, name) in pins {
+        let index = index.min(mods.len());
+        mods.insert(index, name);
+    }
+
+    Ok(())
+}
+
 pub fn get_graph_data(
     plugins: &[PluginData],
     order_rules: &[EOrderRule],
-    _warn_rules: &[EWarningRule],
+    warn_rules: &[EWarningRule],
 ) -> GraphData {
     // build hashmaps for lookup
     let mut index_dict: HashMap<String, usize> = HashMap::new();
@@ -328,7 +619,10 @@ pub fn get_graph_data(
         .map(|f| f.name.to_lowercase())
         .collect::<Vec<String>>();
 
-    let order_pairs = get_ordering_from_order_rules(order_rules);
+    // guarded order rules only apply if their guard is true for the installed mods
+    // patches load after the original, if both are installed
+    let mut order_pairs = get_ordering_from_order_rules_for(order_rules, &mods);
+    order_pairs.extend(get_ordering_from_patch_rules(warn_rules));
     let mut edges: Vec<(usize, usize)> = vec![];
     for (a, b) in order_pairs {
         if let Some(results_for_a) = wild_contains(&mods, &a) {
@@ -372,6 +666,9 @@ pub fn get_graph_data(
         }
     }
 
+    // canonical edge order, so the sort only depends on the mods and the set of edges
+    edges.sort_unstable();
+
     // return
     GraphData {
         index_dict,
@@ -400,3 +697,286 @@ pub fn build_graph(data: &GraphData) -> StableGraph<String, ()> {
 
     g
 }
+
+/// Finds the shortest cycle through the start node, returns the nodes in cycle order starting with start
+pub fn find_cycle(g: &StableGraph<String, ()>, start: NodeIndex) -> Vec<NodeIndex> {
+    // only nodes in the same strongly connected component can be part of the cycle
+    let scc: HashSet<NodeIndex> = petgraph::algo::tarjan_scc(g)
+        .into_iter()
+        .find(|c| c.contains(&start))
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    // bfs from start back to start
+    let mut parents: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for next in g.neighbors(node) {
+            if next == start {
+                // walk back to start
+                let mut cycle = vec![node];
+                let mut current = node;
+                while let Some(parent) = parents.get(&current) {
+                    cycle.push(*parent);
+                    current = *parent;
+                }
+                cycle.reverse();
+                return cycle;
+            }
+            if scc.contains(&next) && next != start && !parents.contains_key(&next) {
+                parents.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    vec![]
+}
+
+/// Explains the position of a plugin in a load order: returns the chains of order edges
+/// that (transitively) force it after or before other mods, e.g. "a.esp -> b.esp".
+/// Uses the same edges as topo_sort. Returns an empty list if the plugin is not in the order
+pub fn explain_position(plugin: &str, order: &[String], rules: &[EOrderRule]) -> Vec<String> {
+    let plugins = order
+        .iter()
+        .map(|f| PluginData::new(f.to_owned(), 0))
+        .collect::<Vec<_>>();
+    let data = get_graph_data(&plugins, rules, &[]);
+    let Some(&start) = data.index_dict.get(&plugin.to_lowercase()) else {
+        return vec![];
+    };
+
+    // walks the edges from start in one direction, in the order they are found
+    let walk = |forward: bool| -> Vec<(usize, usize)> {
+        let mut found = vec![];
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &(from, to) in &data.edges {
+                let (this, next) = if forward { (from, to) } else { (to, from) };
+                if this == node {
+                    found.push((from, to));
+                    if visited.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        found
+    };
+
+    // mods that load before the plugin, farthest first, then mods that load after it
+    let mut edges = walk(false);
+    edges.reverse();
+    edges.extend(walk(true));
+
+    edges
+        .into_iter()
+        .map(|(a, b)| format!("{} -> {}", order[a], order[b]))
+        .collect()
+}
+
+/// Collects the order edges that reference a plugin which is not in the mod list, e.g. because of a typo
+/// in a rules file. topo_sort skips these edges, this is only a diagnostic and does not affect sorting.
+/// Use suggest_plugin to find the installed plugin a dangling name was probably meant to be
+pub fn dangling_order_refs(mods: &[String], order: &[(String, String)]) -> Vec<(String, String)> {
+    let mods = mods.iter().map(|f| f.to_lowercase()).collect::<Vec<_>>();
+    order
+        .iter()
+        .filter(|(a, b)| wild_contains(&mods, a).is_none() || wild_contains(&mods, b).is_none())
+        .cloned()
+        .collect()
+}
+
+/// Partitions the mods into the weakly connected components of the order graph, e.g. to show or sort
+/// independent clusters of mods separately. Mods without order pairs are a component of their own.
+/// Components are listed in the order of their first mod, the mods of a component keep the input order
+pub fn order_components(mods: &[String], order: &[(String, String)]) -> Vec<Vec<String>> {
+    let lower_case = mods.iter().map(|m| m.to_lowercase()).collect::<Vec<_>>();
+    let index_dict = lower_case
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.to_owned(), i))
+        .collect::<HashMap<_, _>>();
+    let index_dict_rev = lower_case
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (i, m.to_owned()))
+        .collect::<HashMap<_, _>>();
+
+    let mut edges: Vec<(usize, usize)> = vec![];
+    for (a, b) in order {
+        let (Some(results_for_a), Some(results_for_b)) = (
+            wild_contains(&lower_case, &a.to_lowercase()),
+            wild_contains(&lower_case, &b.to_lowercase()),
+        ) else {
+            continue;
+        };
+        for i in &results_for_a {
+            for j in &results_for_b {
+                let edge = (index_dict[i], index_dict[j]);
+                if edge.0 != edge.1 && !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+    }
+
+    let data = GraphData {
+        index_dict,
+        index_dict_rev,
+        edges,
+    };
+    let g = build_graph(&data);
+    let mut sets = petgraph::unionfind::UnionFind::<usize>::new(mods.len());
+    for edge in g.edge_indices() {
+        if let Some((a, b)) = g.edge_endpoints(edge) {
+            sets.union(a.index(), b.index());
+        }
+    }
+
+    let mut components: Vec<Vec<String>> = vec![];
+    let mut component_of_root: HashMap<usize, usize> = HashMap::new();
+    for (i, name) in mods.iter().enumerate() {
+        let root = sets.find(i);
+        let component = *component_of_root.entry(root).or_insert_with(|| {
+            components.push(vec![]);
+            components.len() - 1
+        });
+        components[component].push(name.to_owned());
+    }
+    components
+}
+
+/// Finds order pairs that also appear in the opposite direction, e.g. (a, b) and (b, a) from two different rules.
+/// Each contradiction is listed once in the direction it first appears, names are compared case-insensitively
+pub fn find_direct_contradictions(order: &[(String, String)]) -> Vec<(String, String)> {
+    let pairs = order
+        .iter()
+        .map(|(a, b)| (a.to_lowercase(), b.to_lowercase()))
+        .collect::<HashSet<_>>();
+
+    let mut seen = HashSet::new();
+    let mut result = vec![];
+    for (a, b) in order {
+        let (la, lb) = (a.to_lowercase(), b.to_lowercase());
+        if la != lb
+            && pairs.contains(&(lb.clone(), la.clone()))
+            && !seen.contains(&(lb.clone(), la.clone()))
+            && seen.insert((la, lb))
+        {
+            result.push((a.to_owned(), b.to_owned()));
+        }
+    }
+    result
+}
+
+/// Computes the transitive closure of the order pairs: every pair (a, b) where a loads before b through any chain.
+/// Pairs are listed per plugin in the order the plugins first appear in the input, e.g. to find redundant rules
+///
+/// # Errors
+///
+/// This function will return an error if the order pairs contain a cycle
+pub fn transitive_order(order: &[(String, String)]) -> Result<Vec<(String, String)>, TopoError> {
+    let mut index_dict: HashMap<String, usize> = HashMap::new();
+    let mut index_dict_rev: HashMap<usize, String> = HashMap::new();
+    let mut edges: Vec<(usize, usize)> = vec![];
+    for (a, b) in order {
+        let mut index_of = |name: &String| {
+            let n = index_dict.len();
+            *index_dict.entry(name.to_owned()).or_insert_with(|| {
+                index_dict_rev.insert(n, name.to_owned());
+                n
+            })
+        };
+        let edge = (index_of(a), index_of(b));
+        if !edges.contains(&edge) {
+            edges.push(edge);
+        }
+    }
+
+    let data = GraphData {
+        index_dict,
+        index_dict_rev,
+        edges,
+    };
+    let g = build_graph(&data);
+    if let Err(cycle) = petgraph::algo::toposort(&g, None) {
+        let cycle = find_cycle(&g, cycle.node_id())
+            .into_iter()
+            .map(|idx| data.index_dict_rev[&idx.index()].to_owned())
+            .collect();
+        return Err(TopoError::Cycle(cycle));
+    }
+
+    let mut closure = vec![];
+    for start in 0..data.index_dict_rev.len() {
+        let start = NodeIndex::new(start);
+        let mut dfs = petgraph::visit::Dfs::new(&g, start);
+        while let Some(node) = dfs.next(&g) {
+            if node != start {
+                closure.push((g[start].to_owned(), g[node].to_owned()));
+            }
+        }
+    }
+
+    Ok(closure)
+}
+
+/// Finds order pairs that are implied by a chain of other pairs and can be removed without changing the sort,
+/// e.g. (a, c) given (a, b) and (b, c). Returns an empty list if the order pairs contain a cycle
+pub fn find_redundant_orders(order: &[(String, String)]) -> Vec<(String, String)> {
+    let closure = match transitive_order(order) {
+        Ok(closure) => closure.into_iter().collect::<HashSet<_>>(),
+        Err(e) => {
+            warn!("Could not find redundant orders: {}", e);
+            return vec![];
+        }
+    };
+
+    let mut redundant: Vec<(String, String)> = vec![];
+    for (a, c) in order {
+        // a pair is implied if another successor of a loads before c
+        let implied = order
+            .iter()
+            .any(|(x, b)| x == a && b != c && closure.contains(&(b.to_owned(), c.to_owned())));
+        if implied && !redundant.contains(&(a.to_owned(), c.to_owned())) {
+            redundant.push((a.to_owned(), c.to_owned()));
+        }
+    }
+
+    redundant
+}
+
+/// The maximum edit distance for suggest_plugin
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Suggests the installed plugin that is closest to name, e.g. to point out typos in rules.
+/// Names are compared case-insensitively, plugins that need more than two edits are not suggested
+pub fn suggest_plugin(name: &str, installed: &[String]) -> Option<String> {
+    let name = name.to_lowercase();
+    installed
+        .iter()
+        .map(|p| (edit_distance(&name, &p.to_lowercase()), p))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, p)| p.to_owned())
+}
+
+/// The Levenshtein distance between two strings, in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // distances from the previous prefix of a to all prefixes of b
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
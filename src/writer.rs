@@ -0,0 +1,221 @@
+////////////////////////////////////////////////////////////////////////
+// WRITER
+////////////////////////////////////////////////////////////////////////
+
+use std::fmt::Write as _;
+use std::io::{Result, Write};
+
+use crate::{expressions::*, rules::*};
+
+/// Assembles rules in code and writes them as rules file text that the parser reads back.
+/// Names and comments are written as given, the parser lowercases them when reading
+#[derive(Debug, Clone, Default)]
+pub struct RuleWriter {
+    pub rules: Vec<ERule>,
+}
+
+impl RuleWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds any rule
+    pub fn add_rule<T: Into<ERule>>(&mut self, rule: T) -> &mut Self {
+        self.rules.push(rule.into());
+        self
+    }
+
+    /// Adds an [Order] rule, the names load in the given order
+    pub fn add_order(&mut self, names: &[&str]) -> &mut Self {
+        self.add_rule(Order::new(to_names(names)))
+    }
+
+    /// Adds a [NearStart] rule
+    pub fn add_nearstart(&mut self, names: &[&str]) -> &mut Self {
+        self.add_rule(NearStart::new(to_names(names)))
+    }
+
+    /// Adds a [NearEnd] rule
+    pub fn add_nearend(&mut self, names: &[&str]) -> &mut Self {
+        self.add_rule(NearEnd::new(to_names(names)))
+    }
+
+    /// Adds a [Note] rule that fires if any of the expressions is true
+    pub fn add_note(&mut self, comment: &str, expressions: &[Expression]) -> &mut Self {
+        self.add_rule(Note::new(comment.into(), expressions))
+    }
+
+    /// Adds a [Conflict] rule that fires if two or more of the expressions are true
+    pub fn add_conflict(&mut self, comment: &str, expressions: &[Expression]) -> &mut Self {
+        self.add_rule(Conflict::new(comment.into(), expressions))
+    }
+
+    /// Adds a [Requires] rule that fires if a is true and b is false
+    pub fn add_requires(&mut self, comment: &str, a: Expression, b: Expression) -> &mut Self {
+        self.add_rule(Requires::new(comment.into(), a, b))
+    }
+
+    /// Adds a [Patch] rule for the patch a of the original b
+    pub fn add_patch(&mut self, comment: &str, a: Expression, b: Expression) -> &mut Self {
+        self.add_rule(Patch::new(comment.into(), a, b))
+    }
+
+    /// Writes all rules as rules file text
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing fails
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self.to_text().as_bytes())
+    }
+
+    /// Returns all rules as rules file text, rules are separated by an empty line
+    pub fn to_text(&self) -> String {
+        self.rules
+            .iter()
+            .map(rule_to_text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn to_names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+/// Quotes a plugin name in an order rule if it contains whitespace or quotes, quotes and backslashes are escaped
+fn quote_name(name: &str) -> String {
+    if name.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Writes an expression like its Display impl on one line, plugin names keep the case they were written in.
+/// Plugin names in expressions can't be quoted, a name with whitespace ends at its extension
+fn expression_to_text(expression: &Expression) -> String {
+    let list = |expressions: &[Expression]| {
+        expressions
+            .iter()
+            .map(expression_to_text)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    match expression {
+        Expression::Atomic(x) => x.display_name().to_owned(),
+        Expression::ALL(x) => format!("[ALL {}]", list(&x.expressions)),
+        Expression::ANY(x) => format!("[ANY {}]", list(&x.expressions)),
+        Expression::NOT(x) => format!("[NOT {}]", expression_to_text(&x.expression)),
+        Expression::DESC(x) => {
+            let negated = if x.is_negated { "!" } else { "" };
+            format!(
+                "[DESC {}/{}/ {}]",
+                negated,
+                x.regex,
+                x.expression.display_name()
+            )
+        }
+        Expression::SIZE(x) => {
+            let negated = if x.is_negated { "!" } else { "" };
+            format!(
+                "[SIZE {}{} {}]",
+                negated,
+                x.size,
+                x.expression.display_name()
+            )
+        }
+        Expression::VER(x) => format!(
+            "[VER {} {} {}]",
+            x.operator,
+            x.version,
+            x.expression.display_name()
+        ),
+        Expression::GVER(x) => format!(
+            "[GVER {} {} {}]",
+            x.operator,
+            x.version,
+            x.expression.display_name()
+        ),
+    }
+}
+
+/// The header of a warning rule with the severity marker and the comment
+fn warning_header(name: &str, severity: ESeverity, comment: &str) -> String {
+    let marker = match severity {
+        ESeverity::Info => "?",
+        ESeverity::Warning => "",
+        ESeverity::Error => "!",
+    };
+    if comment.is_empty() {
+        format!("[{}{}]", name, marker)
+    } else {
+        format!("[{}{} {}]", name, marker, comment)
+    }
+}
+
+fn rule_to_text(rule: &ERule) -> String {
+    let mut text = String::new();
+    let mut lines = |header: String, body: Vec<String>| {
+        let _ = writeln!(text, "{}", header);
+        for line in body {
+            let _ = writeln!(text, "{}", line);
+        }
+    };
+    let names = |names: &[String]| names.iter().map(|n| quote_name(n)).collect::<Vec<_>>();
+    let expressions =
+        |expressions: &[&Expression]| expressions.iter().map(|e| expression_to_text(e)).collect();
+    let priority = |name: &str, priority: i32| {
+        if priority == 0 {
+            format!("[{}]", name)
+        } else {
+            format!("[{} {}]", name, priority)
+        }
+    };
+
+    match rule {
+        ERule::EOrderRule(o) => match o {
+            EOrderRule::Order(x) => {
+                let mut body = x.guard.iter().map(expression_to_text).collect::<Vec<_>>();
+                body.extend(names(&x.names));
+                let header = if x.soft { "[Order?]" } else { "[Order]" };
+                lines(header.into(), body)
+            }
+            EOrderRule::NearStart(x) => lines(priority("NearStart", x.priority), names(&x.names)),
+            EOrderRule::NearEnd(x) => lines(priority("NearEnd", x.priority), names(&x.names)),
+            EOrderRule::Pin(x) => lines(
+                format!("[Pin {}]", x.index),
+                names(std::slice::from_ref(&x.name)),
+            ),
+            EOrderRule::Remove(x) => lines("[Remove]".into(), names(&x.names)),
+            EOrderRule::Group(x) => lines(format!("[Group {}]", x.name), names(&x.names)),
+        },
+        ERule::EWarningRule(w) => {
+            let mut body = expressions(&w.expressions());
+            let header = match w {
+                // ordered notes list their two plugins instead of expressions
+                EWarningRule::Note(x) => match &x.order {
+                    Some((a, b)) => {
+                        body = vec![a.to_owned(), b.to_owned()];
+                        warning_header("NoteOrder", x.severity, &x.comment)
+                    }
+                    None => warning_header("Note", x.severity, &x.comment),
+                },
+                EWarningRule::Conflict(x) => warning_header("Conflict", x.severity, &x.comment),
+                EWarningRule::Requires(x) => warning_header("Requires", x.severity, &x.comment),
+                EWarningRule::RequiresVersion(x) => warning_header(
+                    "RequiresVersion",
+                    x.severity,
+                    format!("{} {}", x.version, x.comment).trim(),
+                ),
+                EWarningRule::Patch(x) => warning_header("Patch", x.severity, &x.comment),
+                EWarningRule::Blacklist(x) => warning_header("Blacklist", x.severity, &x.comment),
+            };
+            lines(header, body)
+        }
+    }
+    if rule.is_disabled() {
+        text.insert(1, '!');
+    }
+    text
+}
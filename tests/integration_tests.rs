@@ -7,7 +7,7 @@ mod integration_tests {
     use plox::{parser::*, sorter::*, *};
     use rand::rng;
     use rand::seq::SliceRandom;
-    use rules::{EWarningRule, TWarningRule};
+    use rules::{EOrderRule, ESeverity, EWarningRule, TWarningRule};
     use semver::Version;
 
     fn init() {
@@ -21,13 +21,47 @@ mod integration_tests {
         Sorter::new(sorter::ESortType::StableFull, 1000)
     }
 
+    /// writes a minimal TES3 plugin with only a header record
+    fn write_tes3_plugin(path: &std::path::Path, description: &str, masters: &[(&str, u64)]) {
+        let mut hedr: Vec<u8> = vec![];
+        hedr.extend(b"HEDR");
+        hedr.extend(300_u32.to_le_bytes());
+        hedr.extend(1.3_f32.to_le_bytes());
+        hedr.extend(0_u32.to_le_bytes());
+        let mut author = [0_u8; 32];
+        author[..4].copy_from_slice(b"plox");
+        hedr.extend(author);
+        let mut desc = [0_u8; 256];
+        desc[..description.len()].copy_from_slice(description.as_bytes());
+        hedr.extend(desc);
+        hedr.extend(0_u32.to_le_bytes());
+        for (name, size) in masters {
+            let name = format!("{}\0", name);
+            hedr.extend(b"MAST");
+            hedr.extend((name.len() as u32).to_le_bytes());
+            hedr.extend(name.as_bytes());
+            hedr.extend(b"DATA");
+            hedr.extend(8_u32.to_le_bytes());
+            hedr.extend(size.to_le_bytes());
+        }
+
+        let mut buffer: Vec<u8> = vec![];
+        buffer.extend(b"TES3");
+        buffer.extend((hedr.len() as u32).to_le_bytes());
+        buffer.extend([0_u8; 8]);
+        buffer.extend(hedr);
+
+        std::fs::write(path, buffer).expect("could not write plugin");
+    }
+
     fn clean_mods(plugins: &[PluginData], warning_rules: &[EWarningRule]) -> Vec<PluginData> {
         let mut mods_to_remove = vec![];
         let mut warning_rules = warning_rules.to_vec();
+        let context = expressions::EvalContext::from_plugins(plugins);
         for rule in warning_rules.iter_mut() {
             // only conflict rules
             if let EWarningRule::Conflict(ref mut conflict) = rule {
-                if conflict.eval(plugins) {
+                if conflict.eval_context(&context) {
                     // remove mods
                     // switch on the len of conflict.conflicts
                     let groups_size = conflict.conflicts.len();
@@ -502,6 +536,48 @@ mod integration_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_sort_deterministic() -> std::io::Result<()> {
+        init();
+
+        let mut parser = parser::new_tes3_parser();
+        parser.init_from_file("./tests/mlox/mlox_base.txt")?;
+        let mut mods = debug_get_mods_from_order_rules(&parser.order_rules);
+        mods.reverse();
+        let mods = mods.into_iter().take(100).collect::<Vec<_>>();
+
+        // every sort creates new hash maps with new random seeds
+        for mut sorter in [new_unstable_sorter(), new_stable_sorter()] {
+            let first = sorter
+                .topo_sort(ESupportedGame::Morrowind, &mods, &parser.order_rules, &[])
+                .expect("rules contain a cycle");
+            for _ in 0..10 {
+                let result = sorter
+                    .topo_sort(ESupportedGame::Morrowind, &mods, &parser.order_rules, &[])
+                    .expect("rules contain a cycle");
+                assert_eq!(first, result);
+            }
+        }
+
+        // the order of the order rules does not change the result
+        let (mut orders, others): (Vec<_>, Vec<_>) = parser
+            .order_rules
+            .iter()
+            .cloned()
+            .partition(|r| matches!(r, EOrderRule::Order(_)));
+        orders.reverse();
+        let reordered = others.into_iter().chain(orders).collect::<Vec<_>>();
+        let expected = new_unstable_sorter()
+            .topo_sort(ESupportedGame::Morrowind, &mods, &parser.order_rules, &[])
+            .expect("rules contain a cycle");
+        let result = new_unstable_sorter()
+            .topo_sort(ESupportedGame::Morrowind, &mods, &reordered, &[])
+            .expect("rules contain a cycle");
+        assert_eq!(expected, result);
+
+        Ok(())
+    }
+
     #[test]
     fn test_optimized_sort_time() -> std::io::Result<()> {
         init();
@@ -548,6 +624,99 @@ mod integration_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_validate_rules() -> std::io::Result<()> {
+        init();
+
+        let path = std::path::Path::new("./tests/plox/rules_malformed.txt");
+        assert_eq!(
+            std::process::ExitCode::FAILURE,
+            validate_rules(path, ESupportedGame::Morrowind)
+        );
+
+        // the valid rules around the malformed block are still counted
+        let (rules, errors) = parser::new_tes3_parser()
+            .parse_rules_with_errors(std::io::BufReader::new(std::fs::File::open(path)?))?;
+        let counts = count_rules(&rules);
+        assert_eq!(Some(&1), counts.get("Order"));
+        assert_eq!(Some(&1), counts.get("Note"));
+        assert_eq!(Some(&1), counts.get("NearStart"));
+        assert_eq!(None, counts.get("Requires"));
+        assert_eq!(1, errors.len());
+        assert_eq!(Some("requires".to_owned()), errors[0].rule);
+        assert_eq!(9, errors[0].line);
+
+        assert_eq!(
+            std::process::ExitCode::SUCCESS,
+            validate_rules(
+                std::path::Path::new("./tests/plox/rules_order.txt"),
+                ESupportedGame::Morrowind
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mlox_order_rules_parse() -> std::io::Result<()> {
+        init();
+
+        // plugin names with brackets, e.g. [Official]Adamantium Armor.esp, are not guards
+        for (path, expected) in [
+            ("./tests/mlox/mlox_base.txt", 2889),
+            ("./tests/mlox/mlox_user.txt", 1514),
+        ] {
+            let (rules, errors) = parser::new_tes3_parser()
+                .parse_rules_with_errors(std::io::BufReader::new(std::fs::File::open(path)?))?;
+            let order_errors = errors
+                .iter()
+                .filter(|e| e.rule.as_deref() == Some("order"))
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>();
+            assert!(order_errors.is_empty(), "{}: {:?}", path, order_errors);
+            assert_eq!(
+                Some(&expected),
+                count_rules(&rules).get("Order"),
+                "{}",
+                path
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_rules() -> std::io::Result<()> {
+        init();
+
+        let rules_dir = PathBuf::from("tmp").join("merge_rules");
+        create_dir_all(&rules_dir)?;
+        std::fs::write(
+            rules_dir.join("mlox_base.txt"),
+            "[Order]\na.esp\nb.esp\nc.esp\n\n[Order]\nA.esp\nC.esp\n\n[Order]\nd.esp\ne.esp\n",
+        )?;
+        std::fs::write(rules_dir.join("mlox_user.txt"), "[Remove]\na.esp\nb.esp\n")?;
+
+        let mut parser = parser::new_tes3_parser();
+        parser.parse(&rules_dir)?;
+
+        // a -> b is removed, the duplicate a -> c is only kept once
+        assert_eq!(
+            vec![
+                ("a.esp".to_owned(), "c.esp".to_owned()),
+                ("b.esp".into(), "c.esp".into()),
+                ("d.esp".into(), "e.esp".into()),
+            ],
+            get_ordering_from_order_rules(&parser.order_rules)
+        );
+        assert!(!parser
+            .order_rules
+            .iter()
+            .any(|r| matches!(r, rules::EOrderRule::Remove(_))));
+
+        Ok(())
+    }
+
     #[test]
     fn test_gather_mods() {
         init();
@@ -565,6 +734,549 @@ mod integration_tests {
         )
     }
 
+    #[test]
+    fn test_gather_mods_modlist() {
+        init();
+
+        let archive_path = PathBuf::from("tmp")
+            .join("cp77_modlist")
+            .join("archive")
+            .join("pc")
+            .join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["a.archive", "b.archive", "c.archive", "d.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        // missing.archive is listed but not on disk, b.archive is on disk but not listed
+        std::fs::write(
+            archive_path.join("modlist.txt"),
+            "d.archive\nmissing.archive\nc.archive\na.archive\n",
+        )
+        .expect("could not write file");
+
+        let mods = gather_mods(
+            &PathBuf::from("tmp").join("cp77_modlist"),
+            ESupportedGame::Cyberpunk,
+            &None,
+        );
+        assert_eq!(
+            mods.iter().map(|s| s.name.to_owned()).collect::<Vec<_>>(),
+            vec![
+                "d.archive".to_owned(),
+                "c.archive".into(),
+                "a.archive".into(),
+                "b.archive".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_load_order() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("cp77_write_order");
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["a.archive", "b.archive", "c.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        std::fs::write(archive_path.join("modlist.txt"), "a.archive\n")
+            .expect("could not write file");
+
+        let order_rules = vec![
+            rules::Order::from("c.archive", "b.archive").into(),
+            rules::Order::from("b.archive", "a.archive").into(),
+        ];
+        let mods = gather_mods(&root_path, ESupportedGame::Cyberpunk, &None);
+        let result = new_stable_sorter()
+            .topo_sort(ESupportedGame::Cyberpunk, &mods, &order_rules, &[])
+            .expect("rules contain a cycle");
+        assert_eq!(vec!["c.archive", "b.archive", "a.archive"], result);
+
+        write_load_order(&root_path, ESupportedGame::Cyberpunk, &result)
+            .expect("could not write load order");
+
+        // the old modlist is backed up, writing again in the same second keeps both backups
+        write_load_order(&root_path, ESupportedGame::Cyberpunk, &result)
+            .expect("could not write load order");
+        assert!(
+            std::fs::read_dir(&archive_path)
+                .expect("could not read dir")
+                .filter_map(Result::ok)
+                .filter(|e| e.file_name().to_string_lossy().ends_with(".bak"))
+                .count()
+                >= 2
+        );
+
+        let mods = gather_mods(&root_path, ESupportedGame::Cyberpunk, &None);
+        assert_eq!(
+            result,
+            mods.iter().map(|s| s.name.to_owned()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_gather_openmw_mods() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("openmw_gather");
+        let data1 = root_path.join("data1");
+        let data2 = root_path.join("data2");
+        create_dir_all(&data1).expect("could not create dir");
+        create_dir_all(&data2).expect("could not create dir");
+        write_tes3_plugin(&data1.join("a.esp"), "a", &[]);
+        write_tes3_plugin(&data2.join("b.esp"), "b", &[]);
+        write_tes3_plugin(&data2.join("c.esp"), "c", &[]);
+
+        let data1 = data1.canonicalize().unwrap();
+        let data2 = data2.canonicalize().unwrap();
+        let cfg = format!(
+            "# a comment\ndata=\"{}\"\ndata-local=\"{}\"\ncontent=c.esp\n#content=b.esp\ncontent=a.esp\ncontent=missing.esp\ncontent=b.esp\n",
+            data1.display(),
+            data2.display()
+        );
+        let cfg_path = root_path.join("openmw.cfg");
+        std::fs::write(&cfg_path, cfg).expect("could not write file");
+
+        let mods = gather_openmw_mods_from_cfg(&cfg_path);
+        assert_eq!(
+            mods.iter().map(|s| s.name.to_owned()).collect::<Vec<_>>(),
+            vec!["c.esp".to_owned(), "a.esp".into(), "b.esp".into()]
+        );
+        assert_eq!(Some("a".to_owned()), mods[1].description);
+    }
+
+    #[test]
+    fn test_openmw_master_edges() {
+        init();
+
+        // omwaddon and omwgame files use the tes3 header format
+        let root_path = PathBuf::from("tmp").join("openmw_masters");
+        let data = root_path.join("data");
+        create_dir_all(&data).expect("could not create dir");
+        write_tes3_plugin(&data.join("Addon.omwaddon"), "", &[("Game.omwgame", 0)]);
+        write_tes3_plugin(&data.join("Game.omwgame"), "", &[]);
+
+        let data = data.canonicalize().unwrap();
+        let cfg = format!(
+            "data=\"{}\"\ncontent=Addon.omwaddon\ncontent=Game.omwgame\n",
+            data.display()
+        );
+        let cfg_path = root_path.join("openmw.cfg");
+        std::fs::write(&cfg_path, cfg).expect("could not write file");
+
+        let mods = gather_openmw_mods_from_cfg(&cfg_path);
+        assert_eq!(Some(vec![("Game.omwgame".to_owned(), 0)]), mods[0].masters);
+
+        // the addon's master is an order edge without any rules
+        let order_rules = vec![rules::Order::from("x.esp", "y.esp").into()];
+        let data = get_graph_data(&mods, &order_rules, &[]);
+        assert_eq!(vec![(1, 0)], data.edges);
+    }
+
+    #[test]
+    fn test_gather_skyrim_mods() {
+        init();
+
+        // only plugins marked with * are enabled
+        assert_eq!(
+            vec!["a.esm".to_owned(), "c.esl".into()],
+            parse_plugins_txt("# comment\n*a.esm\nb.esp\n\n*c.esl\n")
+        );
+        // without any marker all plugins are enabled
+        assert_eq!(
+            vec!["a.esm".to_owned(), "b.esp".into()],
+            parse_plugins_txt("a.esm\nb.esp\n")
+        );
+
+        let root_path = PathBuf::from("tmp").join("skyrim_gather");
+        let data = root_path.join("Data");
+        create_dir_all(&data).expect("could not create dir");
+        for name in ["a.esm", "b.esp", "c.esl"] {
+            std::fs::write(data.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        std::fs::write(
+            root_path.join("plugins.txt"),
+            "*c.esl\n*a.esm\nb.esp\n*missing.esp\n",
+        )
+        .expect("could not write file");
+
+        let mods = gather_mods(&root_path, ESupportedGame::Skyrim, &None);
+        assert_eq!(
+            mods.iter().map(|s| s.name.to_owned()).collect::<Vec<_>>(),
+            vec!["c.esl".to_owned(), "a.esm".into()]
+        );
+
+        // writing the order keeps the disabled and missing entries
+        write_load_order(
+            &root_path,
+            ESupportedGame::Skyrim,
+            &["a.esm".to_owned(), "new.esp".into(), "c.esl".into()],
+        )
+        .expect("could not write load order");
+        assert_eq!(
+            "*a.esm\n*new.esp\nb.esp\n*missing.esp\n*c.esl\n",
+            std::fs::read_to_string(root_path.join("plugins.txt")).expect("could not read file")
+        );
+    }
+
+    #[test]
+    fn test_check_json() {
+        init();
+
+        let rules_dir = PathBuf::from("tests").join("plox").join("check");
+        let install = |name: &str, archives: &[&str]| {
+            let root_path = PathBuf::from("tmp").join(name);
+            let archive_path = root_path.join("archive").join("pc").join("mod");
+            let _ = std::fs::remove_dir_all(&root_path);
+            create_dir_all(&archive_path).expect("could not create dir");
+            for archive in archives {
+                std::fs::write(archive_path.join(archive), [0_u8; 4])
+                    .expect("could not write file");
+            }
+            root_path
+        };
+
+        // a clean load order has no warnings
+        let root_path = install("check_clean", &["a.archive", "c.archive", "d.archive"]);
+        let warnings = check_mods(ESupportedGame::Cyberpunk, &root_path, &rules_dir)
+            .expect("could not check mods");
+        assert_eq!(
+            serde_json::json!([]),
+            serde_json::to_value(&warnings).expect("could not serialize")
+        );
+        assert_eq!(
+            std::process::ExitCode::SUCCESS,
+            check(
+                ESupportedGame::Cyberpunk,
+                &Some(root_path),
+                &Some(rules_dir.to_string_lossy().to_string()),
+                EOutputFormat::Json,
+                ESeverity::Warning
+            )
+        );
+
+        // conflicting and missing mods
+        let root_path = install("check_warnings", &["a.archive", "b.archive", "c.archive"]);
+        let warnings = check_mods(ESupportedGame::Cyberpunk, &root_path, &rules_dir)
+            .expect("could not check mods");
+        assert_eq!(
+            serde_json::json!([
+                {
+                    "kind": "Conflict",
+                    "comment": "a and b overwrite the same files",
+                    "plugins": ["a.archive", "b.archive"],
+                    "severity": "warning"
+                },
+                {
+                    "kind": "Requires",
+                    "comment": "c needs d",
+                    "plugins": ["c.archive"],
+                    "severity": "warning",
+                    "missing": ["d.archive"]
+                }
+            ]),
+            serde_json::to_value(&warnings).expect("could not serialize")
+        );
+        assert_eq!(
+            std::process::ExitCode::FAILURE,
+            check(
+                ESupportedGame::Cyberpunk,
+                &Some(root_path),
+                &Some(rules_dir.to_string_lossy().to_string()),
+                EOutputFormat::Json,
+                ESeverity::Warning
+            )
+        );
+
+        // only errors fail with --fail-on error
+        assert_eq!(
+            std::process::ExitCode::SUCCESS,
+            check_exit_code(&warnings, ESeverity::Error)
+        );
+        let mut errors = warnings.clone();
+        errors[0].severity = ESeverity::Error;
+        assert_eq!(
+            std::process::ExitCode::FAILURE,
+            check_exit_code(&errors, ESeverity::Error)
+        );
+    }
+
+    #[test]
+    fn test_sort_mods() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("sort_mods");
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        let _ = std::fs::remove_dir_all(&root_path);
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["final.archive", "patch.archive", "base.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+
+        let rules_dir = PathBuf::from("tests").join("plox").join("sort");
+        let (_, proposed) = gather_and_sort(ESupportedGame::Cyberpunk, &root_path, &rules_dir)
+            .expect("could not sort");
+        assert_eq!(
+            vec!["base.archive", "patch.archive", "final.archive"],
+            proposed
+        );
+        assert_eq!(
+            std::process::ExitCode::SUCCESS,
+            sort_mods(
+                &Some(root_path.clone()),
+                ESupportedGame::Cyberpunk,
+                &rules_dir
+            )
+        );
+
+        // a cycle in the rules fails
+        let rules_dir = PathBuf::from("tests").join("plox").join("sort_cycle");
+        assert_eq!(
+            std::process::ExitCode::FAILURE,
+            sort_mods(&Some(root_path), ESupportedGame::Cyberpunk, &rules_dir)
+        );
+    }
+
+    #[test]
+    fn test_resolve_rules_dir() {
+        init();
+
+        let rules_dir = PathBuf::from("tmp").join("resolve_rules").join("rules");
+        create_dir_all(&rules_dir).expect("could not create dir");
+        let expected = std::fs::canonicalize(&rules_dir).expect("could not canonicalize");
+
+        // relative to the current working dir
+        assert_eq!(
+            expected,
+            resolve_rules_dir("tmp/resolve_rules/rules").expect("could not resolve")
+        );
+
+        // environment variables in all notations
+        std::env::set_var("PLOX_TEST_RULES_ROOT", "tmp/resolve_rules");
+        for input in [
+            "$PLOX_TEST_RULES_ROOT/rules",
+            "${PLOX_TEST_RULES_ROOT}/rules",
+            "%PLOX_TEST_RULES_ROOT%/rules",
+        ] {
+            assert_eq!(
+                expected,
+                resolve_rules_dir(input).expect("could not resolve")
+            );
+        }
+
+        // ~ is the home dir
+        if let Ok(home) = std::env::var("HOME") {
+            assert_eq!(
+                std::fs::canonicalize(&home).expect("could not canonicalize"),
+                resolve_rules_dir("~").expect("could not resolve")
+            );
+        }
+
+        // missing paths and variables are errors
+        let err = resolve_rules_dir("tmp/resolve_rules/missing").unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+        assert!(err.to_string().contains("does not exist"));
+        let err = resolve_rules_dir("$PLOX_TEST_RULES_UNSET/rules").unwrap_err();
+        assert!(err.to_string().contains("PLOX_TEST_RULES_UNSET"));
+    }
+
+    #[test]
+    fn test_detect_game() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("detect_game");
+        let cases = [
+            (
+                "cyberpunk",
+                PathBuf::from("archive").join("pc").join("mod"),
+                true,
+            ),
+            ("morrowind_ini", PathBuf::from("Morrowind.ini"), false),
+            ("morrowind_data", PathBuf::from("Data Files"), true),
+            ("openmw", PathBuf::from("openmw.cfg"), false),
+        ];
+        for (dir, signature, is_dir) in &cases {
+            let game_path = root_path.join(dir);
+            let _ = std::fs::remove_dir_all(&game_path);
+            create_dir_all(&game_path).expect("could not create dir");
+            if *is_dir {
+                create_dir_all(game_path.join(signature)).expect("could not create dir");
+            } else {
+                std::fs::write(game_path.join(signature), "").expect("could not write file");
+            }
+        }
+
+        assert_eq!(
+            Some(ESupportedGame::Cyberpunk),
+            detect_game(&root_path.join("cyberpunk"))
+        );
+        assert_eq!(
+            Some(ESupportedGame::Morrowind),
+            detect_game(&root_path.join("morrowind_ini"))
+        );
+        assert_eq!(
+            Some(ESupportedGame::Morrowind),
+            detect_game(&root_path.join("morrowind_data"))
+        );
+        assert_eq!(
+            Some(ESupportedGame::Openmw),
+            detect_game(&root_path.join("openmw"))
+        );
+
+        // an empty directory is not a game
+        let empty_path = root_path.join("empty");
+        create_dir_all(&empty_path).expect("could not create dir");
+        assert_eq!(None, detect_game(&empty_path));
+    }
+
+    #[test]
+    fn test_gather_mods_with_depth() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("cp77_depth");
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        let nested_path = archive_path.join("nested");
+        let _ = std::fs::remove_dir_all(&root_path);
+        create_dir_all(nested_path.join("deeper")).expect("could not create dir");
+        std::fs::write(archive_path.join("a.archive"), [0_u8; 4]).expect("could not write file");
+        std::fs::write(nested_path.join("b.archive"), [0_u8; 4]).expect("could not write file");
+        std::fs::write(nested_path.join("readme.txt"), "").expect("could not write file");
+        std::fs::write(nested_path.join("deeper").join("c.archive"), [0_u8; 4])
+            .expect("could not write file");
+
+        let names = |depth: usize| {
+            gather_mods_with_depth(&root_path, ESupportedGame::Cyberpunk, &None, depth)
+                .into_iter()
+                .map(|m| m.name)
+                .collect::<Vec<_>>()
+        };
+
+        // the default depth only reads the mod folder
+        assert_eq!(
+            vec!["a.archive".to_owned()],
+            gather_mods(&root_path, ESupportedGame::Cyberpunk, &None)
+                .into_iter()
+                .map(|m| m.name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["a.archive"], names(1));
+        assert_eq!(vec!["a.archive", "nested/b.archive"], names(2));
+        assert_eq!(
+            vec!["a.archive", "nested/b.archive", "nested/deeper/c.archive"],
+            names(3)
+        );
+    }
+
+    #[test]
+    fn test_size_expr_on_disk() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("cp77_size");
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        std::fs::write(archive_path.join("x.archive"), [0_u8; 8]).expect("could not write file");
+
+        let mods = gather_mods(&root_path, ESupportedGame::Cyberpunk, &None);
+        let parser = parser::new_cyberpunk_parser();
+        for (input, expected) in [
+            ("[size 8 x.archive]", Some(vec!["x.archive".to_owned()])),
+            ("[size 9 x.archive]", None),
+            ("[size !9 x.archive]", Some(vec!["x.archive".to_owned()])),
+        ] {
+            let expr = parser
+                .parse_expression(input, true)
+                .expect("could not parse expression");
+            assert_eq!(
+                expected,
+                expressions::TExpression::eval_plugins(&expr, &mods)
+            );
+        }
+    }
+
+    #[test]
+    fn test_gather_redmods() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("cp77_redmods");
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["b.archive", "a.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+
+        let redmod_path = root_path.join("mods").join("redmod_a");
+        create_dir_all(redmod_path.join("archives")).expect("could not create dir");
+        std::fs::write(
+            redmod_path.join("info.json"),
+            r#"{ "name": "redmod_a", "version": "1.2.0", "description": "a redmod" }"#,
+        )
+        .expect("could not write file");
+        std::fs::write(redmod_path.join("archives").join("x.archive"), [0_u8; 8])
+            .expect("could not write file");
+
+        let mods = gather_mods(&root_path, ESupportedGame::Cyberpunk, &None);
+        assert_eq!(
+            mods.iter().map(|s| s.name.to_owned()).collect::<Vec<_>>(),
+            vec![
+                "a.archive".to_owned(),
+                "b.archive".into(),
+                "redmod_a".into()
+            ]
+        );
+
+        let redmod = mods.last().unwrap();
+        assert_eq!(8, redmod.size);
+        assert_eq!(Some("a redmod".to_owned()), redmod.description);
+        assert_eq!(Some(Version::new(1, 2, 0)), redmod.version);
+    }
+
+    #[test]
+    fn test_gather_duplicate_names() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("cp77_duplicates");
+        let _ = std::fs::remove_dir_all(&root_path);
+        let archive_path = root_path.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        std::fs::write(archive_path.join("x.archive"), [0_u8; 4]).expect("could not write file");
+
+        // two REDmods with the same archive, and a REDmod folder named like a loose archive
+        for folder in ["redmod_a", "redmod_b", "x.archive"] {
+            let archives = root_path.join("mods").join(folder).join("archives");
+            create_dir_all(&archives).expect("could not create dir");
+            std::fs::write(archives.join("shared.archive"), [0_u8; 4])
+                .expect("could not write file");
+        }
+
+        let mods = gather_mods(&root_path, ESupportedGame::Cyberpunk, &None);
+        assert_eq!(
+            vec!["x.archive", "redmod_a", "redmod_b", "mods/x.archive"],
+            mods.iter().map(|m| m.name.as_str()).collect::<Vec<_>>()
+        );
+        assert!(find_duplicate_mods(&mods).is_empty());
+
+        // the qualified name still points to the REDmod folder
+        let detailed = gather_mods_detailed(&root_path, ESupportedGame::Cyberpunk);
+        assert_eq!(EModKind::Redmod, detailed[3].kind);
+        assert_eq!(root_path.join("mods").join("x.archive"), detailed[3].path);
+
+        // only the loose archive is written to the modlist, not the REDmods named like archives
+        let order = mods.iter().map(|m| m.name.clone()).collect::<Vec<_>>();
+        write_load_order(&root_path, ESupportedGame::Cyberpunk, &order)
+            .expect("could not write load order");
+        assert_eq!(
+            "x.archive\n",
+            std::fs::read_to_string(archive_path.join("modlist.txt"))
+                .expect("could not read modlist")
+        );
+
+        // duplicates are reported case-insensitively
+        let plugins =
+            ["a.esp", "B.esp", "A.ESP", "b.esp", "a.esp"].map(|n| PluginData::new(n.to_owned(), 0));
+        assert_eq!(vec!["a.esp", "b.esp"], find_duplicate_mods(&plugins));
+    }
+
     #[test]
     fn test_parse_header() {
         init();
@@ -614,4 +1326,590 @@ mod integration_tests {
             assert_eq!(got.unwrap(), expected);
         }
     }
+
+    #[test]
+    fn test_read_tes3_description() {
+        init();
+
+        create_dir_all("tmp").expect("could not create dir");
+        let plugin_path = PathBuf::from("tmp").join("handcrafted.esp");
+        write_tes3_plugin(&plugin_path, "A handcrafted plugin v1.2", &[]);
+
+        let description = read_tes3_description(&plugin_path).expect("failed to read description");
+        assert_eq!(description, "A handcrafted plugin v1.2");
+
+        // not a plugin
+        let text_path = PathBuf::from("tmp").join("handcrafted.txt");
+        std::fs::write(&text_path, "not a plugin").expect("could not write file");
+        assert!(read_tes3_description(&text_path).is_err());
+    }
+
+    #[test]
+    fn test_master_edges() {
+        init();
+
+        let root_path = PathBuf::from("tmp").join("masters");
+        create_dir_all(&root_path).expect("could not create dir");
+        let master_path = root_path.join("Master.esm");
+        let plugin_path = root_path.join("Dependent.esp");
+        write_tes3_plugin(&master_path, "", &[]);
+        write_tes3_plugin(&plugin_path, "", &[("Master.esm", 0)]);
+
+        assert_eq!(
+            vec!["Master.esm".to_owned()],
+            read_tes3_masters(&plugin_path).expect("failed to read masters")
+        );
+        assert!(read_tes3_masters(&master_path)
+            .expect("failed to read masters")
+            .is_empty());
+
+        // the dependent plugin is listed first and there are no rules for it
+        let mut dependent = PluginData::new("Dependent.esp".to_owned(), 0);
+        dependent.masters = parse_header(&plugin_path)
+            .expect("failed to parse header")
+            .masters;
+        let mods = vec![dependent, PluginData::new("Master.esm".to_owned(), 0)];
+
+        // masters in the header are order edges even without rules
+        let order_rules = vec![rules::Order::from("x.esp", "y.esp").into()];
+        let data = get_graph_data(&mods, &order_rules, &[]);
+        assert_eq!(vec![(1, 0)], data.edges);
+
+        // the cyberpunk sort has no masters-first pass, only the header edge moves the master
+        let result = new_stable_sorter()
+            .topo_sort(ESupportedGame::Cyberpunk, &mods, &order_rules, &[])
+            .expect("could not sort");
+        assert_eq!(vec!["Master.esm", "Dependent.esp"], result);
+    }
+
+    #[test]
+    fn test_bundled_fallback() {
+        init();
+
+        let rules_dir = PathBuf::from("tmp").join("bundled_fallback");
+        create_dir_all(&rules_dir).expect("could not create dir");
+
+        // no rules files and no fallback: no rules
+        let mut parser = new_tes3_parser();
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        assert!(parser.order_rules.is_empty());
+
+        // no rules files with the fallback on: the bundled rules are loaded
+        let mut parser = new_tes3_parser().with_bundled_fallback(true);
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        let expected = parser
+            .parse_rules_from_reader(std::io::Cursor::new(
+                bundled_rules(ESupportedGame::Morrowind)
+                    .expect("no bundled rules")
+                    .as_bytes(),
+            ))
+            .expect("bundled rules parsing failed")
+            .len();
+        assert!(expected > 0);
+        assert_eq!(
+            expected,
+            parser.order_rules.len() + parser.warning_rules.len()
+        );
+
+        // rules files on disk take precedence over the bundled rules
+        std::fs::copy(
+            "tests/plox/rules_order.txt",
+            rules_dir.join("mlox_user.txt"),
+        )
+        .expect("could not copy rules");
+        let mut parser = new_tes3_parser().with_bundled_fallback(true);
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        assert!(!parser.order_rules.iter().any(
+            |r| matches!(r, EOrderRule::Order(o) if o.names.contains(&"tribunal.esm".to_owned()))
+        ));
+    }
+
+    #[test]
+    fn test_detect_game_version() {
+        init();
+
+        let root = PathBuf::from("tmp").join("detect_game_version");
+        create_dir_all(root.join("resources")).expect("could not create dir");
+
+        assert_eq!(None, detect_game_version(&root, ESupportedGame::Openmw));
+
+        std::fs::write(root.join("resources").join("version"), "0.48.0\nabcdef\n")
+            .expect("could not write version");
+        assert_eq!(
+            Some("0.48.0".to_owned()),
+            detect_game_version(&root, ESupportedGame::Openmw)
+        );
+        assert_eq!(None, detect_game_version(&root, ESupportedGame::Cyberpunk));
+
+        // Morrowind.exe is a 32-bit executable
+        assert_eq!(
+            Some("1.6.0.1820".to_owned()),
+            detect_game_version(&PathBuf::from("tests"), ESupportedGame::Morrowind)
+        );
+    }
+
+    #[test]
+    fn test_rule_source() {
+        init();
+
+        let dir = PathBuf::from("tmp").join("rule_source");
+        create_dir_all(&dir).expect("could not create dir");
+        let path = dir.join("mlox_user.txt");
+        std::fs::write(
+            &path,
+            "; rules\n[Order]\na.esp\nb.esp\n\n[Note]\n  message\nc.esp\n",
+        )
+        .expect("could not write rules");
+
+        let mut parser = new_tes3_parser();
+        parser.init_from_file(&path).expect("rules parsing failed");
+
+        let order = parser.order_rules[0].source().expect("no source");
+        assert_eq!(Some(path.clone()), order.file);
+        assert_eq!(2, order.line);
+
+        let note = parser.warning_rules[0].source().expect("no source");
+        assert_eq!(Some(path.clone()), note.file);
+        assert_eq!(6, note.line);
+
+        // the source is not serialized
+        let json = parser.export_rules_json().expect("could not export");
+        assert!(!json.contains("source"));
+    }
+
+    #[test]
+    fn test_gather_mods_disabled() {
+        init();
+
+        let root = PathBuf::from("tmp").join("cp77_disabled");
+        let archive_path = root.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["a.archive", "b.archive", "c.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        let names = |mods: Vec<PluginData>| mods.into_iter().map(|m| m.name).collect::<Vec<_>>();
+
+        // an explicit exclude list
+        assert_eq!(
+            vec!["a.archive".to_owned(), "c.archive".into()],
+            names(gather_mods_excluding(
+                &root,
+                ESupportedGame::Cyberpunk,
+                &None,
+                &["B.archive".into()]
+            ))
+        );
+
+        // disabled.txt in the game root
+        std::fs::write(
+            root.join(DISABLED_PLUGINS_FILE),
+            "; disabled\nc.archive\n\n",
+        )
+        .expect("could not write file");
+        assert_eq!(vec!["c.archive".to_owned()], read_disabled_plugins(&root));
+        assert_eq!(
+            vec!["a.archive".to_owned(), "b.archive".into()],
+            names(gather_mods(&root, ESupportedGame::Cyberpunk, &None))
+        );
+    }
+
+    #[test]
+    fn test_sort_disabled() {
+        init();
+
+        let root = PathBuf::from("tmp").join("sort_disabled");
+        let archive_path = root.join("archive").join("pc").join("mod");
+        let rules_dir = root.join("rules");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&archive_path).expect("could not create dir");
+        create_dir_all(&rules_dir).expect("could not create dir");
+        for name in ["a.archive", "b.archive", "c.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        // the rules contradict each other, but only if c is sorted
+        std::fs::write(
+            rules_dir.join("plox_base.txt"),
+            "[Order]\na.archive\nc.archive\n\n[Order]\nc.archive\na.archive\n",
+        )
+        .expect("could not write file");
+
+        let options = || CliSortOptions {
+            game: ESupportedGame::Cyberpunk,
+            game_folder: Some(root.clone()),
+            rules_dir: Some(rules_dir.to_string_lossy().into_owned()),
+            mod_list: None,
+            dry_run: true,
+            unstable: true,
+            no_download: true,
+        };
+        assert_eq!(std::process::ExitCode::FAILURE, sort(options()));
+
+        // disabled plugins are not sorted
+        std::fs::write(root.join(DISABLED_PLUGINS_FILE), "c.archive\n")
+            .expect("could not write file");
+        assert_eq!(std::process::ExitCode::SUCCESS, sort(options()));
+    }
+
+    #[test]
+    fn test_sort_writes_game_folder() {
+        init();
+
+        let root = PathBuf::from("tmp").join("sort_game_folder");
+        let archive_path = root.join("archive").join("pc").join("mod");
+        let rules_dir = root.join("rules");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&archive_path).expect("could not create dir");
+        create_dir_all(&rules_dir).expect("could not create dir");
+        for name in ["a.archive", "b.archive"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        std::fs::write(
+            rules_dir.join("plox_base.txt"),
+            "[Order]\nb.archive\na.archive\n",
+        )
+        .expect("could not write file");
+
+        // the modlist is written to the game folder, not the current directory
+        let options = CliSortOptions {
+            game: ESupportedGame::Cyberpunk,
+            game_folder: Some(root.clone()),
+            rules_dir: Some(rules_dir.to_string_lossy().into_owned()),
+            mod_list: None,
+            dry_run: false,
+            unstable: true,
+            no_download: true,
+        };
+        assert_eq!(std::process::ExitCode::SUCCESS, sort(options));
+        assert_eq!(
+            "b.archive\na.archive\n",
+            std::fs::read_to_string(archive_path.join("modlist.txt"))
+                .expect("could not read modlist")
+        );
+    }
+
+    #[test]
+    fn test_gather_mods_disabled_suffix() {
+        init();
+
+        let names = |mods: Vec<PluginData>| mods.into_iter().map(|m| m.name).collect::<Vec<_>>();
+
+        // mod managers disable files by appending .disabled
+        let root = PathBuf::from("tmp").join("cp77_disabled_suffix");
+        let archive_path = root.join("archive").join("pc").join("mod");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in [
+            "foo.archive",
+            "foo.archive.disabled",
+            "foo.disabled",
+            "foo.xarchive",
+        ] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        assert_eq!(
+            vec!["foo.archive".to_owned()],
+            names(gather_mods(&root, ESupportedGame::Cyberpunk, &None))
+        );
+
+        let root = PathBuf::from("tmp").join("tes3_disabled_suffix");
+        let data_path = root.join("Data Files");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&data_path).expect("could not create dir");
+        for name in ["foo.esp", "foo.esp.disabled", "foo.disabled"] {
+            std::fs::write(data_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        assert_eq!(
+            vec!["foo.esp".to_owned()],
+            names(gather_mods(&root, ESupportedGame::Morrowind, &None))
+        );
+    }
+
+    #[test]
+    fn test_order_groups() {
+        init();
+
+        let dir = PathBuf::from("tmp").join("order_groups");
+        create_dir_all(&dir).expect("could not create dir");
+        // groups may be defined in a different rules file than where they are used
+        std::fs::write(
+            dir.join("mlox_base.txt"),
+            "[Group textures]\na.esp\nb.esp\n\n[Group all]\n@textures\nc.esp\n",
+        )
+        .expect("could not write rules");
+        std::fs::write(
+            dir.join("mlox_user.txt"),
+            "[Order]\nx.esp\n@textures\n\n[NearEnd] @all\n[Order] @missing y.esp z.esp\n",
+        )
+        .expect("could not write rules");
+
+        let mut parser = new_tes3_parser();
+        parser.parse(&dir).expect("rules parsing failed");
+        assert_eq!(2, parser.groups.len());
+
+        let pairs = get_ordering_from_order_rules(&parser.order_rules);
+        for pair in [("x.esp", "a.esp"), ("x.esp", "b.esp"), ("a.esp", "b.esp")] {
+            assert!(
+                pairs.contains(&(pair.0.to_owned(), pair.1.to_owned())),
+                "{:?}",
+                pair
+            );
+        }
+        // unknown groups are dropped
+        assert!(pairs.contains(&("y.esp".to_owned(), "z.esp".to_owned())));
+        assert!(!pairs
+            .iter()
+            .any(|(a, b)| a.starts_with('@') || b.starts_with('@')));
+
+        // nested groups expand to all members
+        let nearend = parser
+            .order_rules
+            .iter()
+            .find_map(nearend2)
+            .expect("no nearend rule");
+        assert_eq!(vec!["a.esp", "b.esp", "c.esp"], nearend.names);
+    }
+
+    #[test]
+    fn test_watch_rules() {
+        init();
+
+        let dir = PathBuf::from("tmp").join("watch_rules");
+        create_dir_all(&dir).expect("could not create dir");
+        let path = dir.join("mlox_user.txt");
+        std::fs::write(&path, "[Order]\na.esp\nb.esp\n").expect("could not write rules");
+
+        // break the rules after the watcher started, it validates once more and returns.
+        // the new contents are renamed into place so the watcher never sees a truncated file
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            let tmp_path = writer_path.with_extension("tmp");
+            std::fs::write(&tmp_path, "[Order]\na.esp\n").expect("could not write rules");
+            std::fs::rename(&tmp_path, &writer_path).expect("could not rename rules");
+        });
+
+        let result = commands::watch_rules_for(
+            &path,
+            ESupportedGame::Morrowind,
+            std::time::Duration::from_millis(20),
+            Some(1),
+        );
+        writer.join().expect("writer failed");
+
+        // the re-parse saw the malformed order rule
+        assert_eq!(std::process::ExitCode::FAILURE, result);
+
+        // an atomic save removes the file and creates it again, only the new file is validated
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::fs::remove_file(&writer_path).expect("could not remove rules");
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            let tmp_path = writer_path.with_extension("tmp");
+            std::fs::write(&tmp_path, "[Order]\na.esp\nb.esp\n").expect("could not write rules");
+            std::fs::rename(&tmp_path, &writer_path).expect("could not rename rules");
+        });
+
+        let result = commands::watch_rules_for(
+            &path,
+            ESupportedGame::Morrowind,
+            std::time::Duration::from_millis(20),
+            Some(1),
+        );
+        writer.join().expect("writer failed");
+        assert_eq!(std::process::ExitCode::SUCCESS, result);
+    }
+
+    #[test]
+    fn test_gather_mods_detailed() {
+        init();
+
+        // archives, a REDmod and a file that isn't a mod
+        let root = PathBuf::from("tmp").join("cp77_detailed");
+        let archive_path = root.join("archive").join("pc").join("mod");
+        create_dir_all(&archive_path).expect("could not create dir");
+        for name in ["a.archive", "b.ARCHIVE", "readme.txt"] {
+            std::fs::write(archive_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+        create_dir_all(root.join("mods").join("redmod_a").join("archives"))
+            .expect("could not create dir");
+
+        let mods = gather_mods_detailed(&root, ESupportedGame::Cyberpunk);
+        assert_eq!(
+            vec![
+                ModEntry {
+                    name: "a.archive".into(),
+                    index: 0,
+                    path: archive_path.join("a.archive"),
+                    kind: EModKind::Archive,
+                },
+                ModEntry {
+                    name: "b.ARCHIVE".into(),
+                    index: 1,
+                    path: archive_path.join("b.ARCHIVE"),
+                    kind: EModKind::Archive,
+                },
+                ModEntry {
+                    name: "redmod_a".into(),
+                    index: 2,
+                    path: root.join("mods").join("redmod_a"),
+                    kind: EModKind::Redmod,
+                },
+            ],
+            mods
+        );
+
+        // esm and esp content files
+        let root = PathBuf::from("tmp").join("tes3_detailed");
+        let data_path = root.join("Data Files");
+        create_dir_all(&data_path).expect("could not create dir");
+        for name in ["a.esm", "b.esp"] {
+            std::fs::write(data_path.join(name), [0_u8; 4]).expect("could not write file");
+        }
+
+        let mods = gather_mods_detailed(&root, ESupportedGame::Morrowind);
+        assert_eq!(vec![0, 1], mods.iter().map(|m| m.index).collect::<Vec<_>>());
+        assert!(mods.iter().all(|m| m.kind == EModKind::Plugin));
+        assert_eq!(data_path.join("a.esm"), mods[0].path);
+
+        let json = serde_json::to_string(&mods[0]).expect("could not serialize");
+        assert!(json.contains("\"kind\":\"plugin\""));
+    }
+
+    #[test]
+    fn test_custom_rules_files() {
+        init();
+
+        let rules_dir = PathBuf::from("tmp").join("custom_rules_files");
+        create_dir_all(&rules_dir).expect("could not create dir");
+        std::fs::write(rules_dir.join("my_order.txt"), "[Order]\na.esp\nb.esp\n")
+            .expect("could not write file");
+        std::fs::write(rules_dir.join("mlox_base.txt"), "[Note]\n message\na.esp\n")
+            .expect("could not write file");
+
+        // the defaults read mlox_base.txt only
+        let mut parser = new_tes3_parser();
+        assert_eq!(
+            default_rules_files(ESupportedGame::Morrowind),
+            parser.rules_files
+        );
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        assert!(parser.order_rules.is_empty());
+        assert!(!parser.warning_rules.is_empty());
+
+        // custom names read exactly those files
+        let mut parser = new_tes3_parser();
+        parser.set_rules_files(vec!["my_order.txt".into()]);
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        assert!(!parser.order_rules.is_empty());
+        assert!(parser.warning_rules.is_empty());
+    }
+
+    #[test]
+    fn test_rules_hash() {
+        init();
+
+        let rules_dir = PathBuf::from("tmp").join("rules_hash");
+        let _ = std::fs::remove_dir_all(&rules_dir);
+        create_dir_all(&rules_dir).expect("could not create dir");
+        let hash = |base: &str| {
+            std::fs::write(rules_dir.join("mlox_base.txt"), base).expect("could not write file");
+            let mut parser = new_tes3_parser();
+            parser.parse(&rules_dir).expect("rules parsing failed");
+            parser.rules_hash()
+        };
+
+        // identical content hashes the same, also when parsing again
+        let first = hash("[Order]\na.esp\nb.esp\n");
+        assert_eq!(16, first.len());
+        assert_eq!(first, hash("[Order]\na.esp\nb.esp\n"));
+        let mut parser = new_tes3_parser();
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        parser.parse(&rules_dir).expect("rules parsing failed");
+        assert_eq!(first, parser.rules_hash());
+
+        // any change to the raw content changes the hash, even if the rules are the same
+        assert_ne!(first, hash("[Order]\nb.esp\na.esp\n"));
+        assert_ne!(first, hash("[Order]\na.esp\nb.esp\n; comment\n"));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_parse_rules_from_url() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        init();
+
+        // a mock server that answers each request with the next response
+        let serve = |responses: Vec<(&'static str, &'static str)>| {
+            let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind");
+            let url = format!("http://{}/mlox_base.txt", listener.local_addr().unwrap());
+            let handle = std::thread::spawn(move || {
+                for (status, body) in responses {
+                    let (mut stream, _) = listener.accept().expect("could not accept");
+                    // read the request headers
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                        line.clear();
+                    }
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                }
+            });
+            (url, handle)
+        };
+
+        let cache_dir = PathBuf::from("tmp").join("url_cache");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let parser = new_tes3_parser().with_cache_dir(cache_dir.clone());
+
+        // non-200 responses are errors
+        let (url, handle) = serve(vec![("404 Not Found", "")]);
+        let err = parser
+            .parse_rules_from_url(&url)
+            .expect_err("a missing file should fail");
+        assert!(err.to_string().contains("404"));
+        handle.join().unwrap();
+
+        // fetched rules are parsed and cached
+        let rules = "[Order]\na.esp\nb.esp\n[Note]\nc.esp\n";
+        let (url, handle) = serve(vec![("200 OK", rules)]);
+        assert_eq!(
+            2,
+            parser
+                .parse_rules_from_url(&url)
+                .expect("could not fetch rules")
+                .len()
+        );
+        handle.join().unwrap();
+        assert_eq!(1, std::fs::read_dir(&cache_dir).unwrap().count());
+
+        // the cached copy is used when the server is down
+        assert_eq!(
+            2,
+            parser
+                .parse_rules_from_url(&url)
+                .expect("could not read cached rules")
+                .len()
+        );
+
+        // file urls are read from disk
+        let path = cache_dir.join("local.txt");
+        std::fs::write(&path, rules).expect("could not write file");
+        let url = format!("file://{}", path.display());
+        assert_eq!(
+            2,
+            parser
+                .parse_rules_from_url(&url)
+                .expect("could not read file")
+                .len()
+        );
+    }
 }
@@ -37,10 +37,11 @@ mod scc_tests {
 
         let mut mods_to_remove = vec![];
         let mut warning_rules = warning_rules.to_vec();
+        let context = expressions::EvalContext::from_plugins(plugins);
         for rule in warning_rules.iter_mut() {
             // only conflict rules
             if let EWarningRule::Conflict(ref mut conflict) = rule {
-                if conflict.eval(plugins) {
+                if conflict.eval_context(&context) {
                     // remove mods
                     // switch on the len of conflict.conflicts
                     let groups_size = conflict.conflicts.len();
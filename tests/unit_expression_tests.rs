@@ -19,11 +19,8 @@ mod unit_tests {
         Atomic::from(str).into()
     }
 
-    fn get_mods() -> Vec<PluginData> {
-        [A, B, C, D, E, F]
-            .iter()
-            .map(|e| PluginData::new(e.to_string(), 0))
-            .collect::<Vec<_>>()
+    fn get_mods() -> EvalContext {
+        EvalContext::from_mods(&[A, B, C, D, E, F].map(|e| e.to_string()))
     }
 
     #[test]
@@ -99,6 +96,80 @@ mod unit_tests {
             let expr = NOT::new(e(A));
             assert!(expr.eval(&get_mods()).is_none());
         }
+
+        // [NOT] matches no plugins
+        {
+            let expr = NOT::new(e(X));
+            assert_eq!(Some(vec![]), expr.eval(&get_mods()));
+        }
+    }
+
+    #[test]
+    fn evaluate_matched_plugins() {
+        init();
+
+        // [ALL] returns the union of all expressions
+        {
+            let expr = ALL::new(vec![e(A), e(B), e(A)]);
+            assert_eq!(
+                Some(vec![A.to_owned(), B.to_owned()]),
+                expr.eval(&get_mods())
+            );
+        }
+
+        // [ANY] returns the union of the matching expressions
+        {
+            let expr = ANY::new(vec![e(X), e(B), e(A), e(B)]);
+            assert_eq!(
+                Some(vec![B.to_owned(), A.to_owned()]),
+                expr.eval(&get_mods())
+            );
+        }
+
+        // [NOT] does not add plugins
+        {
+            let expr = ALL::new(vec![e(A), NOT::new(e(X)).into()]);
+            assert_eq!(Some(vec![A.to_owned()]), expr.eval(&get_mods()));
+        }
+    }
+
+    #[test]
+    fn evaluate_atomic_glob() {
+        init();
+
+        let mods = ["patch_a.esp", "patch_b.esp", "patch_a_c.esp"]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
+
+        // literal names match exactly
+        {
+            let expr = Atomic::from("patch_a.esp");
+            assert_eq!(Some(vec!["patch_a.esp".to_owned()]), expr.eval(&context));
+            let expr = Atomic::from("patch_a");
+            assert!(expr.eval(&context).is_none());
+        }
+
+        // wildcards return all matches
+        {
+            let expr = Atomic::from("patch_*.esp");
+            assert_eq!(
+                Some(vec![
+                    "patch_a.esp".to_owned(),
+                    "patch_b.esp".to_owned(),
+                    "patch_a_c.esp".to_owned()
+                ]),
+                expr.eval(&context)
+            );
+            let expr = Atomic::from("patch_?.esp");
+            assert_eq!(
+                Some(vec!["patch_a.esp".to_owned(), "patch_b.esp".to_owned()]),
+                expr.eval(&context)
+            );
+            let expr = Atomic::from("patch_x*.esp");
+            assert!(expr.eval(&context).is_none());
+        }
     }
 
     #[test]
@@ -110,26 +181,57 @@ mod unit_tests {
             .enumerate()
             .map(|(i, e)| PluginData::new(e.to_string(), (i + 1) as u64))
             .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
 
         // [SIZE] is true if the plugin size matches the given size
         {
             let expr = SIZE::new(Atomic::from(A), 1_u64, false);
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [SIZE] is true if the plugin size does not matches the given size and is negated
         {
             let expr = SIZE::new(Atomic::from(A), 2_u64, true);
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [SIZE] is false if the plugin size does not match the given size
         {
             let expr = SIZE::new(Atomic::from(A), 2_u64, false);
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
     }
 
+    #[test]
+    fn test_desc_regex() {
+        init();
+
+        let mods = [PluginData {
+            name: A.to_string(),
+            size: 0_u64,
+            description: Some("requires the patch for purists".to_string()),
+            version: None,
+            masters: None,
+            game_version: None,
+        }];
+        let context = EvalContext::from_plugins(&mods);
+
+        // a valid pattern is compiled once
+        let expr = DESC::new(Atomic::from(A), "patch for (purists|pedants)".into(), false)
+            .expect("invalid regex");
+        assert!(expr.eval(&context).is_some());
+
+        // an invalid pattern is an error
+        assert!(DESC::new(Atomic::from(A), "patch (".into(), false).is_err());
+
+        // the pattern string survives a serde round-trip and is compiled again on use
+        let json = serde_json::to_string(&expr).expect("could not serialize");
+        let expr2: DESC = serde_json::from_str(&json).expect("could not deserialize");
+        assert_eq!(expr, expr2);
+        assert_eq!("patch for (purists|pedants)", expr2.regex);
+        assert!(expr2.eval(&context).is_some());
+    }
+
     #[test]
     fn evaluate_desc() {
         init();
@@ -145,37 +247,122 @@ mod unit_tests {
                 game_version: None,
             })
             .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
 
         // [DESC] is true if the plugin description matches the given description
         {
-            let expr = DESC::new(Atomic::from(A), "description".to_string(), false);
-            assert!(expr.eval(&mods).is_some());
+            let expr = DESC::new(Atomic::from(A), "description".to_string(), false)
+                .expect("invalid regex");
+            assert!(expr.eval(&context).is_some());
         }
         // [DESC] is true if the plugin description matches the given description with regex
         {
-            let expr = DESC::new(Atomic::from(A), "des*".to_string(), false);
-            assert!(expr.eval(&mods).is_some());
+            let expr =
+                DESC::new(Atomic::from(A), "des*".to_string(), false).expect("invalid regex");
+            assert!(expr.eval(&context).is_some());
         }
 
         // [DESC] is false if the plugin description does not match the given description
         {
-            let expr = DESC::new(Atomic::from(A), "another description".to_string(), false);
-            assert!(expr.eval(&mods).is_none());
+            let expr = DESC::new(Atomic::from(A), "another description".to_string(), false)
+                .expect("invalid regex");
+            assert!(expr.eval(&context).is_none());
         }
 
         // [DESC] is true if the plugin description does not matches the given description and is negated is true
         {
-            let expr = DESC::new(Atomic::from(A), "another description".to_string(), true);
-            assert!(expr.eval(&mods).is_some());
+            let expr = DESC::new(Atomic::from(A), "another description".to_string(), true)
+                .expect("invalid regex");
+            assert!(expr.eval(&context).is_some());
         }
 
         // [DESC] is false if the plugin description does match the given description and is negated is true
         {
-            let expr = DESC::new(Atomic::from(A), "description".to_string(), true);
-            assert!(expr.eval(&mods).is_none());
+            let expr =
+                DESC::new(Atomic::from(A), "description".to_string(), true).expect("invalid regex");
+            assert!(expr.eval(&context).is_none());
         }
     }
 
+    #[test]
+    fn evaluate_desc_with_descriptions() {
+        init();
+
+        let mods = [A, B, C].map(|m| m.to_string());
+        let descriptions = std::collections::HashMap::from([
+            ("A.esp".to_owned(), "A Patch for Purists".to_owned()),
+            (B.to_owned(), "the original".to_owned()),
+        ]);
+        let desc = |regex: &str, negated: bool| {
+            DESC::new(Atomic::from(A), regex.into(), negated).expect("invalid regex")
+        };
+
+        // [DESC] matches the description from the map
+        assert_eq!(
+            Some(vec![A.to_owned()]),
+            desc("patch for (purists|pedants)", false).eval_with_descriptions(&mods, &descriptions)
+        );
+        assert!(desc("original", false)
+            .eval_with_descriptions(&mods, &descriptions)
+            .is_none());
+        assert!(desc("original", true)
+            .eval_with_descriptions(&mods, &descriptions)
+            .is_some());
+
+        // plugins without a description never match
+        let expr = DESC::new(Atomic::from(C), ".*".into(), false).expect("invalid regex");
+        assert!(expr.eval_with_descriptions(&mods, &descriptions).is_none());
+
+        // nested expressions see the descriptions too
+        let expr: Expression = ALL::new(vec![desc("purists", false).into(), e(B)]).into();
+        assert!(expr.eval_with_descriptions(&mods, &descriptions).is_some());
+    }
+
+    #[test]
+    fn evaluate_context() {
+        init();
+
+        let context = EvalContext::from_mods(&[A, B, "C.esp"].map(|m| m.to_string()));
+
+        // the boolean expressions only need the names, matches keep the case of the mod list
+        assert_eq!(Some(vec!["C.esp".to_owned()]), e(C).eval(&context));
+        assert!(e(X).eval(&context).is_none());
+        let all: Expression = ALL::new(vec![e(A), e(B)]).into();
+        assert!(all.eval(&context).is_some());
+        let any: Expression = ANY::new(vec![e(X), e(B)]).into();
+        assert_eq!(Some(vec![B.to_owned()]), any.eval(&context));
+        let not: Expression = NOT::new(e(X)).into();
+        assert!(not.eval(&context).is_some());
+        let not: Expression = NOT::new(e(A)).into();
+        assert!(not.eval(&context).is_none());
+
+        // the predicates use the data added to the context
+        let size = SIZE::new(Atomic::from(A), 42, false);
+        let ver = VER::new(Atomic::from(B), EVerOperator::Greater, "1.0.0".into());
+        let desc = DESC::new(Atomic::from(C), "patch".into(), false).expect("invalid regex");
+        assert!(size.eval(&context).is_none());
+        assert!(ver.eval(&context).is_none());
+        assert!(desc.eval(&context).is_none());
+
+        let context = context
+            .with_sizes(&std::collections::HashMap::from([(A.to_owned(), 42)]))
+            .with_versions(&std::collections::HashMap::from([(
+                "B.ESP".to_owned(),
+                semver::Version::new(1, 2, 0),
+            )]))
+            .with_descriptions(&std::collections::HashMap::from([(
+                C.to_owned(),
+                "a patch".to_owned(),
+            )]));
+        assert!(size.eval(&context).is_some());
+        assert!(ver.eval(&context).is_some());
+        assert!(desc.eval(&context).is_some());
+
+        // the index is built once for all expressions
+        assert_eq!(3, context.index().plugins().len());
+        assert!(size.eval(&context).is_some() && all.eval(&context).is_some());
+    }
+
     #[test]
     fn evaluate_ver() {
         init();
@@ -191,18 +378,19 @@ mod unit_tests {
                 game_version: None,
             })
             .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
 
         // Check equals
         // [VER] equals is true if the plugin version matches the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Equal, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [VER] equals is false if the plugin version does not matches the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Equal, "1.1.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // Check greater
@@ -210,19 +398,19 @@ mod unit_tests {
         // [VER] greater is true if the plugin version is greater than the rule version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Greater, "0.1.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [VER] greater is false if the plugin version is less than the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Greater, "1.2.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // [VER] greater is false if the plugin version is equal to the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Greater, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // Check less
@@ -230,19 +418,19 @@ mod unit_tests {
         // [VER] less is true if the plugin version is less than the rule version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Less, "1.2.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [VER] less is false if the plugin version is greater than the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Less, "0.1.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // [VER] less is false if the plugin version is equal to the given version
         {
             let expr = VER::new(Atomic::from(A), EVerOperator::Less, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
     }
 
@@ -263,18 +451,19 @@ mod unit_tests {
                 game_version: Some(version.clone()),
             })
             .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
 
         // Check equals
         // [GVER] equals is true if the plugin version matches the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Equal, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [GVER] equals is false if the plugin version does not matches the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Equal, "1.1.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // Check greater
@@ -282,19 +471,19 @@ mod unit_tests {
         // [GVER] greater is true if the plugin version is greater than the rule version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Greater, "0.1.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [GVER] greater is false if the plugin version is less than the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Greater, "1.2.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // [GVER] greater is false if the plugin version is equal to the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Greater, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // Check less
@@ -302,19 +491,25 @@ mod unit_tests {
         // [GVER] less is true if the plugin version is less than the rule version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Less, "1.2.0".to_string());
-            assert!(expr.eval(&mods).is_some());
+            assert!(expr.eval(&context).is_some());
         }
 
         // [GVER] less is false if the plugin version is greater than the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Less, "0.1.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
         }
 
         // [GVER] less is false if the plugin version is equal to the given version
         {
             let expr = GVER::new(Atomic::from(A), EGVerOperator::Less, "1.0.0".to_string());
-            assert!(expr.eval(&mods).is_none());
+            assert!(expr.eval(&context).is_none());
+        }
+
+        // [GVER] is false instead of panicking if the rule version is invalid, e.g. from deserialized rules
+        {
+            let expr = GVER::new(Atomic::from(A), EGVerOperator::Less, "abc".to_string());
+            assert!(expr.eval(&context).is_none());
         }
     }
 
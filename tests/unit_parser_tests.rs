@@ -3,7 +3,11 @@ mod unit_tests {
     use core::panic;
     use std::io::Cursor;
 
-    use plox::{expressions::Expression, rules::TWarningRule, *};
+    use plox::{
+        expressions::{EvalContext, Expression, TExpression},
+        rules::TWarningRule,
+        *,
+    };
 
     fn init() {
         let env = env_logger::Env::default()
@@ -60,6 +64,923 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_order_inline_pairs() {
+        init();
+
+        // inline and multiline order rules produce the same ordering pairs
+        for input in ["[Order] A.esp B.esp", "[Order]\nA.esp\nB.esp"] {
+            let input = input.to_lowercase();
+            let reader = Cursor::new(input.as_bytes());
+
+            let rules = parser::new_tes3_parser()
+                .parse_rules_from_reader(reader)
+                .expect("Failed to parse rule")
+                .into_iter()
+                .filter_map(|r| match r {
+                    rules::ERule::EOrderRule(o) => Some(o),
+                    rules::ERule::EWarningRule(_) => None,
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                vec![("a.esp".to_owned(), "b.esp".to_owned())],
+                get_ordering_from_order_rules(&rules)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rules_iter() {
+        init();
+
+        // the lazy iterator yields the same rules as the batch parser
+        let parser = parser::new_tes3_parser();
+        let path = "./tests/mlox/mlox_base.txt";
+        let batch = parser
+            .parse_rules_from_reader(std::io::BufReader::new(
+                std::fs::File::open(path).expect("file not found"),
+            ))
+            .expect("Failed to parse rules");
+        let lazy = parser
+            .parse_rules_iter(std::io::BufReader::new(
+                std::fs::File::open(path).expect("file not found"),
+            ))
+            .filter_map(|r| r.ok())
+            .collect::<Vec<_>>();
+
+        assert!(!batch.is_empty());
+        assert_eq!(format!("{:?}", batch), format!("{:?}", lazy));
+
+        // errors are yielded in place and parsing continues
+        let input = "[order]\na.esp\nb.esp\n[pin x] c.esp\n[nearend] d.esp";
+        let results = parser
+            .parse_rules_iter(Cursor::new(input.as_bytes()))
+            .collect::<Vec<_>>();
+        assert_eq!(3, results.len());
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn test_near_priority() {
+        init();
+
+        let input = "[NearStart 10] a.esp\n[NearEnd -2]\nb.esp\n[NearStart] c.esp".to_lowercase();
+        let rules = parser::new_tes3_parser()
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule");
+        assert_eq!(3, rules.len());
+
+        let priorities = rules
+            .iter()
+            .map(|r| match r {
+                rules::ERule::EOrderRule(rules::EOrderRule::NearStart(x)) => x.priority,
+                rules::ERule::EOrderRule(rules::EOrderRule::NearEnd(x)) => x.priority,
+                _ => panic!("wrong rule type"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![10, -2, 0], priorities);
+    }
+
+    #[test]
+    fn test_bom_crlf() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let clean = "[order]\na.esp\nb.esp\n\n[note] a comment\nc.esp\n";
+        let expected = parser
+            .parse_rules_from_reader(Cursor::new(clean.as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(2, expected.len());
+
+        let bom = format!("\u{feff}{}", clean);
+        let crlf = clean.replace('\n', "\r\n");
+        let both = format!("\u{feff}{}", crlf);
+        for input in [bom, crlf, both] {
+            let rules = parser
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rules");
+            assert_eq!(format!("{:?}", expected), format!("{:?}", rules));
+        }
+    }
+
+    #[test]
+    fn test_pin_rule() {
+        init();
+
+        for input in ["[Pin 2] A.esp", "[Pin 2]\nA.esp"] {
+            let input = input.to_lowercase();
+            let reader = Cursor::new(input.as_bytes());
+
+            let rules = parser::new_tes3_parser()
+                .parse_rules_from_reader(reader)
+                .expect("Failed to parse rule");
+            assert_eq!(1, rules.len());
+            match &rules[0] {
+                rules::ERule::EOrderRule(rules::EOrderRule::Pin(pin)) => {
+                    assert_eq!("a.esp", pin.name);
+                    assert_eq!(2, pin.index);
+                }
+                _ => panic!("wrong rule type"),
+            }
+        }
+
+        // a pin needs a valid index and exactly one plugin
+        for input in ["[Pin x] a.esp", "[Pin 1] a.esp b.esp"] {
+            let (rules, errors) = parser::new_tes3_parser()
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to read rules");
+            assert!(rules.is_empty());
+            assert_eq!(1, errors.len());
+        }
+    }
+
+    #[test]
+    fn test_order_inline_comments() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let body = "a.esp;inline\nb.esp ; note\n; only a comment\nc.esp";
+
+        // order rule parsers strip comments themselves
+        {
+            use rules::TParser;
+
+            let mut order = rules::Order::default();
+            rules::Order::parse(&mut order, Cursor::new(body.as_bytes()), &parser)
+                .expect("Failed to parse order");
+            assert_eq!(vec!["a.esp", "b.esp", "c.esp"], order.names);
+
+            let mut nearstart = rules::NearStart::default();
+            rules::NearStart::parse(&mut nearstart, Cursor::new(body.as_bytes()), &parser)
+                .expect("Failed to parse nearstart");
+            assert_eq!(vec!["a.esp", "b.esp", "c.esp"], nearstart.names);
+
+            let mut nearend = rules::NearEnd::default();
+            rules::NearEnd::parse(&mut nearend, Cursor::new(body.as_bytes()), &parser)
+                .expect("Failed to parse nearend");
+            assert_eq!(vec!["a.esp", "b.esp", "c.esp"], nearend.names);
+        }
+
+        // and the same through the rules parser
+        let input = format!("[Order]\n{}", body);
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(order)
+            .collect::<Vec<_>>();
+        assert_eq!(1, rules.len());
+        assert_eq!(vec!["a.esp", "b.esp", "c.esp"], rules[0].names);
+
+        assert_eq!("a.esp", parser::strip_comment("a.esp;inline"));
+        assert_eq!(vec!["a.esp"], parser.tokenize("a.esp ; b.esp".into()));
+    }
+
+    #[test]
+    fn test_conflict_expressions() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+
+        // any number of expressions conflict with each other
+        let input = "[Conflict]\na.esp\nb.esp\nc.esp";
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule");
+        match rules.first() {
+            Some(rules::ERule::EWarningRule(rules::EWarningRule::Conflict(c))) => {
+                assert_eq!(3, c.expressions.len())
+            }
+            _ => panic!("Expected a conflict rule"),
+        }
+
+        // a single expression is accepted but never conflicts
+        let input = "[Conflict]\na.esp";
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule");
+        match rules.first() {
+            Some(rules::ERule::EWarningRule(rules::EWarningRule::Conflict(c))) => {
+                assert_eq!(1, c.expressions.len());
+                assert!(!c.clone().eval(&[PluginData::new("a.esp".into(), 0)]));
+            }
+            _ => panic!("Expected a conflict rule"),
+        }
+    }
+
+    #[test]
+    fn test_order_guard() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let input = "[Order] [ALL Guard.esp] a.esp b.esp\n\n[Order]\n[ANY x.esp\n y.esp] c.esp\nd.esp\n\n[Order]\ne.esp\nf.esp\n\n[Order]\n[Official]g.esp\n[SIZE 123 h.esp]\ni.esp";
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(order)
+            .collect::<Vec<_>>();
+        assert_eq!(4, rules.len());
+
+        // inline guard
+        assert_eq!(vec!["a.esp", "b.esp"], rules[0].names);
+        assert!(matches!(rules[0].guard, Some(Expression::ALL(_))));
+
+        // multiline guard
+        assert_eq!(vec!["c.esp", "d.esp"], rules[1].names);
+        assert!(matches!(rules[1].guard, Some(Expression::ANY(_))));
+
+        // unguarded
+        assert_eq!(vec!["e.esp", "f.esp"], rules[2].names);
+        assert!(rules[2].guard.is_none());
+
+        // brackets that are not followed by names on the same line are plugin names
+        assert_eq!(
+            vec!["[official]g.esp", "[size 123 h.esp]", "i.esp"],
+            rules[3].names
+        );
+        assert!(rules[3].guard.is_none());
+
+        // an unclosed guard is an error
+        let input = "[Order] [ALL Guard.esp a.esp b.esp";
+        assert!(parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_order_soft() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let input = "[Order?]\na.esp\nb.esp\n\n[Order]\nc.esp\nd.esp";
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(order)
+            .collect::<Vec<_>>();
+        assert_eq!(2, rules.len());
+
+        assert_eq!(vec!["a.esp", "b.esp"], rules[0].names);
+        assert!(rules[0].soft);
+        assert_eq!(vec!["c.esp", "d.esp"], rules[1].names);
+        assert!(!rules[1].soft);
+    }
+
+    #[test]
+    fn test_quoted_tokenize() {
+        init();
+
+        let parser = parser::new_cyberpunk_parser();
+
+        // the extension followed by a space inside quotes does not end the token
+        assert_eq!(
+            vec!["weird.archive backup.archive"],
+            parser.tokenize("\"weird.archive backup.archive\"".into())
+        );
+        assert_eq!(
+            vec!["a.archive", "weird.archive  backup.archive", "c.archive"],
+            parser.tokenize("a.archive \"weird.archive  backup.archive\" c.archive".into())
+        );
+
+        let input = "[Order]\n\"weird.archive backup.archive\"\nb.archive".to_owned();
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(order)
+            .collect::<Vec<_>>();
+        assert_eq!(1, rules.len());
+        assert_eq!(
+            vec!["weird.archive backup.archive", "b.archive"],
+            rules[0].names
+        );
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        init();
+
+        let parser = parser::new_cyberpunk_parser();
+
+        // spans exclude the quotes and surrounding whitespace
+        let line = "a.archive \"weird.archive  backup.archive\" c.archive ; comment";
+        let spans = parser.tokenize_spans(line);
+        assert_eq!(
+            vec![
+                (0..9, "a.archive".to_owned()),
+                (11..40, "weird.archive  backup.archive".to_owned()),
+                (42..51, "c.archive".to_owned()),
+            ],
+            spans
+        );
+        for (range, token) in &spans {
+            assert_eq!(token, &line[range.clone()]);
+        }
+
+        // ranges are byte ranges
+        let line = "  héllo.archive b.archive";
+        assert_eq!(
+            vec![
+                (2..16, "héllo.archive".to_owned()),
+                (17..26, "b.archive".to_owned()),
+            ],
+            parser.tokenize_spans(line)
+        );
+    }
+
+    #[test]
+    fn test_tokenize_escapes() {
+        init();
+
+        let parser = parser::new_cyberpunk_parser();
+
+        // an escaped quote is part of the token
+        assert_eq!(
+            vec!["a \"quoted\" mod.archive".to_owned(), "b.archive".into()],
+            parser.tokenize(r#""a \"quoted\" mod.archive" b.archive"#.to_owned())
+        );
+        // an escaped backslash is a literal backslash
+        assert_eq!(
+            vec!["folder\\a.archive".to_owned(), "b.archive".into()],
+            parser.tokenize(r#""folder\\a.archive" b.archive"#.to_owned())
+        );
+        // other backslashes and backslashes outside of quotes are kept as is
+        assert_eq!(
+            vec!["folder\\a.archive".to_owned(), "x\\b.archive".into()],
+            parser.tokenize(r#""folder\a.archive" x\b.archive"#.to_owned())
+        );
+
+        // the span covers the escaped text
+        let line = r#""a\"b.archive""#;
+        assert_eq!(
+            vec![(1..13, "a\"b.archive".to_owned())],
+            parser.tokenize_spans(line)
+        );
+
+        // rules read escaped names
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(
+                "[Order]\n\"a \\\"quoted\\\" mod.archive\"\nb.archive".as_bytes(),
+            ))
+            .expect("Failed to parse rules");
+        let Some(rules::ERule::EOrderRule(rules::EOrderRule::Order(order))) = rules.first() else {
+            panic!("wrong rule type");
+        };
+        assert_eq!(vec!["a \"quoted\" mod.archive", "b.archive"], order.names);
+    }
+
+    #[test]
+    fn test_tokenize_names() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        assert_eq!(vec!["@group"], parser.tokenize_names("@group".into()));
+        assert_eq!(
+            vec!["@group", "a.esp", "@other"],
+            parser.tokenize_names("@group a.esp @other".into())
+        );
+    }
+
+    #[test]
+    fn test_group() {
+        init();
+
+        let input =
+            "[Group textures]\na.esp b.esp\n[Group]\na.esp\n[Group two words] a.esp\n[Group empty]";
+        let rules = parser::new_tes3_parser()
+            .parse_rules_from_reader(Cursor::new(input.to_lowercase().into_bytes()))
+            .expect("Failed to parse rules")
+            .into_iter()
+            .filter_map(group)
+            .collect::<Vec<_>>();
+
+        // groups need a one word name and plugins
+        assert_eq!(1, rules.len());
+        assert_eq!("textures", rules[0].name);
+        assert_eq!(vec!["a.esp", "b.esp"], rules[0].names);
+    }
+
+    #[test]
+    fn test_skyrim_tokenize() {
+        init();
+
+        let parser = parser::new_skyrim_parser();
+        assert_eq!(
+            vec!["a.esm", "b with space.esl", "c.esp"],
+            parser.tokenize("a.esm b with space.esl c.esp".into())
+        );
+
+        let input = "[Order]\nunofficial patch.esl\nmy mod.esp".to_owned();
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(order)
+            .collect::<Vec<_>>();
+        assert_eq!(1, rules.len());
+        assert_eq!(vec!["unofficial patch.esl", "my mod.esp"], rules[0].names);
+    }
+
+    #[test]
+    fn test_openmw_tokenize() {
+        init();
+
+        let parser = parser::new_openmw_parser();
+        assert_eq!(
+            vec!["a scripts.omwscripts", "b.omwaddon", "c.esp", "d.omwgame"],
+            parser.tokenize("a scripts.omwscripts b.omwaddon c.esp d.omwgame".into())
+        );
+
+        // .omwscripts terminates an atomic in expressions
+        let exprs = parser
+            .parse_expressions(Cursor::new("a scripts.omwscripts b.esm".as_bytes()))
+            .expect("No expressions parsed");
+        assert_eq!(2, exprs.len());
+        assert!(is_atomic(&exprs[0], "a scripts.omwscripts"));
+        assert!(is_atomic(&exprs[1], "b.esm"));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        init();
+
+        let nested = |n: usize| format!("{}a.esp{}", "[any ".repeat(n), "]".repeat(n));
+        let parser = parser::new_tes3_parser();
+
+        // at the limit
+        let exprs = parser
+            .parse_expressions(Cursor::new(nested(parser::MAX_EXPRESSION_DEPTH).as_bytes()))
+            .expect("No expressions parsed");
+        assert_eq!(1, exprs.len());
+
+        // just beyond the limit and far beyond it fail gracefully
+        for n in [parser::MAX_EXPRESSION_DEPTH + 1, 10_000] {
+            let err = parser
+                .parse_expressions(Cursor::new(nested(n).as_bytes()))
+                .expect_err("Expected a depth error");
+            assert_eq!(
+                error::ParseError::MaxDepthExceeded(parser::MAX_EXPRESSION_DEPTH),
+                error::ParseError::from_io(&err)
+            );
+        }
+
+        // the limit is configurable and reported per rule
+        let input = format!("[Note]\n{}", nested(3));
+        let (rules, errors) = parser::new_tes3_parser()
+            .with_max_depth(2)
+            .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+            .expect("Failed to read rules");
+        assert!(rules.is_empty());
+        assert_eq!(error::ParseError::MaxDepthExceeded(2), errors[0].error);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        init();
+
+        let input = "[Note a is installed]\na.esp\n\
+            [Conflict a and b conflict]\na.esp\nb.esp\n\
+            [Requires c needs x]\nc.esp\nx.esp\n\
+            [Patch p patches y]\np.esp\ny.esp\n\
+            [Note not installed]\nz.esp\n";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, &input.to_lowercase()).expect("Failed to parse rules");
+        assert_eq!(5, parser.warning_rules.len());
+
+        let mods = ["A.esp", "b.esp", "c.esp"].map(|m| m.to_owned());
+        let expected = vec![
+            parser::EvaluatedWarning {
+                rule: "Note".into(),
+                comment: "a is installed".into(),
+                plugins: vec!["a.esp".into()],
+                severity: rules::ESeverity::Warning,
+                missing: vec![],
+            },
+            parser::EvaluatedWarning {
+                rule: "Conflict".into(),
+                comment: "a and b conflict".into(),
+                plugins: vec!["a.esp".into(), "b.esp".into()],
+                severity: rules::ESeverity::Warning,
+                missing: vec![],
+            },
+            parser::EvaluatedWarning {
+                rule: "Requires".into(),
+                comment: "c needs x".into(),
+                plugins: vec!["c.esp".into()],
+                severity: rules::ESeverity::Warning,
+                missing: vec!["x.esp".into()],
+            },
+        ];
+        assert_eq!(expected, parser.evaluate(&mods));
+        // evaluating does not change the rules
+        assert_eq!(expected, parser.evaluate(&mods));
+    }
+
+    #[test]
+    fn test_requires_missing() {
+        init();
+
+        let input = "[Requires foo needs bar]\nfoo.esp\n[ALL bar.esp baz.esp]\n";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, input).expect("Failed to parse rules");
+
+        // only the dependencies that are not installed are reported
+        let mods = ["Foo.esp", "baz.esp"].map(|m| m.to_owned());
+        let warnings = parser.evaluate(&mods);
+        assert_eq!(1, warnings.len());
+        assert_eq!(vec!["foo.esp".to_owned()], warnings[0].plugins);
+        assert_eq!(vec!["bar.esp".to_owned()], warnings[0].missing);
+
+        parser.evaluate_plugins(&mods.map(|m| PluginData::new(m, 0)));
+        if let rules::EWarningRule::Requires(r) = &parser.warnings[0].rule {
+            assert_eq!(
+                Some("foo.esp requires bar.esp (not installed)".to_owned()),
+                r.missing_message()
+            );
+        } else {
+            panic!("expected a requires rule");
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_evaluate_parallel() {
+        init();
+
+        let mut input = String::new();
+        for i in 0..500 {
+            input.push_str(&format!("[Note n{i}]\nm{}.esp\n", i % 50));
+            input.push_str(&format!(
+                "[Conflict c{i}]\nm{}.esp\nm{}.esp\n",
+                i % 7,
+                i % 11
+            ));
+            input.push_str(&format!("[Requires r{i}]\nm{}.esp\nx{i}.esp\n", i % 13));
+        }
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, &input).expect("Failed to parse rules");
+
+        let mods = (0..30).map(|i| format!("m{i}.esp")).collect::<Vec<_>>();
+        let serial = parser.evaluate_serial(&mods);
+        assert!(!serial.is_empty());
+        assert_eq!(serial, parser.evaluate_parallel(&mods));
+        assert_eq!(serial, parser.evaluate(&mods));
+    }
+
+    #[test]
+    fn test_evaluate_ordered() {
+        init();
+
+        let mut parser = parser::new_tes3_parser();
+        parser
+            .warning_rules
+            .push(rules::Note::new_ordered("b must load first".into(), "a.esp", "b.esp").into());
+        parser.warning_rules.push(
+            rules::Note::new(
+                "a is installed".into(),
+                &[expressions::Atomic::from("a.esp").into()],
+            )
+            .into(),
+        );
+
+        let order = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let fired = |warnings: Vec<parser::EvaluatedWarning>| {
+            warnings.into_iter().map(|w| w.comment).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["b must load first", "a is installed"],
+            fired(parser.evaluate_ordered(&order(&["B.esp", "A.esp"])))
+        );
+        assert_eq!(
+            vec!["a is installed"],
+            fired(parser.evaluate_ordered(&order(&["A.esp", "B.esp"])))
+        );
+        // without positions only the presence based note fires
+        assert_eq!(
+            vec!["a is installed"],
+            fired(parser.evaluate(&order(&["B.esp", "A.esp"])))
+        );
+    }
+
+    #[test]
+    fn test_note_order() {
+        init();
+
+        let input = "[NoteOrder! b must load first]\na.esp\nb.esp\n";
+        let parser = parser::new_tes3_parser();
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(1, rules.len());
+        let rules::ERule::EWarningRule(rules::EWarningRule::Note(note)) = &rules[0] else {
+            panic!("expected a note");
+        };
+        assert_eq!(Some(("a.esp".to_owned(), "b.esp".to_owned())), note.order);
+        assert!(note.expressions.is_empty());
+        assert_eq!("b must load first", note.comment);
+        assert_eq!(rules::ESeverity::Error, note.severity);
+
+        // the writer output parses back to the same rule
+        let mut writer = writer::RuleWriter::new();
+        writer.add_rule(rules::Note::new_ordered(
+            "b must load first".into(),
+            "a.esp",
+            "b.esp",
+        ));
+        let reparsed = parser
+            .parse_rules_from_reader(Cursor::new(writer.to_text().as_bytes()))
+            .expect("Failed to parse rules");
+        let rules::ERule::EWarningRule(rules::EWarningRule::Note(note)) = &reparsed[0] else {
+            panic!("expected a note");
+        };
+        assert_eq!(Some(("a.esp".to_owned(), "b.esp".to_owned())), note.order);
+        assert_eq!("b must load first", note.comment);
+
+        // the body must be two plugin names
+        for input in ["[NoteOrder]\na.esp\n", "[NoteOrder]\na.esp\n[NOT b.esp]\n"] {
+            assert!(parser
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rules")
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn test_eval_rule() {
+        init();
+
+        let input = "[Note a is installed]\na.esp\n[Note not installed]\nz.esp\n";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, input).expect("Failed to parse rules");
+
+        let mods = ["A.esp", "b.esp"].map(|m| m.to_owned());
+        assert_eq!(
+            Some(parser::EvaluatedWarning {
+                rule: "Note".into(),
+                comment: "a is installed".into(),
+                plugins: vec!["a.esp".into()],
+                severity: rules::ESeverity::Warning,
+                missing: vec![],
+            }),
+            parser.eval_rule(0, &mods)
+        );
+        assert_eq!(None, parser.eval_rule(1, &mods));
+        assert_eq!(None, parser.eval_rule(2, &mods));
+        // evaluating does not change the rules
+        assert!(parser.warning_rules[0].get_plugins().is_empty());
+    }
+
+    #[test]
+    fn test_rule_writer() {
+        init();
+
+        let atomic = |name: &str| -> Expression { expressions::Atomic::from(name).into() };
+        let mut writer = writer::RuleWriter::new();
+        writer
+            .add_order(&["a.esp", "a \"quoted\" mod.esp", "c.esp"])
+            .add_nearstart(&["b.esp"])
+            .add_rule(rules::Pin::new("d.esp".into(), 2))
+            .add_note(
+                "a is installed",
+                &[expressions::ALL::new(vec![
+                    atomic("a.esp"),
+                    expressions::NOT::new(atomic("b with spaces.esp")).into(),
+                ])
+                .into()],
+            )
+            .add_conflict("", &[atomic("a.esp"), atomic("b.esp")])
+            .add_requires("a needs b", atomic("a.esp"), atomic("b.esp"))
+            .add_patch("", atomic("p.esp"), atomic("c.esp"));
+        let mut blacklist = rules::Blacklist::new("outdated".into(), &[atomic("old.esp")]);
+        blacklist.severity = rules::ESeverity::Error;
+        writer.add_rule(blacklist);
+
+        let text = writer.to_text();
+        let parsed = parser::new_tes3_parser()
+            .parse_rules_from_reader(Cursor::new(text.as_bytes()))
+            .expect("Failed to parse rules");
+
+        // the parsed rules are the written rules
+        assert_eq!(writer.rules.len(), parsed.len(), "{}", text);
+        assert_eq!(
+            serde_json::to_value(&writer.rules).expect("could not serialize"),
+            serde_json::to_value(&parsed).expect("could not serialize"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_header_comment() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let inputs = [
+            "[Note message] ; editor comment\na.esp\nb.esp",
+            "[Note message]; see [Note other] for more\na.esp\nb.esp",
+            "[Note]   ;  comment\n message\na.esp\nb.esp",
+        ];
+        for input in inputs {
+            let rules = parser
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rules");
+            assert_eq!(1, rules.len(), "{}", input);
+            let rules::ERule::EWarningRule(rules::EWarningRule::Note(note)) = &rules[0] else {
+                panic!("wrong rule type");
+            };
+            assert_eq!("message", note.comment);
+            assert_eq!(2, note.expressions.len());
+        }
+    }
+
+    #[test]
+    fn test_find_dead_rules() {
+        init();
+
+        let input = "[Note normal]\na.esp\n[Note contradiction]\n[ALL a.esp [NOT a.esp]]\n[Note nested]\n[ANY [ALL b.esp [NOT b.esp]] c.esp]\n";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, input).expect("Failed to parse rules");
+        assert_eq!(3, parser.warning_rules.len());
+
+        // the parser rejects atomics without an extension, rules from other sources may still have them
+        parser.warning_rules.push(
+            rules::Note::new(
+                "no extension".into(),
+                &[expressions::Atomic::from("a.txt").into()],
+            )
+            .into(),
+        );
+
+        // a contradiction and a malformed atomic are dead, a rule with one valid alternative is not
+        assert_eq!(vec![1, 3], parser.find_dead_rules());
+
+        // ordered notes have no expressions but are not dead
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, "[NoteOrder]\na.esp\nb.esp").expect("Failed to parse rules");
+        assert_eq!(1, parser.warning_rules.len());
+        assert!(parser.find_dead_rules().is_empty());
+    }
+
+    #[test]
+    fn test_sort_many() {
+        init();
+
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, "[Order]\na.esp\nb.esp\n[Order]\nc.esp\nd.esp")
+            .expect("Failed to parse rules");
+
+        // each profile is sorted on its own with the same rules
+        let groups = [
+            vec!["b.esp".to_owned(), "x.esp".into(), "a.esp".into()],
+            vec!["d.esp".to_owned(), "c.esp".into(), "b.esp".into()],
+        ];
+        let results = parser.sort_many(&groups);
+        assert_eq!(2, results.len());
+        assert_eq!(
+            vec!["a.esp", "b.esp", "x.esp"],
+            results[0].clone().expect("could not sort")
+        );
+        assert_eq!(
+            vec!["c.esp", "d.esp", "b.esp"],
+            results[1].clone().expect("could not sort")
+        );
+    }
+
+    #[test]
+    fn test_severity() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let severity = |input: &str| {
+            let rules = parser
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rules");
+            match &rules[0] {
+                rules::ERule::EWarningRule(w) => w.severity(),
+                _ => panic!("wrong rule type"),
+            }
+        };
+
+        assert_eq!(rules::ESeverity::Warning, severity("[Note message]\na.esp"));
+        assert_eq!(rules::ESeverity::Error, severity("[Note! message]\na.esp"));
+        assert_eq!(rules::ESeverity::Info, severity("[Note? message]\na.esp"));
+        assert_eq!(
+            rules::ESeverity::Error,
+            severity("[Conflict!]\na.esp\nb.esp")
+        );
+        assert_eq!(
+            rules::ESeverity::Info,
+            severity("[Requires?]\na.esp\nb.esp")
+        );
+        assert_eq!(rules::ESeverity::Error, severity("[Patch!]\na.esp\nb.esp"));
+        assert_eq!(rules::ESeverity::Info, severity("[Blacklist? old]\na.esp"));
+        assert_eq!(
+            rules::ESeverity::Error,
+            severity("[RequiresVersion! >=1.0 message]\na.esp")
+        );
+
+        // the marker is not part of the message
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new("[Note! message]\na.esp".as_bytes()))
+            .expect("Failed to parse rules");
+        let rules::ERule::EWarningRule(rule) = &rules[0] else {
+            panic!("wrong rule type");
+        };
+        assert_eq!("message", rule.get_comment());
+
+        // evaluate carries the severity
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, "[Note? message]\na.esp").expect("Failed to parse rules");
+        let warnings = parser.evaluate(&["a.esp".to_owned()]);
+        assert_eq!(rules::ESeverity::Info, warnings[0].severity);
+    }
+
+    #[test]
+    fn test_referenced_plugins() {
+        init();
+
+        let input = "[Order]\nb.esp\na.esp\n[Pin 0]\nc.esp\n[Note message]\n[ALL d.esp [NOT [ANY e.esp [DESC /x/ f.esp]]]]\n[Requires]\n[VER > 1.0 g.esp]\na.esp\n[Conflict]\n[SIZE 100 h.esp]\ni.esp";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, input).expect("Failed to parse rules");
+
+        // deduplicated and sorted
+        assert_eq!(
+            vec!["a.esp", "b.esp", "c.esp", "d.esp", "e.esp", "f.esp", "g.esp", "h.esp", "i.esp"],
+            parser.referenced_plugins().into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_match_mode() {
+        init();
+
+        let plugins = [PluginData::new("Afoo.esp".into(), 0)];
+        for (mode, expected) in [
+            (parser::EMatchMode::Exact, 0),
+            (parser::EMatchMode::CaseInsensitive, 0),
+            (parser::EMatchMode::Normalized, 1),
+        ] {
+            let mut parser = parser::new_tes3_parser().with_match_mode(mode);
+            add_rules(&mut parser, &"[Note]\nÅfoo.esp".to_lowercase())
+                .expect("Failed to parse rules");
+            assert_eq!(1, parser.warning_rules.len());
+
+            parser.evaluate_plugins(&plugins);
+            assert_eq!(expected, parser.warnings.len(), "{:?}", mode);
+        }
+
+        assert_eq!(
+            "afoo bar.esp",
+            parser::normalize_name("Åfoo   Bar.esp", parser::EMatchMode::Normalized)
+        );
+    }
+
+    #[test]
+    fn test_match_mode_exact_case() {
+        init();
+
+        for (mode, installed, expected) in [
+            (parser::EMatchMode::Exact, "Tamriel_Data.esm", 1),
+            (parser::EMatchMode::Exact, "tamriel_data.esm", 0),
+            (parser::EMatchMode::CaseInsensitive, "tamriel_data.esm", 1),
+        ] {
+            let mut parser = parser::new_tes3_parser().with_match_mode(mode);
+            add_rules(&mut parser, "[Note]\nTamriel_Data.esm").expect("Failed to parse rules");
+
+            parser.evaluate_plugins(&[PluginData::new(installed.into(), 0)]);
+            assert_eq!(expected, parser.warnings.len(), "{:?} {}", mode, installed);
+            assert_eq!(
+                expected,
+                parser.evaluate(&[installed.to_owned()]).len(),
+                "{:?} {}",
+                mode,
+                installed
+            );
+        }
+
+        // predicates match the plugin name like atomics
+        for (mode, installed, expected) in [
+            (parser::EMatchMode::Exact, "Tamriel_Data.esm", 1),
+            (parser::EMatchMode::Exact, "tamriel_data.esm", 0),
+            (parser::EMatchMode::CaseInsensitive, "tamriel_data.esm", 1),
+        ] {
+            let mut parser = parser::new_tes3_parser().with_match_mode(mode);
+            add_rules(&mut parser, "[Note]\n[DESC /data/ Tamriel_Data.esm]")
+                .expect("Failed to parse rules");
+
+            let mut plugin = PluginData::new(installed.into(), 0);
+            plugin.description = Some("Tamriel Data".into());
+            parser.evaluate_plugins(&[plugin]);
+            assert_eq!(expected, parser.warnings.len(), "{:?} {}", mode, installed);
+        }
+    }
+
     #[test]
     fn test_invalid_rule() {
         let input = "[Order]\nab01GOTYpatch.esp\n;JoinAll*.esp";
@@ -73,6 +994,283 @@ mod unit_tests {
         assert!(rules.is_empty());
     }
 
+    #[test]
+    fn test_desc_invalid_regex() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        assert!(parser
+            .parse_expression("[desc /patch (/ a.esp]", true)
+            .is_err());
+        assert!(parser
+            .parse_expression("[desc /patch/ a.esp]", true)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_malformed_rules_do_not_panic() {
+        init();
+
+        let inputs = [
+            "[Unknown]\na.esp",
+            "[Order",
+            "[Note]\n[ANY",
+            "[VER < abc a.esp]",
+            "[Note]\n[VER < abc a.esp]",
+            "[Note]\n[GVER > a.esp]",
+            "[Pin x] a.esp",
+            "[Requires]\n]]]\n",
+            "\n\n  \n",
+            "a.esp\n[Order]",
+        ];
+        for input in inputs {
+            let reader = Cursor::new(input.as_bytes());
+            let (rules, errors) = parser::new_tes3_parser()
+                .parse_rules_with_errors(reader)
+                .expect("Failed to read rules");
+            assert!(rules.is_empty(), "{}", input);
+            assert!(!errors.is_empty() || input.trim().is_empty(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        init();
+
+        let input =
+            "[Order]\na.esp\nb.esp\n\n[Note]\n\n[Requires]\n[ANY a.esp\n\n[Conflict]\na.esp\nb.esp";
+        let reader = Cursor::new(input.to_lowercase().into_bytes());
+        let (rules, errors) = parser::new_tes3_parser()
+            .parse_rules_with_errors(reader)
+            .expect("Failed to parse rules");
+
+        // the valid rules are still parsed
+        assert_eq!(2, rules.len());
+
+        assert_eq!(2, errors.len());
+        assert_eq!(Some("note".into()), errors[0].rule);
+        assert_eq!(5, errors[0].line);
+        assert_eq!(error::ParseError::EmptyBody, errors[0].error);
+
+        assert_eq!(Some("requires".into()), errors[1].rule);
+        assert_eq!(7, errors[1].line);
+        assert!(matches!(
+            errors[1].error,
+            error::ParseError::MalformedExpression(_)
+        ));
+    }
+
+    #[test]
+    fn test_empty_order_bodies() {
+        init();
+
+        // order rules with an empty body are errors, not empty rules
+        for header in [
+            "[Order]",
+            "[NearStart]",
+            "[NearEnd 2]",
+            "[Remove]",
+            "[Group g]",
+        ] {
+            let input = format!("{}\n\n[Order]\na.esp\nb.esp\n", header);
+            let (rules, errors) = parser::new_tes3_parser()
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rules");
+            assert_eq!(1, rules.len(), "{}", header);
+            assert_eq!(1, errors.len(), "{}", header);
+            assert_eq!(1, errors[0].line, "{}", header);
+            assert_eq!(error::ParseError::EmptyBody, errors[0].error, "{}", header);
+        }
+
+        // a pin needs exactly one plugin
+        let (_, errors) = parser::new_tes3_parser()
+            .parse_rules_with_errors(Cursor::new("[Pin 1]\n\n".as_bytes()))
+            .expect("Failed to parse rules");
+        assert!(matches!(
+            errors[0].error,
+            error::ParseError::MalformedRule(_)
+        ));
+    }
+
+    #[test]
+    fn test_disabled_rules() {
+        init();
+
+        let input = "[Order]\na.esp\nb.esp\n[!Order]\nb.esp\nc.esp\n\
+            [!Note! disabled]\na.esp\n[Note active]\na.esp\n";
+
+        // disabled rules are parsed and flagged
+        let parser = parser::new_tes3_parser();
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(
+            vec![false, true, true, false],
+            rules.iter().map(|r| r.is_disabled()).collect::<Vec<_>>()
+        );
+        if let rules::ERule::EWarningRule(w) = &rules[2] {
+            assert_eq!("disabled", w.get_comment());
+            assert_eq!(rules::ESeverity::Error, w.severity());
+        } else {
+            panic!("expected a warning rule");
+        }
+
+        // but they are not in the active rule set
+        let dir = std::path::PathBuf::from("tmp").join("disabled_rules");
+        std::fs::create_dir_all(&dir).expect("could not create dir");
+        std::fs::write(dir.join("mlox_base.txt"), input).expect("could not write file");
+        let mut parser = parser::new_tes3_parser();
+        parser.parse(&dir).expect("Failed to parse rules");
+        assert_eq!(1, parser.order_rules.len());
+        assert_eq!(1, parser.warning_rules.len());
+        assert_eq!("active", parser.warning_rules[0].get_comment());
+
+        // the writer keeps the flag
+        let mut writer = writer::RuleWriter::new();
+        for rule in rules {
+            writer.add_rule(rule);
+        }
+        let reparsed = parser::new_tes3_parser()
+            .parse_rules_from_reader(Cursor::new(writer.to_text().as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(
+            vec![false, true, true, false],
+            reparsed.iter().map(|r| r.is_disabled()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_display_names() {
+        init();
+
+        let input = "[Requires]\nMixedCase.esp\n[ANY Other.ESP [VER > 1.0 Third.esp]]\n";
+        let mut parser = parser::new_tes3_parser();
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rules");
+        let rules::ERule::EWarningRule(rule) = &rules[0] else {
+            panic!("expected a warning rule");
+        };
+
+        // the atomics match lowercase but display the original case
+        let mut atomics = vec![];
+        for expression in rule.expressions() {
+            expression.visit_atomics(&mut |a| {
+                atomics.push((a.item.clone(), a.display_name().to_owned()))
+            });
+        }
+        assert_eq!(
+            vec![
+                ("mixedcase.esp".to_owned(), "MixedCase.esp".to_owned()),
+                ("other.esp".to_owned(), "Other.ESP".to_owned()),
+                ("third.esp".to_owned(), "Third.esp".to_owned()),
+            ],
+            atomics
+        );
+        assert_eq!("MixedCase.esp", rule.expressions()[0].to_string());
+
+        // matching still uses the lowercase names
+        parser.warning_rules.push(rule.clone());
+        let warnings = parser.evaluate(&["mixedcase.esp".to_owned()]);
+        assert_eq!(1, warnings.len());
+        assert_eq!(vec!["mixedcase.esp".to_owned()], warnings[0].plugins);
+
+        // the writer echoes the original text
+        let mut writer = writer::RuleWriter::new();
+        writer.add_rule(rules[0].clone());
+        assert!(writer.to_text().contains("MixedCase.esp"));
+        assert!(writer.to_text().contains("[VER > 1.0.0 Third.esp]"));
+    }
+
+    #[test]
+    fn test_strict() {
+        init();
+
+        let input =
+            "[Order]\na.esp\nb.esp\n\n[Note]\n\n[Requires]\n[ANY a.esp\n\n[Conflict]\na.esp\nb.esp";
+
+        // lenient parsing returns the good rules only
+        let rules = parser::new_tes3_parser()
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(2, rules.len());
+
+        // strict parsing stops at the first bad rule
+        let parser = parser::new_tes3_parser().with_strict(true);
+        let err = parser
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect_err("strict parsing should fail");
+        assert_eq!(
+            error::ParseError::EmptyBody,
+            error::ParseError::from_io(&err)
+        );
+        let err = parser
+            .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+            .expect_err("strict parsing should fail");
+        assert_eq!(
+            error::ParseError::EmptyBody,
+            error::ParseError::from_io(&err)
+        );
+
+        // valid rules parse the same in strict mode
+        let rules = parser
+            .parse_rules_from_reader(Cursor::new("[Order]\na.esp\nb.esp".as_bytes()))
+            .expect("Failed to parse rules");
+        assert_eq!(1, rules.len());
+    }
+
+    #[test]
+    fn test_rules_json_roundtrip() {
+        init();
+
+        let input = "[Order]\na.esp\nb.esp\n[NearStart]\nc.esp\n[NearEnd]\nd.esp\n[Note message]\n[ALL a.esp [NOT b.esp]]\n[Conflict]\n[DESC !/vampire/ a.esp]\n[SIZE 100 b.esp]\n[Requires]\n[VER > 1.0 a.esp]\nb.esp\n[Patch]\n[ANY c.esp d.esp]\ne.esp";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, &input.to_lowercase()).expect("Failed to parse rules");
+        assert_eq!(3, parser.order_rules.len());
+        assert_eq!(4, parser.warning_rules.len());
+
+        let json = parser.export_rules_json().expect("Failed to export rules");
+
+        let mut imported = parser::new_tes3_parser();
+        imported
+            .import_rules_json(&json)
+            .expect("Failed to import rules");
+        assert_eq!(3, imported.order_rules.len());
+        assert_eq!(4, imported.warning_rules.len());
+        assert_eq!(
+            json,
+            imported
+                .export_rules_json()
+                .expect("Failed to export rules")
+        );
+    }
+
+    #[test]
+    fn test_rules_json_schema() {
+        init();
+
+        // one of each rule
+        let input = "[Order]\na.esp\nb.esp\n[NearStart 1]\nc.esp\n[NearEnd]\nd.esp\n[Pin 2]\ne.esp\n[Remove]\nb.esp\na.esp\n[Note message]\n[ALL a.esp [NOT b.esp]]\n[Conflict]\na.esp\n[ANY b.esp c.esp]\n[Requires]\na.esp\nb.esp\n[Patch]\nc.esp\nd.esp";
+        let mut parser = parser::new_tes3_parser();
+        add_rules(&mut parser, &input.to_lowercase()).expect("Failed to parse rules");
+        assert_eq!(5, parser.order_rules.len());
+        assert_eq!(4, parser.warning_rules.len());
+
+        // the serialized format must not change without bumping the schema version
+        let json = parser.export_rules_json().expect("Failed to export rules");
+        let expected = std::fs::read_to_string("./tests/plox/rules_schema.json")
+            .expect("Failed to read golden file");
+        assert_eq!(expected.trim_end(), json);
+
+        // other schema versions are rejected
+        let other = json.replacen(
+            &format!("\"version\": {}", RULES_SCHEMA_VERSION),
+            &format!("\"version\": {}", RULES_SCHEMA_VERSION + 1),
+            1,
+        );
+        assert!(parser::new_tes3_parser().import_rules_json(&other).is_err());
+    }
+
     ////////////////////////////////////////////////////////////////////////
     // NEARSTART
 
@@ -214,6 +1412,52 @@ mod unit_tests {
         assert_eq!(1, rule.expressions.len());
     }
 
+    #[test]
+    fn test_note_indented_message() {
+        init();
+
+        let inputs = [
+            ("[Note]\n message line\nPlugin.archive", "message line"),
+            ("[Note]\n\tmessage line\nPlugin.archive", "message line"),
+            (
+                "[Note]\n  first line\n\tsecond line  \n   third\nPlugin.archive",
+                "first line second line third",
+            ),
+            (
+                "[Note] ; a comment\n message line\n\nPlugin.archive",
+                "message line",
+            ),
+        ];
+
+        for (input, message) in inputs {
+            let input = input.to_lowercase();
+            let rules = parser::new_cyberpunk_parser()
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rule")
+                .into_iter()
+                .filter_map(note)
+                .collect::<Vec<_>>();
+            assert_eq!(1, rules.len());
+
+            let n = rules.first().expect("No rules found");
+            assert_eq!(message, n.get_comment());
+            assert_eq!(1, n.expressions.len());
+            assert!(is_atomic(&n.expressions[0], "plugin.archive"));
+        }
+
+        // indented lines after the first expression are part of the body
+        let input = "[Note]\n message\n[ANY a.archive\n b.archive]".to_lowercase();
+        let rules = parser::new_cyberpunk_parser()
+            .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+            .expect("Failed to parse rule")
+            .into_iter()
+            .filter_map(note)
+            .collect::<Vec<_>>();
+        assert_eq!(1, rules.len());
+        assert_eq!("message", rules[0].get_comment());
+        assert_eq!(1, rules[0].expressions.len());
+    }
+
     ////////////////////////////////////////////////////////////////////////
     // CONFLICT
 
@@ -335,6 +1579,95 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_requires_alternatives() {
+        init();
+
+        // the dependency may be a list of alternatives
+        let inputs = [
+            "[Requires] a.esp [any b.esp c.esp]",
+            "[Requires]\na.esp\n[any b.esp\nc.esp]",
+        ];
+
+        for input in inputs {
+            let rules = parser::new_tes3_parser()
+                .parse_rules_from_reader(Cursor::new(input.as_bytes()))
+                .expect("Failed to parse rule")
+                .into_iter()
+                .filter_map(requires)
+                .collect::<Vec<_>>();
+
+            assert_eq!(1, rules.len());
+            let rule = rules.first().expect("No rules found");
+            assert!(is_atomic(rule.expression_a.as_ref().unwrap(), "a.esp"));
+            assert!(matches!(rule.expression_b, Some(Expression::ANY(_))));
+
+            // A requires B or C
+            let mods = |names: &[&str]| {
+                names
+                    .iter()
+                    .map(|n| PluginData::new(n.to_string(), 0))
+                    .collect::<Vec<_>>()
+            };
+            assert!(rule.clone().eval(&mods(&["a.esp"])));
+            assert!(!rule.clone().eval(&mods(&["a.esp", "b.esp"])));
+            assert!(!rule.clone().eval(&mods(&["a.esp", "c.esp"])));
+            assert!(!rule.clone().eval(&mods(&["b.esp"])));
+        }
+
+        // a dependency is required and alternatives must be wrapped in [ANY]
+        for input in ["[Requires] a.esp", "[Requires] a.esp b.esp c.esp"] {
+            let (rules, errors) = parser::new_tes3_parser()
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to read rules");
+            assert!(rules.is_empty());
+            assert_eq!(1, errors.len());
+        }
+        let (_, errors) = parser::new_tes3_parser()
+            .parse_rules_with_errors(Cursor::new("[Requires] a.esp b.esp c.esp".as_bytes()))
+            .expect("Failed to read rules");
+        assert!(errors[0].to_string().contains("[ANY]"));
+    }
+
+    #[test]
+    fn test_self_referential_rules() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        for input in [
+            "[Requires] a.esp a.esp",
+            "[Requires]\n[all a.esp [not b.esp]]\n[all a.esp [not b.esp]]",
+            "[Patch] a.esp a.esp",
+            "[Patch]\n[any a.esp b.esp]\n[any a.esp b.esp]",
+        ] {
+            let (rules, errors) = parser
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to read rules");
+            assert!(rules.is_empty(), "{}", input);
+            assert_eq!(1, errors.len());
+            assert!(matches!(
+                errors[0].error,
+                error::ParseError::MalformedRule(_)
+            ));
+        }
+
+        // structurally different expressions are fine
+        for input in [
+            "[Requires] a.esp b.esp",
+            "[Patch]\n[any a.esp b.esp]\n[any b.esp a.esp]",
+        ] {
+            let (rules, errors) = parser
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to read rules");
+            assert_eq!(1, rules.len());
+            assert!(errors.is_empty());
+        }
+
+        let a: Expression = expressions::Atomic::from("a.esp").into();
+        assert_eq!(a, expressions::Atomic::from("a.esp").into());
+        assert_ne!(a, expressions::Atomic::from("b.esp").into());
+    }
+
     ////////////////////////////////////////////////////////////////////////
     // PATCH
 
@@ -384,6 +1717,79 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_requires_version() {
+        init();
+
+        let inputs = [
+            "[RequiresVersion >=0.48 message] a.esp",
+            "[RequiresVersion >=0.48 message]\na.esp",
+        ];
+
+        for input in inputs {
+            let reader = Cursor::new(input.to_lowercase().into_bytes());
+            let rules = parser::new_tes3_parser()
+                .parse_rules_from_reader(reader)
+                .expect("Failed to parse rule")
+                .into_iter()
+                .filter_map(requires_version)
+                .collect::<Vec<_>>();
+
+            assert_eq!(1, rules.len(), "{}", input);
+            let n = rules.first().expect("No rules found");
+            assert_eq!("message", n.get_comment());
+            assert_eq!(">=0.48", n.version);
+            assert!(is_atomic(n.expression.as_ref().unwrap(), "a.esp"));
+        }
+
+        // an invalid version requirement is malformed
+        let reader = Cursor::new("[requiresversion abc]\na.esp".as_bytes());
+        let rules = parser::new_tes3_parser()
+            .parse_rules_from_reader(reader)
+            .expect("Failed to parse rules")
+            .into_iter()
+            .filter_map(requires_version)
+            .collect::<Vec<_>>();
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn test_blacklist() {
+        init();
+
+        let inputs = [
+            "[Blacklist outdated] a.esp",
+            "[Blacklist outdated]\na.esp",
+            "[Blacklist outdated]a.esp; with a comment",
+        ];
+
+        for input in inputs {
+            let reader = Cursor::new(input.as_bytes());
+            let rules = parser::new_tes3_parser()
+                .parse_rules_from_reader(reader)
+                .expect("Failed to parse rule")
+                .into_iter()
+                .filter_map(blacklist)
+                .collect::<Vec<_>>();
+
+            assert_eq!(1, rules.len());
+            let n = rules.first().expect("No rules found");
+            assert_eq!("outdated", n.get_comment());
+            assert_eq!(1, n.expressions.len());
+            assert!(is_atomic(&n.expressions[0], "a.esp"));
+        }
+
+        // only plugin names can be blacklisted
+        let reader = Cursor::new("[Blacklist outdated]\n[ANY a.esp b.esp]".as_bytes());
+        let rules = parser::new_tes3_parser()
+            .parse_rules_from_reader(reader)
+            .expect("Failed to parse rules")
+            .into_iter()
+            .filter_map(blacklist)
+            .collect::<Vec<_>>();
+        assert!(rules.is_empty());
+    }
+
     ////////////////////////////////////////////////////////////////////////
     // EXPRESSIONS
     ////////////////////////////////////////////////////////////////////////
@@ -462,6 +1868,51 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_empty_all_any() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        for input in ["[all]", "[any]", "[all ]", "[any\t]"] {
+            assert!(parser.parse_expression(input, true).is_err());
+        }
+
+        // also inside rules
+        for input in ["[Note]\n[all ]", "[Requires]\na.esp\n[any ]"] {
+            let (rules, errors) = parser
+                .parse_rules_with_errors(Cursor::new(input.as_bytes()))
+                .expect("Failed to read rules");
+            assert!(rules.is_empty());
+            assert_eq!(1, errors.len());
+        }
+    }
+
+    // NOT
+    #[test]
+    fn test_not_expr() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+
+        // a single child expression is fine
+        for input in ["[not a.esp]", "[not [any a.esp b.esp]]"] {
+            let expr = parser
+                .parse_expression(input, true)
+                .expect("No expressions parsed");
+            assert!(matches!(expr, Expression::NOT(_)));
+        }
+
+        // more than one child expression is an error
+        for input in ["[not a.esp b.esp]", "[not a.esp [any b.esp c.esp]]"] {
+            assert!(parser.parse_expression(input, true).is_err());
+        }
+
+        // DESC checks the same
+        assert!(parser
+            .parse_expression("[desc /regex/ a.esp b.esp]", true)
+            .is_err());
+    }
+
     // DESC
     #[test]
     fn test_desc_expr() {
@@ -496,6 +1947,57 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_desc_expr_eval() {
+        init();
+
+        let parser = parser::new_tes3_parser();
+        let mods = ["a.esp", "b.esp"]
+            .iter()
+            .zip(["a plugin for vampires", "a plugin for werewolves"])
+            .map(|(name, desc)| {
+                let mut data = PluginData::new(name.to_string(), 0);
+                data.description = Some(desc.to_string());
+                data
+            })
+            .collect::<Vec<_>>();
+        let context = EvalContext::from_plugins(&mods);
+
+        // positive match
+        {
+            let expr = parser
+                .parse_expression("[desc /vampire/ a.esp]", true)
+                .expect("No expressions parsed");
+            assert!(expr.eval(&context).is_some());
+
+            let expr = parser
+                .parse_expression("[desc /vampire/ b.esp]", true)
+                .expect("No expressions parsed");
+            assert!(expr.eval(&context).is_none());
+        }
+
+        // negated match
+        {
+            let expr = parser
+                .parse_expression("[desc !/vampire/ a.esp]", true)
+                .expect("No expressions parsed");
+            assert!(expr.eval(&context).is_none());
+
+            let expr = parser
+                .parse_expression("[desc !/vampire/ b.esp]", true)
+                .expect("No expressions parsed");
+            assert!(expr.eval(&context).is_some());
+        }
+
+        // negated match of an absent plugin
+        {
+            let expr = parser
+                .parse_expression("[desc !/vampire/ x.esp]", true)
+                .expect("No expressions parsed");
+            assert!(expr.eval(&context).is_none());
+        }
+    }
+
     fn test_desc(input: &str, expected: Vec<&str>) {
         let parser = parser::new_cyberpunk_parser();
 
@@ -636,6 +2138,29 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_ver_expr_malformed() {
+        init();
+
+        let parser = parser::new_cyberpunk_parser();
+
+        let inputs = [
+            "[VER ~ 1.51 a.archive]",
+            "[VER <> 1.51 a.archive]",
+            "[VER 1.51 a.archive]",
+            "[VER < a.archive]",
+        ];
+
+        for input in inputs {
+            let input = input.to_lowercase();
+            assert!(
+                parser.parse_expression(input.as_str(), true).is_err(),
+                "{} should not parse",
+                input
+            );
+        }
+    }
+
     fn test_ver(input: &str, expected: Vec<&str>) {
         let parser = parser::new_cyberpunk_parser();
 
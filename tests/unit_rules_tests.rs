@@ -1,6 +1,12 @@
 #[cfg(test)]
 mod unit_tests {
-    use plox::{expressions::*, rules::*, sorter::new_stable_sorter, ESupportedGame, PluginData};
+    use plox::{
+        error::TopoError,
+        expressions::*,
+        rules::*,
+        sorter::{new_stable_sorter, new_unstable_sorter},
+        ESupportedGame, PluginData,
+    };
 
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -61,6 +67,68 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_ordered_notes() {
+        init();
+
+        let order = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        // an ordered [Note] fires when a loads after b
+        {
+            let mut rule = Note::new_ordered("a after b".into(), A, B);
+            assert!(rule.eval_ordered(&order(&[C, B, A])));
+            assert_eq!(vec![A, B], rule.get_plugins());
+        }
+
+        // but not in the right order, or when one is missing
+        {
+            let mut rule = Note::new_ordered("a after b".into(), A, B);
+            assert!(!rule.eval_ordered(&order(&[A, C, B])));
+            assert!(!rule.eval_ordered(&order(&[C, A])));
+            assert!(rule.get_plugins().is_empty());
+        }
+
+        // presence alone does not fire an ordered note
+        {
+            let mut rule = Note::new_ordered("a after b".into(), A, B);
+            assert!(!rule.eval(&get_mods()));
+        }
+
+        // presence based notes ignore the order
+        {
+            let mut rule = EWarningRule::from(Note::new("".into(), &[e(A)]));
+            assert!(rule.eval_ordered(&order(&[B, "A.esp"])));
+            let mut rule = EWarningRule::from(Note::new("".into(), &[e(X)]));
+            assert!(!rule.eval_ordered(&order(&[B, A])));
+        }
+    }
+
+    #[test]
+    fn test_comment_placeholders() {
+        init();
+
+        // test that %1 is replaced with the matched plugin
+        {
+            let mut rule = Note::new("conflict with %1".into(), &[e(A), e(X)]);
+            assert!(rule.eval(&get_mods()));
+            assert_eq!("conflict with a.esp", rule.get_comment());
+        }
+
+        // test that placeholders follow the order of the matched plugins
+        {
+            let mut rule = Conflict::new("%2 conflicts with %1".into(), &[e(A), e(B)]);
+            assert!(rule.eval(&get_mods()));
+            assert_eq!("b.esp conflicts with a.esp", rule.get_comment());
+        }
+
+        // test that placeholders without a match are kept
+        {
+            let mut rule = Note::new("%1 and %2".into(), &[e(A)]);
+            assert!(rule.eval(&get_mods()));
+            assert_eq!("a.esp and %2", rule.get_comment());
+        }
+    }
+
     #[test]
     fn test_conflicts() {
         init();
@@ -96,6 +164,30 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_conflict_multi() {
+        init();
+
+        // three mutually conflicting plugins, two of them installed
+        {
+            let mut rule = Conflict::new("".into(), &[e(A), e(X), e(B)]);
+            assert!(rule.eval(&get_mods()));
+            assert_eq!(vec![A.to_string(), B.to_string()], rule.plugins);
+            assert_eq!(
+                vec![vec![A.to_string()], vec![B.to_string()]],
+                rule.conflicts
+            );
+        }
+
+        // only one installed: nothing collided
+        {
+            let mut rule = Conflict::new("".into(), &[e(A), e(X), e(Y)]);
+            assert!(!rule.eval(&get_mods()));
+            assert!(rule.plugins.is_empty());
+            assert!(rule.conflicts.is_empty());
+        }
+    }
+
     #[test]
     fn test_requires() {
         init();
@@ -125,6 +217,81 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_requires_ver() {
+        init();
+
+        let mods_with_b = |version: &str| {
+            get_mods()
+                .into_iter()
+                .map(|mut p| {
+                    if p.name == B {
+                        p.version = Some(semver::Version::parse(version).unwrap());
+                    }
+                    p
+                })
+                .collect::<Vec<_>>()
+        };
+        // b at version >= 2
+        let at_least_2: Expression = ANY::new(vec![
+            VER::new(Atomic::from(B), EVerOperator::Equal, "2.0.0".into()).into(),
+            VER::new(Atomic::from(B), EVerOperator::Greater, "2.0.0".into()).into(),
+        ])
+        .into();
+
+        // test that [Requires] evaluates as true when the dependency is too old
+        {
+            let mut rule = Requires::new("".into(), e(A), at_least_2.clone());
+            assert!(rule.eval(&mods_with_b("1.5.0")));
+            assert_eq!(vec![A.to_string()], rule.get_plugins());
+            // the dependency is installed, just not at the right version
+            assert!(rule.missing.is_empty());
+        }
+
+        // test that [Requires] evaluates as false when the dependency is new enough
+        {
+            let mut rule = Requires::new("".into(), e(A), at_least_2.clone());
+            assert!(!rule.eval(&mods_with_b("2.0.0")));
+            let mut rule = Requires::new("".into(), e(A), at_least_2.clone());
+            assert!(!rule.eval(&mods_with_b("2.1.0")));
+        }
+
+        // test that [Requires] evaluates as true when the dependency is missing
+        {
+            let mut rule = Requires::new("".into(), e(A), at_least_2.clone());
+            let mods = get_mods()
+                .into_iter()
+                .filter(|p| p.name != B)
+                .collect::<Vec<_>>();
+            assert!(rule.eval(&mods));
+            assert_eq!(vec![B.to_string()], rule.missing);
+        }
+
+        // test that [Requires] evaluates as true when the dependency has no version
+        {
+            let mut rule = Requires::new("".into(), e(A), at_least_2);
+            assert!(rule.eval(&get_mods()));
+        }
+    }
+
+    #[test]
+    fn test_check_warnings() {
+        init();
+
+        let mods = [A, B, C].iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        let rules: Vec<EWarningRule> = vec![
+            Requires::new("a requires x".into(), e(A), e(X)).into(),
+            Requires::new("a requires b".into(), e(A), e(B)).into(),
+            Note::new("not installed".into(), &[e(Y)]).into(),
+        ];
+
+        let warnings = plox::check_warnings(&mods, &rules);
+        assert_eq!(1, warnings.len());
+        assert_eq!("Requires", warnings[0].get_rule_name());
+        assert_eq!("a requires x", warnings[0].get_comment());
+        assert_eq!(vec![A.to_string()], warnings[0].get_plugins());
+    }
+
     #[test]
     fn test_patch() {
         init();
@@ -154,6 +321,117 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_requires_version() {
+        init();
+
+        let mods_with_version = |version: &str| {
+            get_mods()
+                .into_iter()
+                .map(|mut p| {
+                    p.game_version = Some(semver::Version::parse(version).unwrap());
+                    p
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // test that [RequiresVersion] evaluates as false when the game version satisfies the requirement
+        {
+            let mut rule = RequiresVersion::new("".into(), e(A), ">=0.48".into());
+            assert!(!rule.eval(&mods_with_version("0.48.0")));
+        }
+
+        // test that [RequiresVersion] evaluates as true when the game version violates the requirement
+        {
+            let mut rule = RequiresVersion::new("".into(), e(A), ">=0.48".into());
+            assert!(rule.eval(&mods_with_version("0.47.0")));
+            assert_eq!(vec![A.to_string()], rule.get_plugins());
+        }
+
+        // test that [RequiresVersion] evaluates as false when the plugin is missing
+        {
+            let mut rule = RequiresVersion::new("".into(), e(X), ">=0.48".into());
+            assert!(!rule.eval(&mods_with_version("0.47.0")));
+        }
+
+        // test that [RequiresVersion] evaluates as false when the game version is unknown
+        {
+            let mut rule = RequiresVersion::new("".into(), e(A), ">=0.48".into());
+            assert!(!rule.eval(&get_mods()));
+        }
+    }
+
+    #[test]
+    fn test_blacklist() {
+        init();
+
+        // test that [Blacklist] evaluates as true when the plugin is present
+        {
+            let mut rule = Blacklist::new("outdated".into(), &[e(A)]);
+            assert!(rule.eval(&get_mods()));
+            assert_eq!(vec![A.to_string()], rule.get_plugins());
+            assert_eq!("outdated", rule.get_comment());
+        }
+
+        // test that [Blacklist] evaluates as false when the plugin is missing
+        {
+            let mut rule = Blacklist::new("outdated".into(), &[e(X)]);
+            assert!(!rule.eval(&get_mods()));
+            assert!(rule.get_plugins().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_mixed_case_mods() {
+        init();
+
+        let mods = ["Tamriel_Data.esm", "OAAB_Data.esm", "My Mod.ESP"]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+
+        // lowercased rule atoms match the installed names, which keep their case
+        {
+            let mut rule = Note::new("".into(), &[e("tamriel_data.esm")]);
+            assert!(rule.eval(&mods));
+            assert_eq!(vec!["Tamriel_Data.esm".to_owned()], rule.get_plugins());
+        }
+        {
+            let mut rule = Note::new("".into(), &[e("my mod.esp")]);
+            assert!(rule.eval(&mods));
+        }
+        // wildcards too
+        {
+            let mut rule = Note::new("".into(), &[e("*_data.esm")]);
+            assert!(rule.eval(&mods));
+            assert_eq!(
+                vec!["Tamriel_Data.esm".to_owned(), "OAAB_Data.esm".to_owned()],
+                rule.get_plugins()
+            );
+        }
+        {
+            let mut rule = Requires::new("".into(), e("oaab_data.esm"), e("tamriel_data.esm"));
+            assert!(!rule.eval(&mods));
+        }
+    }
+
+    #[test]
+    fn test_detect_near_conflicts() {
+        init();
+
+        let order_rules: Vec<EOrderRule> = vec![
+            NearStart::new(vec![A.to_string(), B.to_string()]).into(),
+            NearEnd::new(vec![C.to_string(), A.to_string()]).into(),
+            NearStart::new(vec![A.to_string()]).into(),
+            NearEnd::new(vec![D.to_string()]).into(),
+        ];
+
+        assert_eq!(
+            vec![A.to_string()],
+            plox::sorter::detect_near_conflicts(&order_rules)
+        );
+    }
+
     #[test]
     fn test_nearstart() {
         // check one gets sorted at the start
@@ -239,6 +517,284 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_nearstart_with_order() {
+        // check that a nearstart mod is pulled before unconstrained mods but after its order edges
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let nearstart = NearStart::new(vec![D.to_string()]);
+            let order = Order::new(vec![B.to_string(), D.to_string()]);
+            let mods = get_mods();
+            let order_rules: Vec<EOrderRule> = vec![nearstart.into(), order.into()];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[]) {
+                Ok(result) => {
+                    let pos = |name: &str| result.iter().position(|f| f == name).unwrap();
+                    assert!(pos(B) < pos(D));
+                    for unconstrained in [A, C, E, F] {
+                        assert!(pos(D) < pos(unconstrained));
+                    }
+                }
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_near_priority() {
+        init();
+
+        // check that the nearstart rule with the higher priority lands first, regardless of rule order
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let low = NearStart::new(vec![B.to_string()]);
+            let high = NearStart::new(vec![D.to_string()]).with_priority(10);
+            let order_rules: Vec<EOrderRule> = vec![low.into(), high.into()];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &get_mods(), &order_rules, &[]) {
+                Ok(result) => {
+                    assert_eq!(D, result[0]);
+                    assert_eq!(B, result[1]);
+                }
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+
+        // check that the nearend rule with the higher priority lands last
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let low = NearEnd::new(vec![B.to_string()]).with_priority(-1);
+            let high = NearEnd::new(vec![D.to_string()]);
+            let order_rules: Vec<EOrderRule> = vec![low.into(), high.into()];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &get_mods(), &order_rules, &[]) {
+                Ok(result) => {
+                    assert_eq!(D, result[5]);
+                    assert_eq!(B, result[4]);
+                }
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearend_with_order() {
+        // check that a nearend mod is pushed after unconstrained mods but before its order edges
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let nearend = NearEnd::new(vec![B.to_string()]);
+            let order = Order::new(vec![B.to_string(), D.to_string()]);
+            let mods = get_mods();
+            let order_rules: Vec<EOrderRule> = vec![nearend.into(), order.into()];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[]) {
+                Ok(result) => {
+                    let pos = |name: &str| result.iter().position(|f| f == name).unwrap();
+                    assert!(pos(B) < pos(D));
+                    for unconstrained in [A, C, E, F] {
+                        assert!(pos(unconstrained) < pos(B));
+                    }
+                }
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_pin() {
+        init();
+
+        // check that a pinned mod ends up at its index, even against a nearstart rule
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let pin = Pin::new(E.to_string(), 0);
+            let nearstart = NearStart::new(vec![B.to_string()]);
+            let mods = get_mods();
+            let order_rules: Vec<EOrderRule> = vec![pin.into(), nearstart.into()];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[]) {
+                Ok(result) => {
+                    assert_eq!(E, result[0]);
+                    assert_eq!(B, result[1]);
+                    assert_eq!(mods.len(), result.len());
+                }
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+
+        // check that an index past the end puts the mod last
+        {
+            let pin = Pin::new(A.to_string(), 100);
+            let order_rules: Vec<EOrderRule> = vec![pin.into()];
+
+            match new_stable_sorter().topo_sort(
+                ESupportedGame::Morrowind,
+                &get_mods(),
+                &order_rules,
+                &[],
+            ) {
+                Ok(result) => assert_eq!(Some(&A.to_string()), result.last()),
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_pin_conflict() {
+        init();
+
+        // check that two mods pinned to the same index are an error
+        for mut sorter in [new_stable_sorter(), new_unstable_sorter()] {
+            let order_rules: Vec<EOrderRule> = vec![
+                Pin::new(A.to_string(), 1).into(),
+                Pin::new(C.to_string(), 1).into(),
+            ];
+
+            match sorter.topo_sort(ESupportedGame::Morrowind, &get_mods(), &order_rules, &[]) {
+                Err(TopoError::PinConflict(index, a, c)) => {
+                    assert_eq!(1, index);
+                    assert_eq!(A, a);
+                    assert_eq!(C, c);
+                }
+                other => panic!("Expected a pin conflict, got {:?}", other),
+            }
+        }
+
+        // pins of mods that are not installed don't conflict
+        {
+            let order_rules: Vec<EOrderRule> = vec![
+                Pin::new(A.to_string(), 1).into(),
+                Pin::new(X.to_string(), 1).into(),
+            ];
+            assert!(new_stable_sorter()
+                .topo_sort(ESupportedGame::Morrowind, &get_mods(), &order_rules, &[])
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_patch_order() {
+        init();
+
+        // F is a patch for A
+        let warn_rules: Vec<EWarningRule> = vec![Patch::new("".into(), e(F), e(A)).into()];
+        let order_rules: Vec<EOrderRule> =
+            vec![Order::new(vec![B.to_string(), C.to_string()]).into()];
+
+        // both installed: the original loads before the patch
+        {
+            let mods = vec![
+                PluginData::new(F.to_string(), 0),
+                PluginData::new(A.to_string(), 0),
+            ];
+            let data = plox::sorter::get_graph_data(&mods, &order_rules, &warn_rules);
+            assert_eq!(vec![(1, 0)], data.edges);
+
+            match new_stable_sorter().topo_sort(
+                ESupportedGame::Morrowind,
+                &mods,
+                &order_rules,
+                &warn_rules,
+            ) {
+                Ok(result) => assert_eq!(vec![A.to_string(), F.to_string()], result),
+                Err(e) => panic!("Error: {}", e),
+            }
+        }
+
+        // only the patch installed: no edge
+        {
+            let mods = vec![
+                PluginData::new(F.to_string(), 0),
+                PluginData::new(B.to_string(), 0),
+            ];
+            let data = plox::sorter::get_graph_data(&mods, &order_rules, &warn_rules);
+            assert!(data.edges.is_empty());
+        }
+
+        // non-atomic expressions are skipped
+        {
+            let patch: EWarningRule =
+                Patch::new("".into(), e(F), ANY::new(vec![e(A), e(B)]).into()).into();
+            assert!(plox::get_ordering_from_patch_rules(&[patch]).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_order_guard() {
+        init();
+
+        // A before B, but only if E is installed
+        let order_rules: Vec<EOrderRule> = vec![Order::new(vec![A.to_string(), B.to_string()])
+            .with_guard(e(E))
+            .into()];
+
+        // guard plugin absent: no edge
+        {
+            let mods = vec![
+                PluginData::new(B.to_string(), 0),
+                PluginData::new(A.to_string(), 0),
+            ];
+            let data = plox::sorter::get_graph_data(&mods, &order_rules, &[]);
+            assert!(data.edges.is_empty());
+        }
+
+        // guard plugin present: A loads before B
+        {
+            let mods = vec![
+                PluginData::new(B.to_string(), 0),
+                PluginData::new(A.to_string(), 0),
+                PluginData::new(E.to_string(), 0),
+            ];
+            let data = plox::sorter::get_graph_data(&mods, &order_rules, &[]);
+            assert_eq!(vec![(1, 0)], data.edges);
+        }
+
+        // unguarded orders always apply
+        assert_eq!(
+            1,
+            plox::get_ordering_from_order_rules_for(
+                &[Order::new(vec![A.to_string(), B.to_string()]).into()],
+                &[]
+            )
+            .len()
+        );
+    }
+
+    #[test]
+    fn test_order_soft() {
+        init();
+
+        // soft A before B contradicts the hard B before A
+        let order_rules: Vec<EOrderRule> = vec![
+            Order::new(vec![A.to_string(), B.to_string()])
+                .with_soft(true)
+                .into(),
+            Order::new(vec![B.to_string(), A.to_string()]).into(),
+        ];
+        let mods = vec![
+            PluginData::new(A.to_string(), 0),
+            PluginData::new(B.to_string(), 0),
+        ];
+
+        // soft orders add no edges
+        let data = plox::sorter::get_graph_data(&mods, &order_rules, &[]);
+        assert_eq!(vec![(1, 0)], data.edges);
+
+        // the sort succeeds and the soft order is reported as violated
+        for mut sorter in [new_unstable_sorter(), new_stable_sorter()] {
+            let result = sorter
+                .topo_sort(ESupportedGame::Openmw, &mods, &order_rules, &[])
+                .expect("soft orders never cause a cycle");
+            assert_eq!(vec![B, A], result);
+            assert_eq!(
+                vec![(A.to_string(), B.to_string())],
+                plox::find_soft_order_violations(&result, &order_rules)
+            );
+        }
+
+        // a satisfied soft order is not reported
+        assert!(plox::find_soft_order_violations(
+            &[A.to_string(), B.to_string()],
+            &order_rules[..1]
+        )
+        .is_empty());
+    }
+
     #[test]
     fn test_order_case() {
         {
@@ -277,6 +833,234 @@ mod unit_tests {
         }
     }
 
+    #[test]
+    fn test_sort_with_priority() {
+        init();
+
+        // C must load before A, B and D are unconstrained
+        let mods = [A, B, C, D]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+        let order_rules: Vec<EOrderRule> = vec![Order::from(C, A).into()];
+
+        let result = new_stable_sorter()
+            .topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[])
+            .expect("could not sort");
+        assert_eq!(vec![C, A, B, D], result);
+
+        // D is preferred before B, A is preferred first but the rule wins
+        let priority = [A, D, B].map(String::from);
+        let result = new_stable_sorter()
+            .topo_sort_with_priority(
+                ESupportedGame::Morrowind,
+                &mods,
+                &order_rules,
+                &[],
+                &priority,
+            )
+            .expect("could not sort");
+        assert_eq!(vec![C, A, D, B], result);
+    }
+
+    #[test]
+    fn test_explain_position() {
+        init();
+
+        // A -> B -> C, D is unconstrained
+        let order = [A, D, B, C].map(String::from);
+        let order_rules: Vec<EOrderRule> = vec![Order::from(B, C).into(), Order::from(A, B).into()];
+
+        assert_eq!(
+            vec![format!("{} -> {}", A, B), format!("{} -> {}", B, C)],
+            plox::sorter::explain_position(C, &order, &order_rules)
+        );
+        assert_eq!(
+            vec![format!("{} -> {}", A, B), format!("{} -> {}", B, C)],
+            plox::sorter::explain_position(B, &order, &order_rules)
+        );
+        assert!(plox::sorter::explain_position(D, &order, &order_rules).is_empty());
+        assert!(plox::sorter::explain_position(X, &order, &order_rules).is_empty());
+    }
+
+    #[test]
+    fn test_dangling_order_refs() {
+        init();
+
+        let mods = [A, B, C].map(String::from);
+        let order = [(A, B), (B, X), (C, "*.esp")].map(|(a, b)| (a.to_owned(), b.to_owned()));
+
+        assert_eq!(
+            vec![(B.to_owned(), X.to_owned())],
+            plox::sorter::dangling_order_refs(&mods, &order)
+        );
+    }
+
+    #[test]
+    fn test_suggest_plugin() {
+        init();
+
+        let installed = ["Morrowind.esm", "Tamriel_Data.esm", "OAAB_Data.esm"].map(String::from);
+
+        assert_eq!(
+            Some("Tamriel_Data.esm".to_owned()),
+            plox::sorter::suggest_plugin("tamrel_data.esm", &installed)
+        );
+        assert_eq!(
+            Some("OAAB_Data.esm".to_owned()),
+            plox::sorter::suggest_plugin("oaab-data.esp", &installed)
+        );
+        assert_eq!(
+            None,
+            plox::sorter::suggest_plugin("better bodies.esp", &installed)
+        );
+        assert_eq!(None, plox::sorter::suggest_plugin("a.esp", &[]));
+    }
+
+    #[test]
+    fn test_transitive_order() {
+        init();
+
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+
+        let closure = plox::sorter::transitive_order(&[pair(A, B), pair(B, C)])
+            .expect("could not compute closure");
+        assert_eq!(3, closure.len());
+        assert!(closure.contains(&pair(A, B)));
+        assert!(closure.contains(&pair(B, C)));
+        assert!(closure.contains(&pair(A, C)));
+
+        // cycles are an error
+        match plox::sorter::transitive_order(&[pair(A, B), pair(B, C), pair(C, A)]) {
+            Err(TopoError::Cycle(cycle)) => assert_eq!(3, cycle.len()),
+            _ => panic!("expected a cycle"),
+        }
+    }
+
+    #[test]
+    fn test_order_components() {
+        init();
+
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        // two disjoint chains: a -> b -> c and d -> e
+        let mods = names(&[A, D, B, E, C]);
+        let order = [pair(A, B), pair(D, E), pair(B, C)];
+        assert_eq!(
+            vec![names(&[A, B, C]), names(&[D, E])],
+            plox::sorter::order_components(&mods, &order)
+        );
+
+        // mods without order pairs are on their own, pairs with missing mods are ignored
+        let mods = names(&[F, A, B, "C.esp"]);
+        let order = [pair(A, X), pair(B, C)];
+        assert_eq!(
+            vec![names(&[F]), names(&[A]), names(&[B, "C.esp"])],
+            plox::sorter::order_components(&mods, &order)
+        );
+    }
+
+    #[test]
+    fn test_find_redundant_orders() {
+        init();
+
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+
+        // a -> c is implied by a -> b -> c
+        assert_eq!(
+            vec![pair(A, C)],
+            plox::sorter::find_redundant_orders(&[pair(A, B), pair(B, C), pair(A, C)])
+        );
+
+        // a direct edge that is the only path is never redundant
+        assert!(
+            plox::sorter::find_redundant_orders(&[pair(A, B), pair(B, C), pair(A, D)]).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_find_direct_contradictions() {
+        init();
+
+        let pair = |a: &str, b: &str| (a.to_owned(), b.to_owned());
+
+        // a -> b and b -> a contradict each other, the pair is reported once
+        assert_eq!(
+            vec![pair(A, B)],
+            plox::sorter::find_direct_contradictions(&[pair(A, B), pair(C, D), pair(B, A)])
+        );
+        assert!(plox::sorter::find_direct_contradictions(&[pair(A, B), pair(B, C)]).is_empty());
+
+        // the sort fails with the contradicting pair instead of a generic cycle
+        let mods = [A, B, C]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+        let order_rules: Vec<EOrderRule> = vec![Order::from(A, B).into(), Order::from(B, A).into()];
+        for mut sorter in [new_unstable_sorter(), new_stable_sorter()] {
+            let result = sorter.topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[]);
+            assert!(matches!(result, Err(TopoError::Contradiction(_, _))));
+        }
+    }
+
+    #[test]
+    fn test_order_stability() {
+        init();
+
+        // E, X and Y are unconstrained, C loads before A and D before B
+        let mods = [E, A, X, B, Y, C, D]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+        let order_rules: Vec<EOrderRule> = vec![Order::from(C, A).into(), Order::from(D, B).into()];
+
+        let unconstrained = |result: &[String]| {
+            result
+                .iter()
+                .filter(|f| [E, X, Y].contains(&f.as_str()))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let result = new_stable_sorter()
+            .topo_sort(ESupportedGame::Morrowind, &mods, &order_rules, &[])
+            .expect("could not sort");
+        assert!(plox::check_order(&result, &order_rules));
+        assert_eq!(vec![E, X, Y], unconstrained(&result));
+
+        // repeated sorts don't reshuffle anything
+        let mut current = result;
+        for _ in 0..3 {
+            let plugins = current
+                .iter()
+                .map(|e| PluginData::new(e.to_string(), 0))
+                .collect::<Vec<_>>();
+            let result = new_stable_sorter()
+                .topo_sort(ESupportedGame::Morrowind, &plugins, &order_rules, &[])
+                .expect("could not sort");
+            assert_eq!(current, result);
+            current = result;
+        }
+
+        // the greedy order may move a mod past an unrelated mod: a is pulled up to b, past x
+        let mods = [B, X, A]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect::<Vec<_>>();
+        let data = plox::sorter::get_graph_data(&mods, &[Order::from(A, B).into()], &[]);
+        let names = [B, X, A].map(|e| e.to_string());
+        assert_eq!(
+            vec![A, B, X],
+            plox::sorter::order_by_position(
+                &names,
+                &data.edges,
+                &data.index_dict,
+                &data.index_dict_rev
+            )
+        );
+    }
+
     #[test]
     fn test_order() {
         {
@@ -2,7 +2,9 @@
 mod unit_tests {
 
     use plox::{
-        rules::Order,
+        error::TopoError,
+        expressions::{Atomic, EvalContext},
+        rules::{EOrderRule, EWarningRule, Note, Order, TWarningRule},
         sorter::{self, Sorter},
         *,
     };
@@ -56,6 +58,224 @@ mod unit_tests {
         );
     }
 
+    #[test]
+    fn test_cycle_path() {
+        init();
+
+        let order = [
+            Order::from("a", "b").into(),
+            Order::from("b", "c").into(),
+            Order::from("c", "a").into(),
+            Order::from("d", "e").into(),
+        ];
+
+        let mods: Vec<PluginData> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect();
+
+        for mut sorter in [
+            sorter::new_unstable_sorter(),
+            new_stable_full_sorter(),
+            sorter::new_stable_sorter(),
+        ] {
+            match sorter.topo_sort(ESupportedGame::Morrowind, &mods, &order, &[]) {
+                Err(TopoError::Cycle(mut cycle)) => {
+                    assert_eq!(3, cycle.len());
+                    cycle.sort();
+                    assert_eq!(vec!["a", "b", "c"], cycle);
+                }
+                other => panic!("expected a cycle, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ordering_large() {
+        init();
+
+        // 2000 mods in a scrambled order, with rules that chain every 10th mod
+        let n = 2000;
+        let mods: Vec<PluginData> = (0..n)
+            .map(|i| PluginData::new(format!("mod_{}.esp", (i * 7919) % n), 0))
+            .collect();
+        let order: Vec<EOrderRule> = (0..n - 10)
+            .step_by(10)
+            .map(|i| Order::from(&format!("mod_{}.esp", i), &format!("mod_{}.esp", i + 10)).into())
+            .collect();
+
+        let start = std::time::Instant::now();
+        match sorter::new_stable_sorter().topo_sort(ESupportedGame::Morrowind, &mods, &order, &[]) {
+            Ok(result) => {
+                assert_eq!(n, result.len());
+                assert!(check_order(&result, &order), "stable(true) order is wrong");
+            }
+            Err(e) => panic!("Error: {}", e),
+        }
+        log::info!("Sorted {} mods in {:?}", n, start.elapsed());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn test_eval_index_large() {
+        init();
+
+        // 5000 mods and 5000 rules with three atomics each, half of them refer to missing mods
+        let n = 5000;
+        let mods: Vec<PluginData> = (0..n)
+            .map(|i| PluginData::new(format!("mod_{}.esp", i), 0))
+            .collect();
+        let names: Vec<Vec<String>> = (0..n)
+            .map(|i| {
+                [i, i * 3, i * 7]
+                    .iter()
+                    .map(|j| format!("mod_{}.esp", j % (2 * n)))
+                    .collect()
+            })
+            .collect();
+        let rules: Vec<EWarningRule> = names
+            .iter()
+            .map(|rule_names| {
+                let expressions = rule_names
+                    .iter()
+                    .map(|name| Atomic::from(name.as_str()).into())
+                    .collect::<Vec<_>>();
+                Note::new(String::new(), &expressions).into()
+            })
+            .collect();
+
+        // shared index
+        let start = std::time::Instant::now();
+        let context = EvalContext::from_plugins(&mods);
+        let indexed = rules
+            .iter()
+            .map(|r| r.clone().eval_context(&context))
+            .collect::<Vec<_>>();
+        let indexed_time = start.elapsed();
+
+        // linear scan over the mod list per atomic
+        let start = std::time::Instant::now();
+        let scanned = names
+            .iter()
+            .map(|rule_names| {
+                rule_names
+                    .iter()
+                    .any(|name| mods.iter().any(|m| m.name.eq_ignore_ascii_case(name)))
+            })
+            .collect::<Vec<_>>();
+        let scanned_time = start.elapsed();
+
+        assert_eq!(indexed, scanned);
+        assert!(
+            indexed_time < scanned_time,
+            "indexed: {:?}, linear scan: {:?}",
+            indexed_time,
+            scanned_time
+        );
+        log::info!(
+            "Evaluated {} rules against {} mods in {:?} (linear scan: {:?})",
+            n,
+            n,
+            indexed_time,
+            scanned_time
+        );
+    }
+
+    #[test]
+    fn test_plugin_kind() {
+        init();
+
+        for (name, kind) in [
+            ("a.omwgame", EPluginKind::Game),
+            ("A.ESM", EPluginKind::Master),
+            ("a.esl", EPluginKind::Light),
+            ("a.esp", EPluginKind::Plugin),
+            ("a.omwaddon", EPluginKind::Addon),
+            ("a.omwscripts", EPluginKind::Scripts),
+            ("a.archive", EPluginKind::Archive),
+            ("a.txt", EPluginKind::Unknown),
+        ] {
+            assert_eq!(kind, PluginData::new(name.into(), 0).kind());
+        }
+
+        assert!(EPluginKind::Master.is_master());
+        assert!(!EPluginKind::Addon.is_master());
+        assert!(EPluginKind::Addon.is_content());
+        assert!(!EPluginKind::Scripts.is_content());
+    }
+
+    #[test]
+    fn test_format_mod_list() {
+        init();
+
+        let names = ["a.esp", "b c.esp", "d,e.esp"].map(|n| n.to_owned());
+        assert_eq!(
+            "a.esp\nb c.esp\nd,e.esp\n",
+            format_mod_list(&names, EListFormat::Lines)
+        );
+        assert_eq!(
+            "0 a.esp\n1 b c.esp\n2 d,e.esp\n",
+            format_mod_list(&names, EListFormat::Numbered)
+        );
+        assert_eq!(
+            "a.esp\0b c.esp\0d,e.esp\0",
+            format_mod_list(&names, EListFormat::NullDelimited)
+        );
+        assert_eq!(
+            "a.esp,b c.esp,\"d,e.esp\"\n",
+            format_mod_list(&names, EListFormat::Csv)
+        );
+        assert_eq!("", format_mod_list(&[], EListFormat::Lines));
+    }
+
+    #[test]
+    fn test_diff_order() {
+        init();
+
+        let order = [Order::from("d.esp", "b.esp").into()];
+        let mods: Vec<PluginData> = ["a.esp", "b.esp", "c.esp", "d.esp", "e.esp"]
+            .iter()
+            .map(|e| PluginData::new(e.to_string(), 0))
+            .collect();
+        let current = mods.iter().map(|m| m.name.to_owned()).collect::<Vec<_>>();
+        let proposed = sorter::new_stable_sorter()
+            .topo_sort(ESupportedGame::Morrowind, &mods, &order, &[])
+            .expect("rules contain a cycle");
+
+        assert_eq!(
+            vec![" a.esp", "+d.esp", " b.esp", " c.esp", "-d.esp", " e.esp"],
+            commands::diff_lines(&current, &proposed, false)
+        );
+        assert_eq!(
+            vec!["...", "+d.esp", "...", "-d.esp", "..."],
+            commands::diff_lines(&current, &proposed, true)
+        );
+        assert_eq!(vec!["d.esp"], commands::moved_plugins(&current, &proposed));
+        assert!(commands::diff_lines(&current, &current, true)
+            .iter()
+            .all(|l| l == "..."));
+    }
+
+    #[test]
+    fn test_case_conflicts() {
+        init();
+
+        let mods = ["A.esp", "b.esp", "a.esp", "c.esp"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![("A.esp".to_string(), "a.esp".to_string())],
+            detect_case_conflicts(&mods)
+        );
+
+        let mods = ["a.esp", "b.esp"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert!(detect_case_conflicts(&mods).is_empty());
+    }
+
     #[test]
     fn test_ordering() {
         init();